@@ -0,0 +1,70 @@
+use chrono::{DateTime, NaiveDateTime};
+use serde::de::DeserializeOwned;
+
+/// Minimal JSON client for the forge REST APIs, following `Link` headers
+/// to fetch every page of a listing.
+pub struct ApiClient {
+    headers: Vec<(&'static str, String)>,
+}
+
+impl ApiClient {
+    pub fn new(headers: Vec<(&'static str, String)>) -> Self {
+        ApiClient { headers }
+    }
+
+    fn get(&self, url: &str) -> Result<ureq::Response, Box<ureq::Error>> {
+        let mut request = ureq::get(url)
+            .set("User-Agent", concat!("commits-tilewall/", env!("CARGO_PKG_VERSION")));
+        for (name, value) in &self.headers {
+            request = request.set(name, value);
+        }
+        request.call().map_err(Box::new)
+    }
+
+    /// Fetches a single JSON document
+    pub fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, Box<ureq::Error>> {
+        self.get(url)?.into_json().map_err(|err| Box::new(ureq::Error::from(err)))
+    }
+
+    /// Fetches all pages of a listing, starting at `url`
+    pub fn get_all<T: DeserializeOwned>(&self, url: &str) -> Result<Vec<T>, Box<ureq::Error>> {
+        let mut items = Vec::new();
+        let mut next = Some(url.to_string());
+
+        while let Some(url) = next {
+            let response = self.get(&url)?;
+            next = response.header("Link").and_then(next_link);
+            let page: Vec<T> = response.into_json().map_err(|err| Box::new(ureq::Error::from(err)))?;
+            items.extend(page);
+        }
+
+        Ok(items)
+    }
+}
+
+/// Extracts the `rel="next"` URL from a `Link` header
+fn next_link(header: &str) -> Option<String> {
+    header.split(',').find_map(|link| {
+        let (url, params) = link.split_once(';')?;
+        params.contains("rel=\"next\"").then(|| {
+            url.trim().trim_start_matches('<').trim_end_matches('>').to_string()
+        })
+    })
+}
+
+/// Turns an API error into a message for the user
+pub fn describe_error(err: &ureq::Error) -> String {
+    match err {
+        ureq::Error::Status(401, _) => "authentication failed, check the API token".to_string(),
+        ureq::Error::Status(403, _) | ureq::Error::Status(429, _) => "access denied or rate limited, try passing a token".to_string(),
+        ureq::Error::Status(404, _) => "not found".to_string(),
+        ureq::Error::Status(code, _) => format!("unexpected HTTP status {}", code),
+        ureq::Error::Transport(transport) => transport.to_string(),
+    }
+}
+
+/// Parses an RFC 3339 timestamp such as `2024-03-01T10:00:00Z`, keeping
+/// the local time of its own UTC offset
+pub fn parse_api_timestamp(timestamp: &str) -> Option<NaiveDateTime> {
+    DateTime::parse_from_rfc3339(timestamp).ok().map(|datetime| datetime.naive_local())
+}
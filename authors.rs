@@ -0,0 +1,133 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::process::Command;
+
+use crate::collect::git_output;
+use crate::config::Config;
+use crate::error::Result;
+
+/// Name and email of a commit author
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Identity {
+    pub name: String,
+    pub email: String,
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} <{}>", self.name, self.email)
+    }
+}
+
+impl Identity {
+    /// Name in lower case with single spaces, so that "Jane  Doe" and
+    /// "jane doe" are the same person
+    fn normalized_name(&self) -> String {
+        self.name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+    }
+
+    /// Part of the email before the `@`, without the numeric id GitHub puts
+    /// in front of noreply addresses, like `jane` for
+    /// `12345+jane@users.noreply.github.com`
+    fn mailbox(&self) -> String {
+        let local = self.email.split('@').next().unwrap_or_default().to_lowercase();
+        match local.split_once('+') {
+            Some((id, login)) if id.chars().all(|c| c.is_ascii_digit()) => login.to_string(),
+            _ => local,
+        }
+    }
+
+    /// Identities sharing the email, the name or the mailbox are taken to
+    /// be the same person
+    fn is_same_person(&self, other: &Identity) -> bool {
+        self.email.eq_ignore_ascii_case(&other.email)
+            || self.normalized_name() == other.normalized_name()
+            || (!self.mailbox().is_empty() && self.mailbox() == other.mailbox())
+    }
+}
+
+/// Names of bots found in the author name or email of their commits,
+/// besides the `[bot]` suffix of GitHub apps
+const BOTS: [&str; 7] = [
+    "dependabot",
+    "renovate",
+    "github-actions",
+    "greenkeeper",
+    "snyk-bot",
+    "pre-commit-ci",
+    "mergify",
+];
+
+/// Whether a commit was made by a bot like dependabot or renovate rather
+/// than a person, or by one whose name or email matches any of the
+/// `patterns` of `--exclude-bots`
+pub fn is_bot(name: &str, email: &str, patterns: &[String]) -> bool {
+    let name = name.to_lowercase();
+    let email = email.to_lowercase();
+    name.ends_with("[bot]")
+        || email.contains("[bot]@")
+        || BOTS.iter().any(|bot| name.contains(bot) || email.contains(bot))
+        || patterns.iter().any(|pattern| matches_wildcards(&pattern.to_lowercase(), &name) || matches_wildcards(&pattern.to_lowercase(), &email))
+}
+
+/// Whether `text` matches the whole `pattern`, in which `*` stands for
+/// any text. Brackets are taken literally, so that `*[bot]*` matches
+/// `dependabot[bot]`.
+fn matches_wildcards(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No `*` at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// The authors of the commits matched by any of the `authors` patterns in
+/// all repositories
+pub fn matching_identities(authors: &[String], repos: &[String], config: &Config) -> Result<Vec<Identity>> {
+    let mut identities = BTreeSet::new();
+    for repo in repos {
+        let mut command = Command::new("git");
+        command
+            .args(["log", "--pretty=format:%an%x09%ae"])
+            .args(authors.iter().flat_map(|author| ["--author", author]))
+            .args(config.branches(repo))
+            .arg("--");
+        for line in git_output(&mut command, repo)?.lines() {
+            if let Some((name, email)) = line.split_once('\t') {
+                identities.insert(Identity { name: name.to_string(), email: email.to_string() });
+            }
+        }
+    }
+    Ok(identities.into_iter().collect())
+}
+
+/// Groups identities into the people they belong to, see
+/// `Identity::is_same_person`
+pub fn distinct_people(identities: &[Identity]) -> Vec<Vec<Identity>> {
+    let mut people: Vec<Vec<Identity>> = Vec::new();
+    for identity in identities {
+        // An identity may link several people found so far, which are
+        // merged into one then
+        let (same, others): (Vec<_>, Vec<_>) = people.into_iter()
+            .partition(|person| person.iter().any(|known| known.is_same_person(identity)));
+        let mut person: Vec<Identity> = same.into_iter().flatten().collect();
+        person.push(identity.clone());
+        person.sort();
+        people = others;
+        people.push(person);
+    }
+    people.sort();
+    people
+}
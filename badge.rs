@@ -0,0 +1,128 @@
+use std::fmt::Write;
+
+use ab_glyph::PxScale;
+use chrono::{Duration, NaiveDate};
+use image::Rgba;
+
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::fonts::FontChain;
+use crate::stats::YearStats;
+use crate::svg::{escape, hex, DEFAULT_FONT_FAMILY};
+use crate::theme::Theme;
+
+/// Height of the badge, the same as of shields.io badges
+const BADGE_HEIGHT: u32 = 20;
+
+/// Space left and right of both texts
+const BADGE_PADDING: u32 = 6;
+
+const FONT_SIZE: f32 = 11.0;
+
+/// What a badge shows, given with `--badge`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Badge {
+    /// Commits in the newest year, like "commits | 1,234 in 2024"
+    Commits,
+    /// Days in a row with commits up to today, like
+    /// "current streak | 17 days"
+    Streak,
+}
+
+impl Badge {
+    pub fn parse(kind: &str) -> Result<Badge> {
+        match kind {
+            "commits" => Ok(Badge::Commits),
+            "streak" => Ok(Badge::Streak),
+            _ => Err(Error::Usage(format!("Unknown badge '{}', expected commits or streak", kind))),
+        }
+    }
+
+    /// Label and message of the badge, `active_years` being ordered from
+    /// the newest year
+    pub fn texts(&self, activity: &Activity, active_years: &[YearStats], today: NaiveDate) -> (String, String) {
+        match self {
+            Badge::Commits => {
+                let message = match active_years.first() {
+                    Some(stats) => format!("{} in {}", thousands(stats.commits), stats.year),
+                    None => "none".to_string(),
+                };
+                ("commits".to_string(), message)
+            }
+            Badge::Streak => {
+                let days = current_streak(activity, today);
+                let message = if days == 1 { "1 day".to_string() } else { format!("{} days", days) };
+                ("current streak".to_string(), message)
+            }
+        }
+    }
+}
+
+/// Days in a row with commits that end today, or yesterday when there
+/// are no commits today yet
+fn current_streak(activity: &Activity, today: NaiveDate) -> u32 {
+    let has_commits = |date: &NaiveDate| activity.commits.get(date).is_some_and(|&count| count > 0);
+    let mut date = if has_commits(&today) { today } else { today - Duration::days(1) };
+    let mut days = 0;
+    while has_commits(&date) {
+        days += 1;
+        date -= Duration::days(1);
+    }
+    days
+}
+
+/// Formats a number with thousands separators, like 1,234
+fn thousands(number: i32) -> String {
+    let digits = number.unsigned_abs().to_string();
+    let mut formatted = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            formatted.push(',');
+        }
+        formatted.push(digit);
+    }
+    if number < 0 {
+        formatted.insert(0, '-');
+    }
+    formatted
+}
+
+/// Renders a badge in the flat style of shields.io, the label on gray and
+/// the message on the darkest commit color of the theme, so that white
+/// text stays readable with every theme
+pub fn generate_badge_svg(label: &str, message: &str, theme: &Theme, font: &FontChain) -> String {
+    // The text is measured with the font used for PNG output
+    let scale = PxScale::from(FONT_SIZE);
+    let label_width = font.text_width(label, scale).ceil() as u32 + BADGE_PADDING * 2;
+    let message_width = font.text_width(message, scale).ceil() as u32 + BADGE_PADDING * 2;
+    let width = label_width + message_width;
+    let color = theme.commit_colors[1..]
+        .iter()
+        .copied()
+        .min_by_key(|&color| luminance(color))
+        .unwrap_or(theme.commit_colors[0]);
+    let font_family = match font.family() {
+        Some(family) => format!("'{}', {}", family.replace('\'', ""), DEFAULT_FONT_FAMILY),
+        None => DEFAULT_FONT_FAMILY.to_string(),
+    };
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}" role="img" aria-label="{2}: {3}">"#, width, BADGE_HEIGHT, escape(label), escape(message)).unwrap();
+    writeln!(svg, "<title>{}: {}</title>", escape(label), escape(message)).unwrap();
+    writeln!(svg, r#"<clipPath id="r"><rect width="{}" height="{}" rx="3"/></clipPath>"#, width, BADGE_HEIGHT).unwrap();
+    svg.push_str("<g clip-path=\"url(#r)\">\n");
+    writeln!(svg, r##"<rect width="{}" height="{}" fill="#555555"/>"##, label_width, BADGE_HEIGHT).unwrap();
+    writeln!(svg, r#"<rect x="{}" width="{}" height="{}" fill="{}"/>"#, label_width, message_width, BADGE_HEIGHT, hex(color)).unwrap();
+    svg.push_str("</g>\n");
+    writeln!(svg, r##"<g fill="#ffffff" text-anchor="middle" font-family="{}" font-size="{}">"##, font_family, FONT_SIZE).unwrap();
+    writeln!(svg, r#"<text x="{}" y="14">{}</text>"#, label_width as f32 / 2.0, escape(label)).unwrap();
+    writeln!(svg, r#"<text x="{}" y="14">{}</text>"#, label_width as f32 + message_width as f32 / 2.0, escape(message)).unwrap();
+    svg.push_str("</g>\n");
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Perceived brightness of a color, from 0 to 255000
+fn luminance(color: Rgba<u8>) -> u32 {
+    299 * color[0] as u32 + 587 * color[1] as u32 + 114 * color[2] as u32
+}
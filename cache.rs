@@ -0,0 +1,299 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::collect::{git_output, repo_name, Commit};
+use crate::error::Result;
+use crate::filters::CommitFilter;
+use crate::log;
+use crate::remote;
+
+/// Version of the cache files, files of other versions are read again
+/// from the repository
+const CACHE_VERSION: u32 = 6;
+
+/// How the commits read from local repositories are cached between runs
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum CacheMode {
+    /// Read the cache when the branches haven't moved, write it otherwise
+    #[default]
+    Use,
+    /// Read the repositories again and rewrite the cache, `--refresh`
+    Refresh,
+    /// Neither read nor write the cache, `--no-cache`
+    Off,
+    /// Like `Use`, but once the branches have moved only the commits
+    /// since the cached ones are read, `--incremental`
+    Incremental,
+}
+
+/// Commits of one author in one repository, as stored in
+/// `$XDG_CACHE_HOME/commits-tilewall`
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    repo: PathBuf,
+    /// Patterns of `git log --author`
+    authors: Vec<String>,
+    /// Whether the commits co-authored by them are included, missing in
+    /// files written before
+    #[serde(default)]
+    coauthored: bool,
+    branches: Vec<String>,
+    /// Pathspecs of `--stats-exclude`, missing in files written before
+    #[serde(default)]
+    stats_exclude: Vec<String>,
+    /// Arguments of `git log` filtering the messages, see
+    /// `CommitFilter::grep_args`, missing in files written before
+    #[serde(default)]
+    grep: Vec<String>,
+    /// Patterns of `--exclude-bots`, missing in files written before
+    #[serde(default)]
+    bots: Vec<String>,
+    /// Commits the branches pointed to when the file was written
+    heads: String,
+    commits: Vec<Commit>,
+}
+
+/// Lines of the tree at HEAD of one repository blamed on one author, see
+/// `ownership::ownership`
+#[derive(Debug, Serialize, Deserialize)]
+struct OwnershipFile {
+    version: u32,
+    repo: PathBuf,
+    authors: Vec<String>,
+    /// Commit HEAD pointed to when the lines were blamed
+    head: String,
+    owned: u64,
+    total: u64,
+}
+
+/// The commits of `authors` in `repo`, from the cache when the scanned
+/// branches still point to the same commits, or else from `read`. `read`
+/// is given the commits whose history is already known, and skips them.
+/// The commits and their diff stats depend on the git side of `filter`, so
+/// it's part of the key too.
+pub fn commits(
+    authors: &[String],
+    repo: &str,
+    branches: &[String],
+    filter: &CommitFilter,
+    mode: CacheMode,
+    read: impl FnOnce(&[&str]) -> Result<Vec<Commit>>,
+) -> Result<Vec<Commit>> {
+    // Temporary clones of remote repositories get a new path every run
+    if mode == CacheMode::Off || remote::is_temporary_clone(repo) {
+        return read(&[]);
+    }
+    let (coauthored, stats_exclude, grep, bots) = (filter.include_coauthored, &filter.stats_exclude, filter.grep_args(), &filter.bot_patterns);
+    let Some(path) = cache_path(authors, repo, branches, filter) else {
+        return read(&[]);
+    };
+    let repo_path = canonical(repo);
+    let heads = heads(repo, branches)?;
+
+    let cached = match mode {
+        CacheMode::Use | CacheMode::Incremental => load::<CacheFile>(&path).filter(|cached| {
+            cached.version == CACHE_VERSION
+                && cached.repo == repo_path
+                && cached.authors == authors
+                && cached.coauthored == coauthored
+                && cached.branches == branches
+                && cached.stats_exclude == *stats_exclude
+                && cached.grep == grep
+                && cached.bots == *bots
+        }),
+        CacheMode::Refresh | CacheMode::Off => None,
+    };
+    let commits = match cached {
+        Some(cached) if cached.heads == heads => {
+            log::verbose!("Using the cached commits of {} from {}", repo, path.display());
+            return Ok(cached.commits);
+        }
+        Some(mut cached) if mode == CacheMode::Incremental && contains(repo, &heads, &cached.heads) => {
+            let known: Vec<&str> = cached.heads.lines().collect();
+            let mut commits = read(&known)?;
+            log::verbose!("Read {} new commits of {} since the cached ones", commits.len(), repo);
+            commits.append(&mut cached.commits);
+            commits
+        }
+        _ => read(&[])?,
+    };
+    let file = CacheFile {
+        version: CACHE_VERSION,
+        repo: repo_path,
+        authors: authors.to_vec(),
+        coauthored,
+        branches: branches.to_vec(),
+        stats_exclude: stats_exclude.to_vec(),
+        grep,
+        bots: bots.to_vec(),
+        heads,
+        commits,
+    };
+    if let Err(err) = store(&path, &file) {
+        log::warning!("Failed to write the cache file {}: {}", path.display(), err);
+    }
+    Ok(file.commits)
+}
+
+/// The lines owned by `authors` and all lines at HEAD of `repo`, from the
+/// cache when HEAD still points to the same commit, or else from `read`,
+/// as blaming the whole tree is slow
+pub fn ownership(authors: &[String], repo: &str, mode: CacheMode, read: impl FnOnce() -> Result<(u64, u64)>) -> Result<(u64, u64)> {
+    if mode == CacheMode::Off || remote::is_temporary_clone(repo) {
+        return read();
+    }
+    let Some(path) = ownership_cache_path(authors, repo) else {
+        return read();
+    };
+    let repo_path = canonical(repo);
+    let head = heads(repo, &[])?;
+
+    let cached = match mode {
+        CacheMode::Use | CacheMode::Incremental => load::<OwnershipFile>(&path).filter(|cached| {
+            cached.version == CACHE_VERSION
+                && cached.repo == repo_path
+                && cached.authors == authors
+                && cached.head == head
+        }),
+        CacheMode::Refresh | CacheMode::Off => None,
+    };
+    if let Some(cached) = cached {
+        log::verbose!("Using the cached ownership of {} from {}", repo, path.display());
+        return Ok((cached.owned, cached.total));
+    }
+    let (owned, total) = read()?;
+    let file = OwnershipFile {
+        version: CACHE_VERSION,
+        repo: repo_path,
+        authors: authors.to_vec(),
+        head,
+        owned,
+        total,
+    };
+    if let Err(err) = store(&path, &file) {
+        log::warning!("Failed to write the cache file {}: {}", path.display(), err);
+    }
+    Ok((owned, total))
+}
+
+/// The commits the scanned branches point to, one per line
+fn heads(repo: &str, branches: &[String]) -> Result<String> {
+    let mut command = Command::new("git");
+    command.arg("rev-parse");
+    if branches.is_empty() {
+        command.arg("HEAD");
+    } else {
+        command.args(branches);
+    }
+    git_output(&mut command, repo)
+}
+
+/// Whether the history of `heads` contains all commits of `known`, i.e.
+/// the branches only moved forward and nothing was rewritten since
+fn contains(repo: &str, heads: &str, known: &str) -> bool {
+    let mut command = Command::new("git");
+    command
+        .args(["rev-list", "--count"])
+        .args(known.lines())
+        .arg("--not")
+        .args(heads.lines());
+    // Fails as well when the known commits are gone from the repository
+    git_output(&mut command, repo).is_ok_and(|count| count.trim() == "0")
+}
+
+fn load<T: DeserializeOwned>(path: &Path) -> Option<T> {
+    let content = fs::read_to_string(path).ok()?;
+    match serde_json::from_str(&content) {
+        Ok(file) => Some(file),
+        Err(err) => {
+            log::verbose!("Ignoring the unreadable cache file {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+fn store(path: &Path, file: &impl Serialize) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string(file).map_err(std::io::Error::other)?;
+    // Write to a temporary file first, so that an interrupted run doesn't
+    // leave half a cache file behind
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, path)
+}
+
+/// One file per repository, authors and branches, named after the
+/// repository and a hash of all three and the git side of `filter`
+fn cache_path(authors: &[String], repo: &str, branches: &[String], filter: &CommitFilter) -> Option<PathBuf> {
+    let key = serde_json::json!([
+        canonical(repo).to_string_lossy(),
+        authors,
+        branches,
+        filter.stats_exclude,
+        filter.include_coauthored,
+        filter.grep_args(),
+        filter.bot_patterns,
+    ]);
+    let name = format!("{}-{}.json", repo_name(repo), key_hash(&key));
+    Some(cache_dir()?.join(name))
+}
+
+/// One file per repository and authors, next to the cached commits
+fn ownership_cache_path(authors: &[String], repo: &str) -> Option<PathBuf> {
+    let key = serde_json::json!([canonical(repo).to_string_lossy(), authors]);
+    let name = format!("{}-ownership-{}.json", repo_name(repo), key_hash(&key));
+    Some(cache_dir()?.join(name))
+}
+
+/// 16 hex digits of the SHA-256 of the key of a cache file, which unlike
+/// `DefaultHasher` stays the same across Rust releases
+fn key_hash(key: &serde_json::Value) -> String {
+    Sha256::digest(key.to_string()).iter().take(8).map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let cache_home = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_home.join("commits-tilewall"))
+}
+
+fn canonical(repo: &str) -> PathBuf {
+    Path::new(repo).canonicalize().unwrap_or_else(|_| PathBuf::from(repo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(authors: &[&str], branches: &[&str], filter: &CommitFilter) -> String {
+        let authors: Vec<String> = authors.iter().map(|author| author.to_string()).collect();
+        let branches: Vec<String> = branches.iter().map(|branch| branch.to_string()).collect();
+        let path = cache_path(&authors, "/nonexistent/tilewall", &branches, filter).unwrap();
+        path.file_name().unwrap().to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn keys_only_depend_on_the_arguments() {
+        let filter = CommitFilter::default();
+        let name = key(&["Jane"], &[], &filter);
+        assert_eq!(name, key(&["Jane"], &[], &filter));
+        assert!(name.starts_with("tilewall-") && name.ends_with(".json"));
+        assert_ne!(name, key(&["Joe"], &[], &filter));
+        assert_ne!(name, key(&["Jane"], &["main"], &filter));
+
+        // Stays the same across Rust releases, unlike `DefaultHasher`
+        assert_eq!(name, "tilewall-7826ac0bba9f2faf.json");
+        let filter = CommitFilter { include_coauthored: true, ..Default::default() };
+        assert_ne!(name, key(&["Jane"], &[], &filter));
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::BTreeSet;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+
+use crate::error::{Error, Result};
+
+/// Weekdays on which contributions are planned, given with `--cadence`
+#[derive(Debug, Clone)]
+pub struct Cadence {
+    weekdays: BTreeSet<u32>,
+}
+
+impl Cadence {
+    /// Parses a comma-separated list of weekdays such as `mon,wed,fri`.
+    /// `weekdays`, `weekends` and `daily` are accepted as shortcuts.
+    pub fn parse(spec: &str) -> Result<Cadence> {
+        let mut weekdays = BTreeSet::new();
+        for name in spec.split(',').map(|name| name.trim().to_lowercase()) {
+            match name.as_str() {
+                "daily" => weekdays.extend(0..7),
+                "weekdays" => weekdays.extend(0..5),
+                "weekends" => weekdays.extend(5..7),
+                _ => {
+                    let weekday: Weekday = name.parse()
+                        .map_err(|_| Error::Usage(format!("Invalid weekday '{}' in --cadence", name)))?;
+                    weekdays.insert(weekday.num_days_from_monday());
+                }
+            }
+        }
+
+        Ok(Cadence { weekdays })
+    }
+
+    pub fn is_planned(&self, date: &NaiveDate) -> bool {
+        self.weekdays.contains(&date.weekday().num_days_from_monday())
+    }
+
+    /// The planned weekdays, Monday first, named in the summary with
+    /// `Locale::adherence`
+    pub fn weekdays(&self) -> Vec<Weekday> {
+        self.weekdays.iter()
+            .filter_map(|&day| Weekday::try_from(day as u8).ok())
+            .collect()
+    }
+}
+
+/// How many of the planned days in a period actually had commits
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Adherence {
+    pub planned_days: usize,
+    pub hit_days: usize,
+}
+
+impl Adherence {
+    pub fn percent(&self) -> f64 {
+        if self.planned_days == 0 {
+            return 0.0;
+        }
+        self.hit_days as f64 * 100.0 / self.planned_days as f64
+    }
+}
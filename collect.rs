@@ -0,0 +1,588 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output};
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+use crate::authors;
+use crate::cache::{self, CacheMode};
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::filters::CommitFilter;
+use crate::languages;
+use crate::log;
+use crate::render::Release;
+
+/// Files and lines changed, summed over a commit, a day or a year
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct DiffStats {
+    /// Text files, whose lines are counted
+    pub files: i32,
+    pub insertions: i32,
+    pub deletions: i32,
+    /// Binary files, which have no lines
+    pub binary_files: i32,
+}
+
+impl DiffStats {
+    pub fn add(&mut self, other: &DiffStats) {
+        self.files += other.files;
+        self.insertions += other.insertions;
+        self.deletions += other.deletions;
+        self.binary_files += other.binary_files;
+    }
+}
+
+/// A commit read from a local repository
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Commit {
+    /// Commit time in the local time of the committer
+    pub time: NaiveDateTime,
+    pub diff_stats: DiffStats,
+    /// Lines changed per language, see `languages::language_of`
+    pub languages: BTreeMap<String, i32>,
+    /// Made by a bot matching the author, see `authors::is_bot`
+    pub bot: bool,
+    /// Reverting another commit, see `is_revert`
+    pub revert: bool,
+}
+
+/// Commit activity of one author, merged over all repositories
+#[derive(Debug, Default)]
+pub struct Activity {
+    /// Commits per day, after applying the repository weights
+    pub commits: HashMap<NaiveDate, i32>,
+    /// Commit times in the local time of the committer, unweighted
+    pub timestamps: Vec<NaiveDateTime>,
+    pub diff_stats: HashMap<NaiveDate, DiffStats>,
+    /// Lines changed per year and language, for files of known languages
+    pub language_lines: HashMap<i32, HashMap<String, i32>>,
+    /// False for sources that only report commit dates
+    pub has_diff_stats: bool,
+    /// Repositories with a configured weight other than 1
+    pub weighted_repos: Vec<(String, f64)>,
+    /// Commits per year of every repository, unweighted
+    pub repo_commits: Vec<(String, HashMap<i32, i32>)>,
+    /// Repositories whose history starts with an import, with its date,
+    /// see `find_import`
+    pub imports: Vec<(String, NaiveDate)>,
+    /// Commits of the author that aren't counted, by reason
+    pub skipped: SkippedCommits,
+}
+
+/// Commits left out by the filters, reported so that it's clear why the
+/// wall shows fewer commits than `git log`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SkippedCommits {
+    /// Outside the years of `--years`
+    pub years: usize,
+    /// Matching none of the `--only` filters
+    pub only: usize,
+    /// Made by bots, without `--include-bots`
+    pub bots: usize,
+    /// Reverting other commits, with `--no-reverts`
+    pub reverts: usize,
+}
+
+impl SkippedCommits {
+    /// Whether the filter counts a commit made at `timestamp`, noting the
+    /// reason when it doesn't
+    pub fn keep(&mut self, filter: &CommitFilter, timestamp: &NaiveDateTime) -> bool {
+        if !filter.matches_year(timestamp) {
+            self.years += 1;
+            false
+        } else if !filter.matches_time(timestamp) {
+            self.only += 1;
+            false
+        } else {
+            true
+        }
+    }
+
+    pub fn add(&mut self, other: &SkippedCommits) {
+        self.years += other.years;
+        self.only += other.only;
+        self.bots += other.bots;
+        self.reverts += other.reverts;
+    }
+}
+
+impl Activity {
+    /// Builds the activity of a source that only reports commit times,
+    /// keeping the commits matching `filter`
+    pub fn from_timestamps(timestamps: impl IntoIterator<Item = NaiveDateTime>, filter: &CommitFilter) -> Activity {
+        let mut skipped = SkippedCommits::default();
+        let timestamps: Vec<NaiveDateTime> = timestamps.into_iter()
+            .filter(|timestamp| skipped.keep(filter, timestamp))
+            .collect();
+        let mut commits = HashMap::new();
+        for timestamp in &timestamps {
+            *commits.entry(timestamp.date()).or_insert(0) += 1;
+        }
+
+        Activity {
+            commits,
+            timestamps,
+            skipped,
+            ..Default::default()
+        }
+    }
+
+    /// Made-up commits of two years, covering every commit level, to
+    /// preview themes without scanning a repository. The same commits are
+    /// made up every time.
+    pub fn sample() -> Activity {
+        let mut seed: u32 = 0x2545_f491;
+        let mut random = move |bound: u32| {
+            // xorshift32
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed % bound
+        };
+        let mut timestamps = Vec::new();
+        let mut date = NaiveDate::from_ymd_opt(2023, 1, 1).expect("valid date");
+        while date.year() < 2025 {
+            let busy = date.weekday().number_from_monday() <= 5;
+            let count = match random(10) {
+                0..=2 => 0,
+                3..=5 if busy => 1 + random(4),
+                6..=7 if busy => 5 + random(15),
+                8 if busy => 20 + random(10),
+                _ => random(2),
+            };
+            for i in 0..count {
+                timestamps.push(date.and_hms_opt(9 + i % 10, random(60), 0).expect("valid time"));
+            }
+            date = date.succ_opt().expect("valid date");
+        }
+        Activity::from_timestamps(timestamps, &CommitFilter::default())
+    }
+}
+
+/// Number of commits per year
+pub fn commits_per_year<'a>(timestamps: impl IntoIterator<Item = &'a NaiveDateTime>) -> HashMap<i32, i32> {
+    let mut years = HashMap::new();
+    for timestamp in timestamps {
+        *years.entry(timestamp.year()).or_insert(0) += 1;
+    }
+    years
+}
+
+/// Short name of a repository used in labels, e.g. `linux` for `~/src/linux`
+pub fn repo_name(repo: &str) -> String {
+    Path::new(repo)
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_end_matches(".git").to_string())
+        .unwrap_or_else(|| repo.to_string())
+}
+
+/// Outputs of git commands by the repository and the arguments
+type GitOutputs = HashMap<(String, Vec<OsString>), String>;
+
+thread_local! {
+    /// Outputs of the git commands run so far while the outputs are shared,
+    /// see `sharing_git_outputs`
+    static GIT_OUTPUTS: RefCell<Option<GitOutputs>> = const { RefCell::new(None) };
+}
+
+/// Runs a git command in `repo` and returns its output, turning failures
+/// into messages for the user. Repositories without any commits yet give
+/// an empty output instead of an error. Within `sharing_git_outputs` the
+/// output of the same command in the same repository is reused.
+pub fn git_output(command: &mut Command, repo: &str) -> Result<String> {
+    let key = (repo.to_string(), command.get_args().map(OsStr::to_os_string).collect());
+    if let Some(output) = GIT_OUTPUTS.with_borrow(|outputs| outputs.as_ref()?.get(&key).cloned()) {
+        return Ok(output);
+    }
+    let output = run_git(command, repo)?;
+    GIT_OUTPUTS.with_borrow_mut(|outputs| outputs.as_mut().map(|outputs| outputs.insert(key, output.clone())));
+    Ok(output)
+}
+
+/// Runs `f` with the outputs of `git_output` kept and reused, so that the
+/// walls of a workspace sharing authors and repositories only scan them
+/// once. The outputs are dropped again once `f` returns.
+pub fn sharing_git_outputs<T>(f: impl FnOnce() -> T) -> T {
+    GIT_OUTPUTS.set(Some(HashMap::new()));
+    let result = f();
+    GIT_OUTPUTS.set(None);
+    result
+}
+
+/// Like `git_output`, but always runs the command
+pub fn run_git(command: &mut Command, repo: &str) -> Result<String> {
+    command.current_dir(repo);
+    log::command(command);
+    let output = match git_process(command) {
+        Ok(output) => output,
+        Err(_) if !Path::new(repo).is_dir() => return Err(Error::NotARepository(repo.to_string())),
+        Err(err) => return Err(Error::Git(format!("Failed to run git in {}: {}", repo, err))),
+    };
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if stderr.contains("not a git repository") {
+            return Err(Error::NotARepository(repo.to_string()));
+        }
+        if !has_commits(repo) {
+            return Ok(String::new());
+        }
+        return Err(Error::Git(format!("git failed in {}: {}", repo, stderr.trim())));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs the git command to its end, the only place git processes are
+/// started, so that builds without the `git-cli` feature start none
+#[cfg(feature = "git-cli")]
+pub fn git_process(command: &mut Command) -> io::Result<Output> {
+    command.output()
+}
+
+#[cfg(not(feature = "git-cli"))]
+pub fn git_process(_command: &mut Command) -> io::Result<Output> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "git repositories need a build with the git-cli feature"))
+}
+
+fn has_commits(repo: &str) -> bool {
+    git_process(Command::new("git").args(["rev-list", "-n", "1", "--all"]).current_dir(repo))
+        .is_ok_and(|output| output.status.success() && !output.stdout.is_empty())
+}
+
+/// Root commits changing at least this many files are taken as imports
+const IMPORT_MIN_FILES: u32 = 100;
+
+/// Date of the oldest root commit when it looks like the import of a
+/// history that lived elsewhere before, like a migration that lost the
+/// earlier commits
+pub fn find_import(repo: &str, branches: &[String]) -> Result<Option<NaiveDate>> {
+    let mut command = Command::new("git");
+    command
+        .args(["log", "--max-parents=0", "--pretty=format:%cI", "--shortstat"])
+        .args(branches)
+        .arg("--");
+    let output = git_output(&mut command, repo)?;
+
+    let mut roots: Vec<(NaiveDate, u32)> = Vec::new();
+    for line in output.lines() {
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(line) {
+            roots.push((datetime.naive_local().date(), 0));
+        } else if let (Some(root), Some(files)) = (roots.last_mut(), line.trim().split(' ').next()) {
+            root.1 = files.parse().unwrap_or(root.1);
+        }
+    }
+
+    Ok(roots.into_iter()
+        .min_by_key(|&(date, _)| date)
+        .filter(|&(_, files)| files >= IMPORT_MIN_FILES)
+        .map(|(date, _)| date))
+}
+
+pub fn count_commits(repo: &str, branches: &[String]) -> Result<u64> {
+    let mut command = Command::new("git");
+    command.args(["rev-list", "--count"]);
+    if branches.is_empty() {
+        command.arg("HEAD");
+    } else {
+        command.args(branches);
+    }
+    command.arg("--");
+
+    Ok(git_output(&mut command, repo)?.trim().parse().unwrap_or(0))
+}
+
+/// The tags of a repository as releases on the day they were made, which
+/// is the date of the tagged commit for lightweight tags, see `--show-tags`
+pub fn tags(repo: &str) -> Result<Vec<Release>> {
+    let mut command = Command::new("git");
+    command.args(["for-each-ref", "--sort=creatordate", "--format=%(creatordate:iso-strict)%09%(refname:short)", "refs/tags"]);
+    let output = git_output(&mut command, repo)?;
+    Ok(output.lines()
+        .filter_map(|line| {
+            let (date, name) = line.split_once('\t')?;
+            Some(Release {
+                project: repo_name(repo),
+                version: name.to_string(),
+                date: DateTime::parse_from_rfc3339(date).ok()?.naive_local().date(),
+            })
+        })
+        .collect())
+}
+
+/// `git log` of the commits matching any of the `authors` patterns in
+/// `repo`, or with `coauthored` of the commits crediting them in a
+/// `Co-authored-by:` trailer, leaving out the history of the `known`
+/// commits
+fn log_command(authors: &[String], coauthored: bool, branches: &[String], known: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command.arg("log");
+    if coauthored {
+        command.args(authors.iter().map(|author| format!("--grep={}", coauthor_pattern(author))));
+    } else {
+        command.args(authors.iter().flat_map(|author| ["--author", author]));
+    }
+    command.args(branches);
+    if !known.is_empty() {
+        // git only falls back to HEAD without any revisions at all
+        if branches.is_empty() {
+            command.arg("HEAD");
+        }
+        command.arg("--not").args(known);
+    }
+    command
+}
+
+/// Pattern of `git log --grep` matching a `Co-authored-by:` trailer of
+/// the author `pattern`. Like with `--author`, it's matched against
+/// `Name <email>` unless it's anchored at the start.
+fn coauthor_pattern(pattern: &str) -> String {
+    match pattern.strip_prefix('^') {
+        Some(anchored) => format!("^[Cc]o-[Aa]uthored-[Bb]y:[[:space:]]*{}", anchored),
+        None => format!("^[Cc]o-[Aa]uthored-[Bb]y:.*{}", pattern),
+    }
+}
+
+/// Reads the commits matching any of the `authors` patterns and the message
+/// filter of `filter` in `repo` along with the files and lines they changed
+/// outside of its `stats_exclude` pathspecs, leaving out the history of the
+/// `known` commits. With `include_coauthored`, the commits crediting the
+/// authors as co-authors are read too, once each.
+fn read_commits(authors: &[String], repo: &str, branches: &[String], filter: &CommitFilter, known: &[&str]) -> Result<Vec<Commit>> {
+    let stats_exclude = &filter.stats_exclude;
+    // git looks for the co-authors with --grep too, and would match commits
+    // with either, so the commits of the message filter are listed apart
+    let matching: Option<HashSet<String>> = match filter.grep_args() {
+        grep if filter.include_coauthored && !grep.is_empty() => {
+            let mut command = log_command(&[], false, branches, known);
+            command.args(grep).args(["--pretty=format:%H", "--"]);
+            Some(git_output(&mut command, repo)?.lines().map(str::to_string).collect())
+        }
+        _ => None,
+    };
+
+    let mut commits: Vec<Commit> = Vec::new();
+    let mut hashes: HashMap<String, usize> = HashMap::new();
+    for coauthored in [false, true].into_iter().take(1 + filter.include_coauthored as usize) {
+        let mut command = log_command(authors, coauthored, branches, known);
+        if !coauthored {
+            command.args(filter.grep_args());
+        }
+        command.arg("--pretty=format:%cI%x09%an%x09%ae%x09%H%x09%s");
+        if stats_exclude.is_empty() {
+            command.arg("--numstat");
+        }
+        command.arg("--");
+        let output = git_output(&mut command, repo)?;
+
+        // Commits read before, i.e. authored ones listed again as
+        // co-authored, are skipped along with their stats
+        let first = commits.len();
+        let mut current = None;
+        for line in output.lines() {
+            // Times are kept in the committer's own time zone
+            let mut fields = line.split('\t');
+            if let (Some(Ok(datetime)), Some(name), Some(email), Some(hash), subject) = (fields.next().map(DateTime::parse_from_rfc3339), fields.next(), fields.next(), fields.next(), fields.next()) {
+                current = None;
+                if hashes.contains_key(hash) || matching.as_ref().is_some_and(|matching| !matching.contains(hash)) {
+                    continue;
+                }
+                hashes.insert(hash.to_string(), commits.len());
+                current = Some(commits.len());
+                commits.push(Commit {
+                    time: datetime.naive_local(),
+                    diff_stats: DiffStats::default(),
+                    languages: BTreeMap::new(),
+                    bot: authors::is_bot(name, email, &filter.bot_patterns),
+                    revert: subject.is_some_and(is_revert),
+                });
+            } else if let Some(index) = current {
+                add_numstat(&mut commits[index], line);
+            }
+        }
+        if stats_exclude.is_empty() {
+            continue;
+        }
+
+        // Passing the pathspecs to the log above would leave out the commits
+        // only changing excluded files, so their stats are read separately.
+        // The hashes are marked with a NUL, which no numstat line holds, as
+        // they are 40 or 64 digits long depending on the object format.
+        let mut command = log_command(authors, coauthored, branches, known);
+        command
+            .args(["--pretty=format:%x00%H", "--numstat", "--"])
+            .args(stats_exclude.iter().map(|pathspec| format!(":(exclude){}", pathspec)));
+        let output = git_output(&mut command, repo)?;
+        let mut current = None;
+        for line in output.lines() {
+            if let Some(hash) = line.strip_prefix('\0') {
+                current = hashes.get(hash).copied().filter(|&index| index >= first);
+            } else if let Some(index) = current {
+                add_numstat(&mut commits[index], line);
+            }
+        }
+    }
+    Ok(commits)
+}
+
+/// Whether a commit with the `subject` reverts another one, as written by
+/// `git revert`
+fn is_revert(subject: &str) -> bool {
+    subject.starts_with("Revert \"")
+}
+
+/// Adds a line of `git log --numstat` to the stats of its commit, binary
+/// files are listed with - instead of their lines
+fn add_numstat(commit: &mut Commit, line: &str) {
+    let parts: Vec<&str> = line.splitn(3, '\t').collect();
+    if parts.len() != 3 {
+        return;
+    }
+    if parts[0] == "-" && parts[1] == "-" {
+        commit.diff_stats.add(&DiffStats { binary_files: 1, ..Default::default() });
+    } else if let (Ok(added), Ok(deleted)) = (parts[0].parse::<i32>(), parts[1].parse::<i32>()) {
+        commit.diff_stats.add(&DiffStats { files: 1, insertions: added, deletions: deleted, binary_files: 0 });
+        // Renames are listed as "old => new", the extension is the new one
+        if let Some(language) = languages::language_of(parts[2].trim_end_matches('}')) {
+            *commit.languages.entry(language.name.to_string()).or_insert(0) += added + deleted;
+        }
+    }
+}
+
+pub fn collect_activity(authors: &[String], repos: &[String], config: &Config, filter: &CommitFilter, cache_mode: CacheMode) -> Result<Activity> {
+    // Collect commit dates and stats at the start, with every commit
+    // counting as the configured weight of its repository
+    let mut weighted_counts: HashMap<NaiveDate, f64> = HashMap::new();
+    let mut timestamps: Vec<NaiveDateTime> = Vec::new();
+    let mut diff_stats: HashMap<NaiveDate, DiffStats> = HashMap::new();
+    let mut language_lines: HashMap<i32, HashMap<String, i32>> = HashMap::new();
+    let mut repo_commits = Vec::new();
+    let mut skipped = SkippedCommits::default();
+    let mut bot_commits = 0;
+
+    for repo in repos {
+        // Scan the configured branches, or HEAD when none are set
+        let branches = config.branches(repo);
+        let weight = config.weight(repo);
+
+        log::info!("Collecting commit dates for repo: {}", repo);
+        let commits = cache::commits(authors, repo, branches, filter, cache_mode, |known| read_commits(authors, repo, branches, filter, known))?;
+        let repo_start = timestamps.len();
+        for commit in commits.iter() {
+            if !skipped.keep(filter, &commit.time) {
+                continue;
+            }
+            if commit.bot {
+                bot_commits += 1;
+                if !filter.include_bots {
+                    skipped.bots += 1;
+                    continue;
+                }
+            }
+            if commit.revert && filter.no_reverts {
+                skipped.reverts += 1;
+                continue;
+            }
+            let date = commit.time.date();
+            *weighted_counts.entry(date).or_insert(0.0) += weight;
+            timestamps.push(commit.time);
+            if commit.diff_stats.files > 0 || commit.diff_stats.binary_files > 0 {
+                diff_stats.entry(date).or_default().add(&commit.diff_stats);
+            }
+            for (language, lines) in &commit.languages {
+                *language_lines.entry(date.year()).or_default().entry(language.clone()).or_insert(0) += lines;
+            }
+        }
+        log::verbose!("Found {} commits in {}", timestamps.len() - repo_start, repo);
+        repo_commits.push((repo_name(repo), commits_per_year(&timestamps[repo_start..])));
+    }
+    // Bots left out are reported along with the other skipped commits
+    if bot_commits > 0 && filter.include_bots {
+        log::info!("Counted {} commits by bots like dependabot or renovate", bot_commits);
+    }
+
+    // Count commits per day, keeping days with only down-weighted commits
+    // visible
+    let commits = weighted_counts
+        .iter()
+        .map(|(&date, &count)| (date, (count.round() as i32).max(1)))
+        .collect();
+
+    let weighted_repos = repos.iter()
+        .filter(|repo| config.weight(repo) != 1.0)
+        .map(|repo| (repo_name(repo), config.weight(repo)))
+        .collect();
+
+    Ok(Activity {
+        commits,
+        timestamps,
+        diff_stats,
+        language_lines,
+        has_diff_stats: true,
+        weighted_repos,
+        repo_commits,
+        imports: Vec::new(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit() -> Commit {
+        Commit {
+            time: NaiveDate::from_ymd_opt(2024, 3, 4).unwrap().and_hms_opt(12, 0, 0).unwrap(),
+            diff_stats: DiffStats::default(),
+            languages: BTreeMap::new(),
+            bot: false,
+            revert: false,
+        }
+    }
+
+    #[test]
+    fn numstat_counts_binary_files_apart() {
+        let mut commit = commit();
+        for line in ["12\t3\tREADME", "-\t-\tlogo.png", "0\t7\tdocs/old.txt", "not\ta numstat line", ""] {
+            add_numstat(&mut commit, line);
+        }
+        let DiffStats { files, insertions, deletions, binary_files } = commit.diff_stats;
+        assert_eq!((files, insertions, deletions, binary_files), (2, 12, 10, 1));
+    }
+    #[test]
+    fn numstat_lines_count_per_language() {
+        let mut commit = commit();
+        for line in ["10\t2\tsrc/main.rs", "1\t1\t{old.py => src/new.rs}", "4\t0\ttools/gen.py", "5\t5\tLICENSE", "-\t-\tlogo.rs"] {
+            add_numstat(&mut commit, line);
+        }
+        assert_eq!(commit.languages, BTreeMap::from([("Python".to_string(), 4), ("Rust".to_string(), 14)]));
+    }
+    #[test]
+    fn coauthors_are_looked_for_in_the_trailers() {
+        assert_eq!(coauthor_pattern("jane@example.com"), "^[Cc]o-[Aa]uthored-[Bb]y:.*jane@example.com");
+        assert_eq!(coauthor_pattern("^Jane Doe"), "^[Cc]o-[Aa]uthored-[Bb]y:[[:space:]]*Jane Doe");
+
+        let authors = ["Jane".to_string(), "^Joe".to_string()];
+        let args = |coauthored| {
+            log_command(&authors, coauthored, &[], &[]).get_args().map(|arg| arg.to_str().unwrap().to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(args(false), ["log", "--author", "Jane", "--author", "^Joe"]);
+        assert_eq!(args(true), [
+            "log",
+            "--grep=^[Cc]o-[Aa]uthored-[Bb]y:.*Jane",
+            "--grep=^[Cc]o-[Aa]uthored-[Bb]y:[[:space:]]*Joe",
+        ]);
+    }
+    #[test]
+    fn reverts_are_told_by_their_subject() {
+        assert!(is_revert("Revert \"Add the weeks layout\""));
+        assert!(is_revert("Revert \"Revert \"Add the weeks layout\"\""));
+        assert!(!is_revert("Reverted the layout change"));
+        assert!(!is_revert("Fix the revert of \"Add the weeks layout\""));
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+
+/// Settings read from `config.toml`, e.g.
+///
+/// ```toml
+/// [repos."~/src/linux"]
+/// branches = ["master"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub repos: HashMap<String, RepoConfig>,
+    /// Named sets of walls rendered together with `run --workspace`
+    #[serde(default)]
+    pub workspaces: HashMap<String, Vec<Job>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Branches to scan instead of the checked out HEAD
+    #[serde(default)]
+    pub branches: Vec<String>,
+    /// Factor applied to the commit counts of this repository
+    pub weight: Option<f64>,
+}
+
+/// One wall of a workspace, e.g.
+///
+/// ```toml
+/// [[workspaces.oss]]
+/// author = "Jane Doe"
+/// repos = ["~/src/linux", "~/src/firmware"]
+/// theme = "dark"
+/// output = "~/walls/jane.png"
+/// args = ["--no-legend"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Job {
+    pub author: String,
+    #[serde(default)]
+    pub repos: Vec<String>,
+    pub theme: Option<String>,
+    pub output: Option<String>,
+    /// Further command line options of this wall
+    #[serde(default)]
+    pub args: Vec<String>,
+}
+
+impl Job {
+    /// The command line arguments rendering this wall, after the program
+    /// name
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![self.author.clone()];
+        args.extend(self.repos.iter().map(|repo| expand_tilde(repo).to_string_lossy().into_owned()));
+        if let Some(theme) = &self.theme {
+            args.extend(["--theme".to_string(), theme.clone()]);
+        }
+        if let Some(output) = &self.output {
+            args.extend(["--output".to_string(), expand_tilde(output).to_string_lossy().into_owned()]);
+        }
+        args.extend(self.args.iter().cloned());
+        args
+    }
+}
+
+impl Config {
+    /// Loads the config file given with `--config`, or the default one in
+    /// `$XDG_CONFIG_HOME/commits-tilewall/config.toml` when it exists.
+    pub fn load(path: Option<&str>) -> Result<Config> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match default_config_path() {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|err| Error::Input(format!("Failed to read config file {}: {}", path.display(), err)))?;
+        let config: Config = toml::from_str(&content)
+            .map_err(|err| Error::Input(format!("Failed to parse config file {}: {}", path.display(), err)))?;
+
+        for (repo, repo_config) in &config.repos {
+            if repo_config.weight.is_some_and(|weight| !weight.is_finite() || weight < 0.0) {
+                return Err(Error::Input(format!("Invalid weight for repository {} in {}", repo, path.display())));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Looks up the settings of a repository, matching the config keys and
+    /// the given path after expanding `~` and resolving symlinks.
+    pub fn repo(&self, repo: &str) -> Option<&RepoConfig> {
+        let wanted = resolve_path(repo);
+        self.repos.iter()
+            .find(|(key, _)| resolve_path(key) == wanted)
+            .map(|(_, repo_config)| repo_config)
+    }
+
+    pub fn workspace(&self, name: &str) -> Result<&[Job]> {
+        self.workspaces.get(name)
+            .map(Vec::as_slice)
+            .ok_or_else(|| Error::Usage(format!("No workspace '{}' in the config file", name)))
+    }
+
+    pub fn branches(&self, repo: &str) -> &[String] {
+        self.repo(repo).map(|repo_config| repo_config.branches.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn weight(&self, repo: &str) -> f64 {
+        self.repo(repo).and_then(|repo_config| repo_config.weight).unwrap_or(1.0)
+    }
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("config.toml"))
+}
+
+/// `$XDG_CONFIG_HOME/commits-tilewall`, or `~/.config/commits-tilewall`
+pub fn config_dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_home.join("commits-tilewall"))
+}
+
+pub fn expand_tilde(path: &str) -> PathBuf {
+    match (path.strip_prefix('~'), env::var_os("HOME")) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            Path::new(&home).join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The absolute path of a path given by the user, after expanding `~`,
+/// resolving it against the current directory and resolving symlinks.
+/// Paths that don't exist are only made absolute.
+pub fn resolve_path(path: &str) -> PathBuf {
+    let path = expand_tilde(path);
+    let path = match env::current_dir() {
+        Ok(dir) if path.is_relative() => dir.join(path),
+        _ => path,
+    };
+    path.canonicalize().unwrap_or(path)
+}
@@ -0,0 +1,50 @@
+use serde::Deserialize;
+
+use crate::api::{describe_error, parse_api_timestamp, ApiClient};
+use crate::error::{Error, Result};
+use crate::render::Release;
+
+const BASE_URL: &str = "https://crates.io";
+
+#[derive(Deserialize)]
+struct VersionsPage {
+    versions: Vec<Version>,
+    #[serde(default)]
+    meta: Meta,
+}
+
+#[derive(Deserialize)]
+struct Version {
+    num: String,
+    created_at: String,
+    #[serde(default)]
+    yanked: bool,
+}
+
+#[derive(Default, Deserialize)]
+struct Meta {
+    /// Query of the next page, like `?page=2&per_page=100`
+    next_page: Option<String>,
+}
+
+/// The published versions of a crate on crates.io, given with `--crate`,
+/// leaving out yanked ones
+pub fn releases(name: &str) -> Result<Vec<Release>> {
+    let client = ApiClient::new(Vec::new());
+    let url = format!("{}/api/v1/crates/{}/versions", BASE_URL, name);
+    let mut next = Some(format!("{}?per_page=100", url));
+    let mut releases = Vec::new();
+    while let Some(page_url) = next {
+        let page: VersionsPage = client.get_json(&page_url)
+            .map_err(|err| Error::Api(format!("Failed to read the versions of crate {}: {}", name, describe_error(&err))))?;
+        releases.extend(page.versions.into_iter()
+            .filter(|version| !version.yanked)
+            .filter_map(|version| Some(Release {
+                project: name.to_string(),
+                version: version.num,
+                date: parse_api_timestamp(&version.created_at)?.date(),
+            })));
+        next = page.meta.next_page.map(|query| format!("{}{}", url, query));
+    }
+    Ok(releases)
+}
@@ -0,0 +1,82 @@
+use ab_glyph::PxScale;
+use chrono::{Datelike, Duration, NaiveDate};
+use image::{ImageBuffer, Rgba};
+
+use crate::collect::Activity;
+use crate::fonts::FontChain;
+use crate::locale::Locale;
+use crate::panels::{draw_line_chart, Panel};
+use crate::render::{draw_sharp_text, Grid};
+use crate::stats::{YearStats, LEVELS};
+use crate::theme::Theme;
+
+/// Width of the chart, which is the width of the panel, and its height
+/// between the title and the dates, scaled with the labels, see `Grid::px`
+const CHART_WIDTH: u32 = 180;
+const CHART_HEIGHT: u32 = 40;
+
+/// The commits of all years added up over time, drawn next to the summary
+/// of the newest year only, see `--cumulative`
+#[derive(Debug)]
+pub struct CumulativePanel {
+    pub year: i32,
+    locale: Locale,
+    first: NaiveDate,
+    last: NaiveDate,
+    total: i32,
+    /// Commits up to the day of every column of the chart
+    totals: Vec<f32>,
+}
+
+impl CumulativePanel {
+    /// The panel of all commits of the activity, `None` without commits
+    pub fn new(year: i32, activity: &Activity, locale: Locale) -> Option<CumulativePanel> {
+        let mut days: Vec<(NaiveDate, i32)> = activity.commits.iter().map(|(&date, &count)| (date, count)).collect();
+        days.sort_unstable();
+        let (first, last) = (days.first()?.0, days.last()?.0);
+        let mut running = 0;
+        let sums: Vec<(NaiveDate, i32)> = days.into_iter()
+            .map(|(date, count)| {
+                running += count;
+                (date, running)
+            })
+            .collect();
+
+        let span = (last - first).num_days();
+        let totals = (0..CHART_WIDTH)
+            .map(|column| {
+                let date = first + Duration::days((column as i64 * span) / (CHART_WIDTH - 1) as i64);
+                let days_before = sums.partition_point(|(day, _)| *day <= date);
+                days_before.checked_sub(1).map_or(0.0, |i| sums[i].1 as f32)
+            })
+            .collect();
+        Some(CumulativePanel { year, locale, first, last, total: running, totals })
+    }
+}
+
+impl Panel for CumulativePanel {
+    fn measure(&self, stats: &YearStats, grid: &Grid) -> u32 {
+        if stats.year == self.year { grid.px(CHART_WIDTH) } else { 0 }
+    }
+
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain) {
+        if stats.year != self.year {
+            return;
+        }
+        let line_height = grid.px(12);
+        let text_size = grid.label_unit as f32 * 0.8;
+        let (chart_width, chart_height) = (grid.px(CHART_WIDTH), grid.px(CHART_HEIGHT));
+        let title = format!("{} commits since {}", self.locale.number(self.total), self.first.year());
+        draw_sharp_text(img, &title, x as i32, y as i32, text_size, theme.text_primary, font);
+
+        let chart_y = y + line_height;
+        draw_line_chart(img, x, chart_y, chart_width, chart_height, &self.totals, theme.commit_colors[LEVELS], Some(theme.commit_colors[1]));
+
+        let dates_y = (chart_y + chart_height + grid.px(2)) as i32;
+        let first = self.first.format("%b %Y").to_string();
+        let last = self.last.format("%b %Y").to_string();
+        let last_width = font.text_width(&last, PxScale::from(text_size)).ceil() as u32;
+        draw_sharp_text(img, &first, x as i32, dates_y, text_size, theme.text_secondary, font);
+        draw_sharp_text(img, &last, (x + chart_width).saturating_sub(last_width) as i32, dates_y, text_size, theme.text_secondary, font);
+    }
+}
@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::collect::{git_output, git_process};
+use crate::config::resolve_path;
+use crate::error::{Error, Result};
+use crate::log;
+use crate::remote::is_remote_url;
+
+/// Finds all git repositories below `root`, both working trees (with a
+/// `.git` directory or file) and bare repositories. Repositories nested in
+/// other repositories, like submodules, are not descended into.
+pub fn find_repositories(root: &Path) -> Vec<PathBuf> {
+    let mut repos = Vec::new();
+    walk(root, &mut repos);
+    repos.sort();
+    repos
+}
+
+fn walk(dir: &Path, repos: &mut Vec<PathBuf>) {
+    if dir.join(".git").exists() || is_bare_repository(dir) {
+        repos.push(dir.to_path_buf());
+        return;
+    }
+
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        // Symlinks are skipped to avoid cycles and counting repositories twice
+        let Ok(file_type) = entry.file_type() else { continue };
+        if file_type.is_dir() {
+            walk(&entry.path(), repos);
+        }
+    }
+}
+
+fn is_bare_repository(dir: &Path) -> bool {
+    dir.join("HEAD").is_file() && dir.join("objects").is_dir() && dir.join("refs").is_dir()
+}
+
+/// Checks that every local repository exists and can be read by git,
+/// before any of them is scanned. All invalid paths are reported together,
+/// along with the path they were resolved to, see `resolve_repositories`.
+pub fn validate_repositories(repos: &[String]) -> Result<()> {
+    if cfg!(not(feature = "git-cli")) && !repos.is_empty() {
+        return Err(Error::Usage("git repositories need a build with the git-cli feature".to_string()));
+    }
+    let invalid: Vec<String> = repos.iter()
+        .filter(|repo| !is_remote_url(repo))
+        .filter_map(|repo| {
+            let resolved = resolve_path(repo);
+            let path = resolved.as_path();
+            let problem = if !path.exists() {
+                "does not exist"
+            } else if !path.is_dir() {
+                "is not a directory"
+            } else if !is_git_directory(path) {
+                "is not a git repository"
+            } else {
+                return None;
+            };
+            if path == Path::new(repo) {
+                Some(format!("{} {}", repo, problem))
+            } else {
+                Some(format!("{} ({}) {}", repo, path.display(), problem))
+            }
+        })
+        .collect();
+
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::InvalidRepositories(invalid))
+    }
+}
+
+/// Replaces the paths of local repositories with their absolute paths, see
+/// `config::resolve_path`, so that later messages show which directory
+/// was scanned
+pub fn resolve_repositories(repos: &mut [String]) {
+    for repo in repos.iter_mut().filter(|repo| !is_remote_url(repo)) {
+        *repo = resolve_path(repo).to_string_lossy().into_owned();
+    }
+}
+
+fn is_git_directory(path: &Path) -> bool {
+    git_process(Command::new("git").args(["rev-parse", "--git-dir"]).current_dir(path))
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Whether the repository is a shallow clone, whose history is cut off
+pub fn is_shallow(repo: &str) -> Result<bool> {
+    let mut command = Command::new("git");
+    command.args(["rev-parse", "--is-shallow-repository"]);
+    Ok(git_output(&mut command, repo)?.trim() == "true")
+}
+
+/// Fetches the missing history of a shallow clone
+pub fn unshallow(repo: &str) -> Result<()> {
+    log::info!("Fetching the full history of {}", repo);
+    let mut command = Command::new("git");
+    command.args(["fetch", "--quiet", "--unshallow"]);
+    git_output(&mut command, repo).map(|_| ())
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+/// A failure reported to the user, every kind exiting with its own status
+/// so that scripts can tell them apart
+#[derive(Debug)]
+pub enum Error {
+    /// A large scan that wasn't confirmed, see `--yes`
+    Cancelled(String),
+    /// Invalid or conflicting command line arguments
+    Usage(String),
+    /// An unreadable or invalid config, holidays, font or archive file
+    Input(String),
+    /// A path given as repository that git can't read
+    NotARepository(String),
+    /// Paths given as repositories that don't exist or aren't
+    /// repositories, each with the reason
+    InvalidRepositories(Vec<String>),
+    /// A git command that failed or couldn't be run
+    Git(String),
+    /// A failed request to the API of a forge
+    #[cfg_attr(not(feature = "net"), allow(dead_code))]
+    Api(String),
+    /// The image couldn't be written
+    Output(String),
+    /// Nothing to draw, no commits of the author were found
+    NoCommits(String),
+    /// Number of walls of a workspace that failed, each reported on its
+    /// own, and the error of the first one
+    FailedWalls(usize, Box<Error>),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Exit status of the process, as listed in the README
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Cancelled(_) => 1,
+            Error::Usage(_) => 2,
+            Error::Input(_) => 3,
+            Error::NotARepository(_) | Error::InvalidRepositories(_) | Error::Git(_) => 4,
+            Error::Api(_) => 5,
+            Error::Output(_) => 6,
+            Error::NoCommits(_) => 7,
+            Error::FailedWalls(_, first) => first.exit_code(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotARepository(path) => write!(f, "path {} is not a local git repository", path),
+            Error::NoCommits(author) => write!(
+                f,
+                "no commits by '{}' found, so no image was written. The author is matched against the name and email of every commit, `git shortlog -sne` lists the authors of a repository",
+                author
+            ),
+            Error::InvalidRepositories(problems) => {
+                write!(f, "{} of the given repositories can't be read:", problems.len())?;
+                for problem in problems {
+                    write!(f, "\n  {}", problem)?;
+                }
+                Ok(())
+            }
+            Error::FailedWalls(1, _) => f.write_str("1 wall of the workspace failed"),
+            Error::FailedWalls(failed, _) => write!(f, "{} walls of the workspace failed", failed),
+            Error::Cancelled(message)
+            | Error::Usage(message)
+            | Error::Input(message)
+            | Error::Git(message)
+            | Error::Api(message)
+            | Error::Output(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
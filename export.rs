@@ -0,0 +1,392 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+
+use crate::collect::{Activity, DiffStats};
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::narrative::longest_streak;
+use crate::stats::YearStats;
+
+/// Format of the report written along with the wall, given with `--export`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Export {
+    Markdown,
+}
+
+impl Export {
+    pub fn parse(format: &str) -> Result<Export> {
+        match format {
+            "markdown" | "md" => Ok(Export::Markdown),
+            _ => Err(Error::Usage(format!("Unknown export format '{}', expected markdown", format))),
+        }
+    }
+
+    /// The report on the wall in `image`, to be written next to it and
+    /// named like it, e.g. `commit_image_Jane_Doe.md`
+    pub fn report(&self, image: &Path, author: &str, header: Option<&Header>, activity: &Activity, active_years: &[YearStats]) -> Report {
+        let image_name = image.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let content = match self {
+            Export::Markdown => markdown_report(&image_name, author, header, activity, active_years),
+        };
+        Report { path: image.with_extension("md"), content }
+    }
+}
+
+/// A report along with the file it goes to
+pub struct Report {
+    path: PathBuf,
+    content: String,
+}
+
+impl Report {
+    pub fn save(&self) -> Result<()> {
+        fs::write(&self.path, &self.content)
+            .map_err(|err| Error::Output(format!("Failed to save the report to {}: {}", self.path.display(), err)))?;
+        println!("Wrote {}", self.path.display());
+        Ok(())
+    }
+}
+
+/// A report with a table of the numbers of every year, newest first, and
+/// the wall embedded from `image`, relative to the report
+fn markdown_report(image: &str, author: &str, header: Option<&Header>, activity: &Activity, active_years: &[YearStats]) -> String {
+    let mut report = String::new();
+    writeln!(report, "# Commits of {}", escape(author)).unwrap();
+    writeln!(report).unwrap();
+    if let Some(header) = header {
+        writeln!(report, "{}", header.subtitle()).unwrap();
+        writeln!(report).unwrap();
+    }
+    writeln!(report, "![Commits of {}]({})", escape(author), image.replace(' ', "%20")).unwrap();
+    writeln!(report).unwrap();
+
+    let (columns, rows) = year_table(activity, active_years);
+    writeln!(report, "| {} |", columns.join(" | ")).unwrap();
+    writeln!(report, "|{}", "---:|".repeat(columns.len())).unwrap();
+    for cells in rows {
+        writeln!(report, "| {} |", cells.join(" | ")).unwrap();
+    }
+    report
+}
+
+/// The numbers of every year as a table, newest first, printed with
+/// `--format text`
+pub fn text_stats(author: &str, header: Option<&Header>, activity: &Activity, active_years: &[YearStats]) -> String {
+    let mut text = String::new();
+    writeln!(text, "Commits of {}", author).unwrap();
+    if let Some(header) = header {
+        writeln!(text, "{}", header.subtitle()).unwrap();
+    }
+    writeln!(text).unwrap();
+
+    let (columns, rows) = year_table(activity, active_years);
+    let widths: Vec<usize> = columns.iter()
+        .enumerate()
+        .map(|(i, column)| rows.iter().map(|cells| cells[i].chars().count()).fold(column.chars().count(), usize::max))
+        .collect();
+    let line = |cells: &[String]| {
+        let cells: Vec<String> = cells.iter().zip(&widths).map(|(cell, &width)| format!("{:>1$}", cell, width)).collect();
+        cells.join("  ")
+    };
+    writeln!(text, "{}", line(&columns.iter().map(ToString::to_string).collect::<Vec<_>>())).unwrap();
+    for cells in &rows {
+        writeln!(text, "{}", line(cells)).unwrap();
+    }
+    text
+}
+
+/// The columns of the numbers of every year and the cells of every year,
+/// newest first, with the numbers of the diffs only when they were
+/// collected
+fn year_table(activity: &Activity, active_years: &[YearStats]) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let mut columns = vec!["Year", "Commits", "Active days", "Longest streak"];
+    if activity.has_diff_stats {
+        columns.extend(["Files changed", "Binary files", "Insertions", "Deletions"]);
+    }
+    let rows = active_years.iter().map(|stats| {
+        let streak = longest_streak(activity.commits.keys().filter(|date| date.year() == stats.year).copied().collect());
+        let mut cells = vec![
+            stats.year.to_string(),
+            stats.commits.to_string(),
+            stats.level_days.iter().sum::<usize>().to_string(),
+            if streak == 1 { "1 day".to_string() } else { format!("{} days", streak) },
+        ];
+        if activity.has_diff_stats {
+            cells.extend([
+                stats.diff.files.to_string(),
+                stats.diff.binary_files.to_string(),
+                stats.diff.insertions.to_string(),
+                stats.diff.deletions.to_string(),
+            ]);
+        }
+        cells
+    }).collect();
+    (columns, rows)
+}
+
+/// Version of the stats written with `--format json`, see `STATS_SCHEMA`
+pub const STATS_VERSION: u32 = 1;
+
+/// JSON Schema of the `StatsFile`, printed by `schema stats`
+pub const STATS_SCHEMA: &str = include_str!("schema/stats.schema.json");
+
+/// The numbers of every year written with `--format json` for other tools
+/// to pick up, along with the commits of every day so that
+/// `render --load` can draw the wall again
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StatsFile {
+    pub version: u32,
+    pub author: String,
+    /// Number of repositories analyzed, when the source knows it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repositories: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_commit: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_commit: Option<NaiveDate>,
+    /// Newest first
+    pub years: Vec<YearNumbers>,
+    /// Days with commits, oldest first
+    pub days: Vec<DayCommits>,
+}
+
+/// The numbers of a year, the diff stats only for sources that report them
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct YearNumbers {
+    pub year: i32,
+    pub commits: i32,
+    pub active_days: usize,
+    pub longest_streak: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files_changed: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_files: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insertions: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletions: Option<i32>,
+}
+
+/// The commits of a day, the diff stats only for sources that report them
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DayCommits {
+    pub date: NaiveDate,
+    /// Commits after the repository weights
+    pub commits: i32,
+    /// Local times of the commits, unweighted
+    pub times: Vec<NaiveTime>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub files_changed: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub binary_files: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub insertions: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deletions: Option<i32>,
+}
+
+impl DayCommits {
+    /// The diff stats, when all of them are given
+    fn diff(&self) -> Option<DiffStats> {
+        Some(DiffStats {
+            files: self.files_changed?,
+            binary_files: self.binary_files?,
+            insertions: self.insertions?,
+            deletions: self.deletions?,
+        })
+    }
+
+    /// Whether some of the diff stats are given
+    fn has_diff(&self) -> bool {
+        self.files_changed.is_some() || self.binary_files.is_some() || self.insertions.is_some() || self.deletions.is_some()
+    }
+}
+
+impl StatsFile {
+    pub fn new(author: &str, header: Option<&Header>, activity: &Activity, active_years: &[YearStats]) -> StatsFile {
+        // The diff stats are only given for sources that report them
+        let diff = |stats: DiffStats| activity.has_diff_stats.then_some(stats);
+        let years = active_years.iter().map(|stats| YearNumbers {
+            year: stats.year,
+            commits: stats.commits,
+            active_days: stats.level_days.iter().sum(),
+            longest_streak: longest_streak(activity.commits.keys().filter(|date| date.year() == stats.year).copied().collect()),
+            files_changed: diff(stats.diff).map(|diff| diff.files),
+            binary_files: diff(stats.diff).map(|diff| diff.binary_files),
+            insertions: diff(stats.diff).map(|diff| diff.insertions),
+            deletions: diff(stats.diff).map(|diff| diff.deletions),
+        }).collect();
+
+        let mut times: BTreeMap<NaiveDate, Vec<NaiveTime>> = BTreeMap::new();
+        for timestamp in &activity.timestamps {
+            times.entry(timestamp.date()).or_default().push(timestamp.time());
+        }
+        let mut dates: Vec<&NaiveDate> = activity.commits.keys().collect();
+        dates.sort_unstable();
+        let days = dates.into_iter().map(|date| {
+            let mut times = times.remove(date).unwrap_or_default();
+            times.sort_unstable();
+            let stats = diff(activity.diff_stats.get(date).copied().unwrap_or_default());
+            DayCommits {
+                date: *date,
+                commits: activity.commits[date],
+                times,
+                files_changed: stats.map(|diff| diff.files),
+                binary_files: stats.map(|diff| diff.binary_files),
+                insertions: stats.map(|diff| diff.insertions),
+                deletions: stats.map(|diff| diff.deletions),
+            }
+        }).collect();
+
+        StatsFile {
+            version: STATS_VERSION,
+            author: author.to_string(),
+            repositories: header.and_then(|header| header.repos),
+            first_commit: header.map(|header| header.first),
+            last_commit: header.map(|header| header.last),
+            years,
+            days,
+        }
+    }
+
+    /// Reads stats written with `--format json`, checking them against
+    /// `STATS_SCHEMA`
+    pub fn load(path: &Path) -> Result<StatsFile> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| Error::Input(format!("Failed to read {}: {}", path.display(), err)))?;
+        StatsFile::parse(&text)
+            .map_err(|err| Error::Input(format!("Invalid stats in {}: {}", path.display(), err)))
+    }
+
+    /// The stats in `text`, or what keeps them from matching the schema
+    fn parse(text: &str) -> std::result::Result<StatsFile, String> {
+        let stats: StatsFile = serde_json::from_str(text).map_err(|err| err.to_string())?;
+        if stats.version == 0 || stats.version > STATS_VERSION {
+            return Err(format!("version {} is not supported, this version of commits-tilewall reads version {}", stats.version, STATS_VERSION));
+        }
+        let mut dates = HashSet::new();
+        for day in &stats.days {
+            if !dates.insert(day.date) {
+                return Err(format!("{} is listed twice", day.date));
+            }
+            if day.commits < 0 {
+                return Err(format!("{} has a negative number of commits", day.date));
+            }
+            if day.has_diff() && day.diff().is_none() {
+                return Err(format!("{} has only some of files_changed, binary_files, insertions and deletions", day.date));
+            }
+            if day.has_diff() != stats.days[0].has_diff() {
+                return Err(format!("{} has diff stats unlike {}, either all days have them or none", day.date, stats.days[0].date));
+            }
+        }
+        Ok(stats)
+    }
+
+    /// The commits of the days as collected, for drawing the wall again
+    pub fn activity(&self) -> Activity {
+        let mut activity = Activity {
+            has_diff_stats: self.days.first().is_some_and(DayCommits::has_diff),
+            ..Default::default()
+        };
+        for day in &self.days {
+            activity.commits.insert(day.date, day.commits);
+            activity.timestamps.extend(day.times.iter().map(|&time| day.date.and_time(time)));
+            if let Some(diff) = day.diff() {
+                activity.diff_stats.insert(day.date, diff);
+            }
+        }
+        activity
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Failed to serialize the stats")
+    }
+}
+
+/// Escapes the characters of an author name that Markdown would format
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '[' | ']' | '`' | '<' | '>' | '#' | '|') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, day).unwrap()
+    }
+
+    fn activity() -> Activity {
+        let mut activity = Activity { has_diff_stats: true, ..Default::default() };
+        activity.commits.insert(date(2), 1);
+        activity.commits.insert(date(1), 3);
+        for (day, hour) in [(1, 9), (1, 17), (1, 12), (2, 23)] {
+            activity.timestamps.push(date(day).and_hms_opt(hour, 30, 0).unwrap());
+        }
+        activity.diff_stats.insert(date(1), DiffStats { files: 4, insertions: 20, deletions: 5, binary_files: 1 });
+        activity.diff_stats.insert(date(2), DiffStats { files: 1, insertions: 2, deletions: 0, binary_files: 0 });
+        activity
+    }
+
+    #[test]
+    fn days_round_trip() {
+        let json = StatsFile::new("Jane", None, &activity(), &[]).to_json();
+        let stats = StatsFile::parse(&json).unwrap();
+        assert_eq!(stats.version, STATS_VERSION);
+        assert_eq!(stats.days.iter().map(|day| day.date).collect::<Vec<_>>(), [date(1), date(2)]);
+        assert_eq!(stats.days[0].times[0].to_string(), "09:30:00");
+
+        let loaded = stats.activity();
+        let original = activity();
+        assert_eq!(loaded.commits, original.commits);
+        assert!(loaded.has_diff_stats);
+        assert_eq!(loaded.diff_stats[&date(1)].insertions, 20);
+        let mut timestamps = original.timestamps.clone();
+        timestamps.sort_unstable();
+        assert_eq!(loaded.timestamps, timestamps);
+    }
+
+    #[test]
+    fn diff_stats_are_left_out_for_sources_without_them() {
+        let activity = Activity { has_diff_stats: false, ..activity() };
+        let json = StatsFile::new("Jane", None, &activity, &[]).to_json();
+        assert!(!json.contains("insertions"));
+        assert!(!StatsFile::parse(&json).unwrap().activity().has_diff_stats);
+    }
+
+    #[test]
+    fn invalid_stats_are_refused() {
+        let day = r#"{"date": "2024-03-01", "commits": 1, "times": []}"#;
+        let file = |version: u32, days: &str| format!(r#"{{"version": {}, "author": "Jane", "years": [], "days": [{}]}}"#, version, days);
+        assert!(StatsFile::parse(&file(1, day)).is_ok());
+        assert!(StatsFile::parse(&file(STATS_VERSION + 1, day)).unwrap_err().contains("not supported"));
+        assert!(StatsFile::parse(&file(1, &format!("{}, {}", day, day))).unwrap_err().contains("twice"));
+        assert!(StatsFile::parse(&file(1, r#"{"date": "2024-03-01", "commits": -1, "times": []}"#)).is_err());
+        assert!(StatsFile::parse(&file(1, r#"{"date": "2024-03-01", "commits": 1, "times": [], "insertions": 3}"#)).is_err());
+        assert!(StatsFile::parse(&file(1, r#"{"date": "2024-03-01", "commits": 1, "times": [], "lines": 3}"#)).is_err());
+        assert!(StatsFile::parse(&file(1, r#"{"date": "2024-03-32", "commits": 1, "times": []}"#)).is_err());
+    }
+
+    #[test]
+    fn schema_matches_the_version() {
+        let schema: serde_json::Value = serde_json::from_str(STATS_SCHEMA).unwrap();
+        assert_eq!(schema["properties"]["version"]["const"], STATS_VERSION);
+        assert!(schema["$id"].as_str().unwrap().ends_with(&format!("/{}", STATS_VERSION)));
+    }
+}
@@ -0,0 +1,268 @@
+use chrono::{Datelike, NaiveDateTime, NaiveTime};
+
+use crate::error::{Error, Result};
+use crate::holidays::is_weekend;
+
+/// Restricts the counted commits to certain times, given with `--only`
+#[derive(Debug, Clone, Copy)]
+pub enum TimeFilter {
+    Weekends,
+    Weekdays,
+    /// Commits outside of working hours, `start` and `end` being the
+    /// beginning and end of the after-hours period, which may wrap around
+    /// midnight
+    AfterHours { start: NaiveTime, end: NaiveTime },
+}
+
+impl TimeFilter {
+    /// Parses `weekends`, `weekdays` or `after-hours=18:00-08:00`
+    pub fn parse(spec: &str) -> Result<TimeFilter> {
+        match spec.split_once('=') {
+            None if spec == "weekends" => Ok(TimeFilter::Weekends),
+            None if spec == "weekdays" => Ok(TimeFilter::Weekdays),
+            None if spec == "after-hours" => Ok(TimeFilter::AfterHours {
+                start: NaiveTime::from_hms_opt(18, 0, 0).unwrap(),
+                end: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            }),
+            Some(("after-hours", range)) => {
+                let parse_time = |time: &str| {
+                    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                        .map_err(|_| Error::Usage(format!("Invalid time '{}' in --only {}, expected HH:MM", time, spec)))
+                };
+                let (start, end) = range.split_once('-')
+                    .ok_or_else(|| Error::Usage(format!("Invalid range in --only {}, expected HH:MM-HH:MM", spec)))?;
+                Ok(TimeFilter::AfterHours { start: parse_time(start)?, end: parse_time(end)? })
+            }
+            _ => Err(Error::Usage(format!("Unknown filter '{}', expected weekends, weekdays or after-hours=HH:MM-HH:MM", spec))),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            TimeFilter::Weekends => "on weekends".to_string(),
+            TimeFilter::Weekdays => "on weekdays".to_string(),
+            TimeFilter::AfterHours { start, end } => format!("between {} and {}", start.format("%H:%M"), end.format("%H:%M")),
+        }
+    }
+
+    pub fn matches(&self, timestamp: &NaiveDateTime) -> bool {
+        match *self {
+            TimeFilter::Weekends => is_weekend(&timestamp.date()),
+            TimeFilter::Weekdays => !is_weekend(&timestamp.date()),
+            TimeFilter::AfterHours { start, end } => {
+                let time = timestamp.time();
+                if start <= end {
+                    start <= time && time < end
+                } else {
+                    time >= start || time < end
+                }
+            }
+        }
+    }
+}
+
+/// The years given with `--years`, e.g. `2019-2021,2023,>=2025`
+#[derive(Debug, Clone)]
+pub struct YearFilter {
+    /// Inclusive ranges of years, open ended when a bound is missing
+    ranges: Vec<(Option<i32>, Option<i32>)>,
+}
+
+impl YearFilter {
+    /// Parses a comma-separated list of years (`2023`), ranges
+    /// (`2019-2021`) and comparisons (`>=2025`, `<2010`)
+    pub fn parse(spec: &str) -> Result<YearFilter> {
+        let parse_year = |year: &str| {
+            year.trim().parse::<i32>()
+                .map_err(|_| Error::Usage(format!("Invalid year '{}' in --years {}", year.trim(), spec)))
+        };
+        let ranges = spec.split(',').map(|term| {
+            let term = term.trim();
+            Ok(if let Some(year) = term.strip_prefix(">=") {
+                (Some(parse_year(year)?), None)
+            } else if let Some(year) = term.strip_prefix("<=") {
+                (None, Some(parse_year(year)?))
+            } else if let Some(year) = term.strip_prefix('>') {
+                (Some(parse_year(year)? + 1), None)
+            } else if let Some(year) = term.strip_prefix('<') {
+                (None, Some(parse_year(year)? - 1))
+            } else if let Some((start, end)) = term.split_once('-') {
+                (Some(parse_year(start)?), Some(parse_year(end)?))
+            } else {
+                let year = parse_year(term)?;
+                (Some(year), Some(year))
+            })
+        }).collect::<Result<_>>()?;
+        Ok(YearFilter { ranges })
+    }
+
+    pub fn matches(&self, year: i32) -> bool {
+        self.ranges.iter().any(|&(start, end)| {
+            start.is_none_or(|start| start <= year) && end.is_none_or(|end| year <= end)
+        })
+    }
+}
+
+/// The filters given with `--only`, a commit is counted when it matches
+/// any of them, the years given with `--years`, and the files counted in
+/// the diff stats
+#[derive(Debug, Clone, Default)]
+pub struct CommitFilter {
+    pub only: Vec<TimeFilter>,
+    pub years: Option<YearFilter>,
+    /// Count the commits of bots matching the author too, `--include-bots`
+    pub include_bots: bool,
+    /// Further names and emails of bots, `--exclude-bots`, see
+    /// `authors::is_bot`
+    pub bot_patterns: Vec<String>,
+    /// Leave out the commits reverting others, `--no-reverts`
+    pub no_reverts: bool,
+    /// Count the commits crediting the author in a `Co-authored-by:`
+    /// trailer too, `--include-coauthored`
+    pub include_coauthored: bool,
+    /// Pathspecs of files left out of the diff stats, `--stats-exclude`
+    pub stats_exclude: Vec<String>,
+    /// Pattern the commit messages have to match, `--grep`, or must not
+    /// match with `--invert-grep`
+    pub grep: Option<String>,
+    pub invert_grep: bool,
+}
+
+impl CommitFilter {
+    /// True when no `--only` filter is given. The years aren't described
+    /// in the summary, as only the selected years are shown anyway.
+    pub fn is_empty(&self) -> bool {
+        self.only.is_empty()
+    }
+
+    /// E.g. "on weekends or between 18:00 and 08:00"
+    pub fn describe(&self) -> String {
+        self.only.iter().map(|filter| filter.describe()).collect::<Vec<_>>().join(" or ")
+    }
+
+    /// E.g. "whose message matches 'fix:'", `None` without `--grep`
+    pub fn describe_grep(&self) -> Option<String> {
+        let verb = if self.invert_grep { "doesn't match" } else { "matches" };
+        self.grep.as_ref().map(|pattern| format!("whose message {} '{}'", verb, pattern))
+    }
+
+    /// Arguments of `git log` listing only the commits of `--grep` and
+    /// `--invert-grep`
+    pub fn grep_args(&self) -> Vec<String> {
+        let Some(pattern) = &self.grep else {
+            return Vec::new();
+        };
+        let mut args = vec![format!("--grep={}", pattern)];
+        if self.invert_grep {
+            args.push("--invert-grep".to_string());
+        }
+        args
+    }
+
+    #[cfg_attr(not(feature = "net"), allow(dead_code))]
+    pub fn matches(&self, timestamp: &NaiveDateTime) -> bool {
+        self.matches_year(timestamp) && self.matches_time(timestamp)
+    }
+
+    /// Whether the commit is in one of the years of `--years`
+    pub fn matches_year(&self, timestamp: &NaiveDateTime) -> bool {
+        self.years.as_ref().is_none_or(|years| years.matches(timestamp.year()))
+    }
+
+    /// Whether the commit matches any of the `--only` filters
+    pub fn matches_time(&self, timestamp: &NaiveDateTime) -> bool {
+        self.only.is_empty() || self.only.iter().any(|filter| filter.matches(timestamp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    /// A time on Saturday, March 2 2024, or on the Monday after it
+    fn at(weekend: bool, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, if weekend { 2 } else { 4 }).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn weekends_and_weekdays_split_the_week() {
+        let (weekends, weekdays) = (TimeFilter::parse("weekends").unwrap(), TimeFilter::parse("weekdays").unwrap());
+        assert!(weekends.matches(&at(true, 12, 0)) && !weekends.matches(&at(false, 12, 0)));
+        assert!(weekdays.matches(&at(false, 12, 0)) && !weekdays.matches(&at(true, 12, 0)));
+    }
+
+    #[test]
+    fn after_hours_wrap_around_midnight() {
+        let filter = TimeFilter::parse("after-hours").unwrap();
+        assert_eq!(filter.describe(), "between 18:00 and 08:00");
+        assert!(filter.matches(&at(false, 18, 0)));
+        assert!(filter.matches(&at(false, 23, 59)));
+        assert!(filter.matches(&at(false, 7, 59)));
+        assert!(!filter.matches(&at(false, 8, 0)));
+        assert!(!filter.matches(&at(false, 17, 59)));
+    }
+
+    #[test]
+    fn after_hours_within_a_day() {
+        let filter = TimeFilter::parse("after-hours=12:00-13:30").unwrap();
+        assert!(filter.matches(&at(false, 12, 0)));
+        assert!(filter.matches(&at(true, 13, 29)));
+        assert!(!filter.matches(&at(false, 13, 30)));
+        assert!(!filter.matches(&at(false, 11, 59)));
+    }
+
+    #[test]
+    fn invalid_time_filters_are_rejected() {
+        for spec in ["mornings", "after-hours=18:00", "after-hours=25:00-08:00", "weekends=1"] {
+            assert!(matches!(TimeFilter::parse(spec), Err(Error::Usage(_))), "{}", spec);
+        }
+    }
+
+    #[test]
+    fn years_ranges_and_comparisons() {
+        let filter = YearFilter::parse("2015, 2018-2019,>2022").unwrap();
+        let years: Vec<i32> = (2014..=2024).filter(|&year| filter.matches(year)).collect();
+        assert_eq!(years, [2015, 2018, 2019, 2023, 2024]);
+
+        let filter = YearFilter::parse("<2010,>=2030").unwrap();
+        assert!(filter.matches(2009) && !filter.matches(2010));
+        assert!(filter.matches(2030) && !filter.matches(2029));
+        assert!(YearFilter::parse("<=2010").unwrap().matches(2010));
+    }
+
+    #[test]
+    fn invalid_years_are_rejected() {
+        for spec in ["", "2020-", "twenty", ">=", "2019,,2020"] {
+            assert!(matches!(YearFilter::parse(spec), Err(Error::Usage(_))), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn years_and_times_both_have_to_match() {
+        let filter = CommitFilter {
+            only: vec![TimeFilter::Weekends],
+            years: Some(YearFilter::parse("2024").unwrap()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&at(true, 12, 0)));
+        assert!(!filter.matches(&at(false, 12, 0)));
+        assert!(!filter.matches(&(at(true, 12, 0) - chrono::Duration::weeks(52))));
+        assert!(!filter.is_empty());
+        assert!(CommitFilter { years: filter.years.clone(), ..Default::default() }.is_empty());
+    }
+
+    #[test]
+    fn a_commit_matches_any_of_the_filters() {
+        let filter = CommitFilter {
+            only: vec![TimeFilter::parse("weekends").unwrap(), TimeFilter::parse("after-hours").unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(filter.describe(), "on weekends or between 18:00 and 08:00");
+        assert!(filter.matches_time(&at(true, 12, 0)));
+        assert!(filter.matches_time(&at(false, 20, 0)));
+        assert!(!filter.matches_time(&at(false, 12, 0)));
+        assert!(CommitFilter::default().matches_time(&at(false, 12, 0)));
+    }
+}
@@ -0,0 +1,528 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use ab_glyph::{point, Font, FontVec, GlyphId, PxScale, ScaleFont};
+use image::{ImageBuffer, Rgba};
+use imageproc::pixelops::weighted_sum;
+
+use crate::error::{Error, Result};
+use crate::log;
+
+/// An ordered list of fonts used to render text. Characters missing from
+/// the first font are looked up in the next ones, and as a last resort the
+/// system is asked for any installed font that covers them.
+pub struct FontChain {
+    fonts: RefCell<Vec<FontVec>>,
+    fallbacks: RefCell<HashMap<char, Option<usize>>>,
+    loaded_paths: RefCell<HashMap<PathBuf, usize>>,
+    /// Glyphs rasterized so far, so that labels repeated on every year row
+    /// are only rasterized once
+    glyphs: RefCell<HashMap<GlyphKey, Rc<GlyphRaster>>>,
+    /// Family of the first font, when it was given by name
+    family: Option<String>,
+    /// Rasterize the glyphs at twice the size and average them down, see
+    /// `--supersample-text`
+    supersampling: bool,
+}
+
+/// A glyph of one of the fonts at a size, placed at a fraction of a pixel.
+/// The sizes and fractions are compared by their bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: usize,
+    glyph: GlyphId,
+    scale: (u32, u32),
+    offset: u32,
+}
+
+/// A run of text drawn with one font of the chain, for outputs that set the
+/// glyphs of embedded fonts like PDF, see `FontChain::glyph_runs`
+pub struct GlyphRun {
+    /// Index of the font in the chain
+    pub font: usize,
+    /// Size of an em of the font at the scale of the text in pixels, the
+    /// size fonts are set in outside of `ab_glyph`
+    pub em_size: f32,
+    /// The glyphs with the characters they draw and their kerning against
+    /// the glyph before them in pixels
+    pub glyphs: Vec<(u16, char, f32)>,
+}
+
+/// Coverage of the pixels of a glyph, relative to the pixel its origin on
+/// the baseline is placed at
+struct GlyphRaster {
+    x: i32,
+    y: i32,
+    width: u32,
+    coverage: Vec<f32>,
+}
+
+impl FontChain {
+    pub fn new(font_specs: &[String]) -> Result<Self> {
+        let fonts = if font_specs.is_empty() {
+            vec![load_system_font()?]
+        } else {
+            font_specs.iter().map(|spec| load_font(spec)).collect::<Result<_>>()?
+        };
+
+        Ok(FontChain {
+            fonts: RefCell::new(fonts),
+            fallbacks: RefCell::new(HashMap::new()),
+            loaded_paths: RefCell::new(HashMap::new()),
+            glyphs: RefCell::new(HashMap::new()),
+            family: font_specs.first().filter(|spec| !Path::new(spec).is_file()).cloned(),
+            supersampling: false,
+        })
+    }
+
+    /// Rasterizes the glyphs at twice their size and averages every 2 by 2
+    /// pixels, which smooths small text at the cost of rendering time
+    pub fn with_supersampling(self, supersampling: bool) -> Self {
+        FontChain { supersampling, ..self }
+    }
+
+    pub fn family(&self) -> Option<&str> {
+        self.family.as_deref()
+    }
+
+    fn font_index_for(&self, c: char) -> usize {
+        if c.is_whitespace() || c.is_control() {
+            return 0;
+        }
+
+        if let Some(index) = self.fonts.borrow().iter().position(|font| has_glyph(font, c)) {
+            return index;
+        }
+
+        if let Some(&index) = self.fallbacks.borrow().get(&c) {
+            return index.unwrap_or(0);
+        }
+
+        let index = system::find_for_char(c).into_iter().find_map(|path| {
+            // Fonts loaded before were checked along with the chain
+            if self.loaded_paths.borrow().contains_key(&path) {
+                return None;
+            }
+            // A broken fallback font is skipped like one without the glyph
+            let font = read_font_file(&path).ok()?;
+            if !has_glyph(&font, c) {
+                return None;
+            }
+            let mut fonts = self.fonts.borrow_mut();
+            fonts.push(font);
+            self.loaded_paths.borrow_mut().insert(path, fonts.len() - 1);
+            Some(fonts.len() - 1)
+        });
+        self.fallbacks.borrow_mut().insert(c, index);
+
+        index.unwrap_or(0)
+    }
+
+    /// Splits the text into runs that are rendered with the same font
+    fn runs(&self, text: &str) -> Vec<(usize, String)> {
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for c in text.chars() {
+            let index = self.font_index_for(c);
+            match runs.last_mut() {
+                Some((last, run)) if *last == index => run.push(c),
+                _ => runs.push((index, c.to_string())),
+            }
+        }
+        runs
+    }
+
+    /// The fonts of the chain that draw the text, in the order of the
+    /// chain, each reduced to the glyphs it draws it with. The glyphs keep
+    /// their ids, so the subsets lay out the text like the whole fonts.
+    pub fn subsets(&self, text: &str) -> Result<Vec<Vec<u8>>> {
+        let mut glyphs: Vec<Vec<u16>> = Vec::new();
+        for (index, run) in self.runs(text) {
+            if glyphs.len() <= index {
+                glyphs.resize(index + 1, Vec::new());
+            }
+            let font = &self.fonts.borrow()[index];
+            glyphs[index].extend(run.chars().map(|c| font.glyph_id(c).0));
+        }
+
+        glyphs.into_iter().enumerate()
+            .filter(|(_, glyphs)| !glyphs.is_empty())
+            .map(|(index, glyphs)| self.subset(index, &glyphs))
+            .collect()
+    }
+
+    /// The font at `index` reduced to the glyphs, which keep their ids, and
+    /// the glyph for missing characters
+    pub fn subset(&self, index: usize, glyphs: &[u16]) -> Result<Vec<u8>> {
+        let mut glyphs = glyphs.to_vec();
+        glyphs.push(0);
+        glyphs.sort_unstable();
+        glyphs.dedup();
+        subsetter::subset(self.fonts.borrow()[index].as_slice(), 0, subsetter::Profile::pdf(&glyphs))
+            .map_err(|err| Error::Input(format!("Failed to subset the font for embedding: {}", err)))
+    }
+
+    /// The glyphs drawing the text at the scale, in the runs of the fonts
+    /// `draw_text` draws them with
+    pub fn glyph_runs(&self, text: &str, scale: PxScale) -> Vec<GlyphRun> {
+        // Finding the fonts of the runs can load fallback fonts
+        let runs = self.runs(text);
+        let fonts = self.fonts.borrow();
+        runs.into_iter()
+            .map(|(index, run)| {
+                let font = fonts[index].as_scaled(scale);
+                let mut previous = None;
+                let glyphs = run.chars()
+                    .map(|c| {
+                        let glyph = font.glyph_id(c);
+                        let kern = previous.map_or(0.0, |previous| font.kern(previous, glyph));
+                        previous = Some(glyph);
+                        (glyph.0, c, kern)
+                    })
+                    .collect();
+                let em_size = font.h_scale_factor() * fonts[index].units_per_em().unwrap_or(1000.0);
+                GlyphRun { font: index, em_size, glyphs }
+            })
+            .collect()
+    }
+
+    /// Distance of the baseline from the top of the text drawn with
+    /// `draw_text`, the ascent of the first font
+    pub fn ascent(&self, scale: PxScale) -> f32 {
+        self.fonts.borrow()[0].as_scaled(scale).ascent()
+    }
+
+    /// Width of the text when drawn with `draw_text`
+    pub fn text_width(&self, text: &str, scale: PxScale) -> f32 {
+        let runs = self.runs(text);
+        let fonts = self.fonts.borrow();
+        runs.iter()
+            .map(|(index, run)| layout_run(&fonts[*index], scale, run).1)
+            .sum()
+    }
+
+    /// Draws the text with `y` being the top of the ascent of the first
+    /// font. The baseline is put on a whole pixel, so that the flat parts
+    /// of the letters stay sharp, and the runs of fallback fonts share it.
+    pub fn draw_text(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, text: &str, x: i32, y: i32, scale: PxScale, color: Rgba<u8>) {
+        let runs = self.runs(text);
+        let baseline = (y as f32 + self.fonts.borrow()[0].as_scaled(scale).ascent()).round() as i32;
+        let mut run_x = x as f32;
+        for (index, run) in &runs {
+            let (glyphs, advance) = layout_run(&self.fonts.borrow()[*index], scale, run);
+            for (glyph, caret) in glyphs {
+                let glyph_x = run_x + caret;
+                let whole_x = glyph_x.floor();
+                let raster = self.raster(*index, glyph, scale, glyph_x - whole_x);
+                for (i, &coverage) in raster.coverage.iter().enumerate() {
+                    let pixel_x = whole_x as i32 + raster.x + (i as u32 % raster.width) as i32;
+                    let pixel_y = baseline + raster.y + (i as u32 / raster.width) as i32;
+                    if coverage > 0.0 && (0..img.width() as i32).contains(&pixel_x) && (0..img.height() as i32).contains(&pixel_y) {
+                        let pixel = img.get_pixel_mut(pixel_x as u32, pixel_y as u32);
+                        *pixel = weighted_sum(*pixel, color, 1.0 - coverage, coverage);
+                    }
+                }
+            }
+            run_x += advance;
+        }
+    }
+
+    /// The raster of a glyph of the font at `index`, placed at the
+    /// fraction `offset` of a pixel right of its pixel, rasterized on first
+    /// use
+    fn raster(&self, index: usize, glyph: GlyphId, scale: PxScale, offset: f32) -> Rc<GlyphRaster> {
+        let key = GlyphKey {
+            font: index,
+            glyph,
+            scale: (scale.x.to_bits(), scale.y.to_bits()),
+            offset: offset.to_bits(),
+        };
+        if let Some(raster) = self.glyphs.borrow().get(&key) {
+            return Rc::clone(raster);
+        }
+
+        // With supersampling, the glyph is outlined at twice the size and
+        // every pixel adds a quarter of its coverage to the pixel it falls in
+        let factor: i32 = if self.supersampling { 2 } else { 1 };
+        let samples = (factor * factor) as f32;
+        let outlined_scale = PxScale { x: scale.x * factor as f32, y: scale.y * factor as f32 };
+        let outlined = self.fonts.borrow()[index].outline_glyph(glyph.with_scale_and_position(outlined_scale, point(offset * factor as f32, 0.0)));
+        let raster = match outlined {
+            Some(outlined) => {
+                let bounds = outlined.px_bounds();
+                let (min_x, min_y) = (bounds.min.x as i32, bounds.min.y as i32);
+                let (max_x, max_y) = (bounds.max.x as i32, bounds.max.y as i32);
+                let (x, y) = (min_x.div_euclid(factor), min_y.div_euclid(factor));
+                let width = ((max_x + factor - 1).div_euclid(factor) - x) as u32;
+                let height = ((max_y + factor - 1).div_euclid(factor) - y) as u32;
+                let mut coverage = vec![0.0; (width * height) as usize];
+                outlined.draw(|px, py, value| {
+                    let column = (min_x + px as i32).div_euclid(factor) - x;
+                    let row = (min_y + py as i32).div_euclid(factor) - y;
+                    coverage[(row as u32 * width + column as u32) as usize] += value / samples;
+                });
+                GlyphRaster { x, y, width, coverage }
+            }
+            // Spaces have nothing to draw
+            None => GlyphRaster { x: 0, y: 0, width: 1, coverage: Vec::new() },
+        };
+        let raster = Rc::new(raster);
+        self.glyphs.borrow_mut().insert(key, Rc::clone(&raster));
+        raster
+    }
+}
+
+/// Whether the font draws the character. Color emoji fonts have glyphs of
+/// bitmaps only, which aren't drawn, so they don't count.
+fn has_glyph(font: &FontVec, c: char) -> bool {
+    let glyph = font.glyph_id(c);
+    glyph != GlyphId(0) && font.outline(glyph).is_some()
+}
+
+/// The glyphs of a run with their distance from its start, kerned, and the
+/// advance of the whole run
+fn layout_run(font: &FontVec, scale: PxScale, text: &str) -> (Vec<(GlyphId, f32)>, f32) {
+    let font = font.as_scaled(scale);
+    let mut glyphs = Vec::new();
+    let mut caret = 0.0;
+    let mut previous = None;
+    for c in text.chars() {
+        let glyph = font.glyph_id(c);
+        if let Some(previous) = previous {
+            caret += font.kern(previous, glyph);
+        }
+        glyphs.push((glyph, caret));
+        caret += font.h_advance(glyph);
+        previous = Some(glyph);
+    }
+    (glyphs, caret)
+}
+
+fn load_system_font() -> Result<FontVec> {
+    let path = system::find_default()
+        .ok_or_else(|| Error::Input("Could not find a sans font on the system, pass one with --font".to_string()))?;
+
+    read_font_file(&path)
+}
+
+fn load_font(spec: &str) -> Result<FontVec> {
+    // A spec naming an existing file is loaded directly, anything else is
+    // treated as a font family and looked up in the system fonts.
+    let path = Path::new(spec);
+    if path.is_file() {
+        return read_font_file(path);
+    }
+
+    let path = system::find_family(spec)
+        .ok_or_else(|| Error::Input(format!("Could not find font '{}' on the system", spec)))?;
+    log::info!("Using font: {} ({})", spec, path.display());
+
+    read_font_file(&path)
+}
+
+fn read_font_file(font_path: &Path) -> Result<FontVec> {
+    let mut font_file = File::open(font_path)
+        .map_err(|err| Error::Input(format!("Failed to open font file {}: {}", font_path.display(), err)))?;
+    let mut font_data = Vec::new();
+    font_file.read_to_end(&mut font_data)
+        .map_err(|err| Error::Input(format!("Failed to read font file {}: {}", font_path.display(), err)))?;
+
+    FontVec::try_from_vec(font_data)
+        .map_err(|_| Error::Input(format!("{} is not a TrueType or OpenType font", font_path.display())))
+}
+
+/// Font discovery through fontconfig on Linux and the BSDs, with the
+/// `system-fonts` feature.
+#[cfg(all(feature = "system-fonts", unix, not(target_os = "macos")))]
+mod system {
+    use std::ffi::{CStr, CString};
+    use std::path::PathBuf;
+    use std::ptr;
+
+    use fontconfig::{Fontconfig, ObjectSet, Pattern, FC_CHARSET, FC_FAMILY, FC_FILE};
+    use fontconfig_sys::{FcChar8, FcCharSetAddChar, FcCharSetCreate, FcCharSetDestroy, FcPatternAddCharSet, FcPatternGetString, FcResultMatch};
+
+    /// Families fontconfig resolves to a configured font, which are found
+    /// under another name
+    const GENERIC_FAMILIES: [&str; 8] = ["sans", "sans-serif", "serif", "monospace", "cursive", "fantasy", "system-ui", "emoji"];
+
+    pub fn find_default() -> Option<PathBuf> {
+        let fontconfig = Fontconfig::new()?;
+        fontconfig
+            .find("sans-bold", None)
+            .or_else(|| fontconfig.find("sans", None))
+            .map(|font| font.path)
+    }
+
+    /// The font of the family, if one is installed. fontconfig always
+    /// matches some font, the closest one for a family that isn't
+    /// installed, so the match only counts when it is of the family.
+    pub fn find_family(family: &str) -> Option<PathBuf> {
+        let fontconfig = Fontconfig::new()?;
+        let mut pattern = Pattern::new(&fontconfig);
+        pattern.add_string(FC_FAMILY, &CString::new(family).ok()?);
+        let matched = pattern.font_match();
+
+        let generic = GENERIC_FAMILIES.iter().any(|generic| generic.eq_ignore_ascii_case(family));
+        if !generic && !families(&matched).iter().any(|name| name.eq_ignore_ascii_case(family)) {
+            return None;
+        }
+        matched.filename().map(PathBuf::from)
+    }
+
+    /// The family names of a font, which may be given in several languages
+    fn families(pattern: &Pattern) -> Vec<String> {
+        (0..).map_while(|index| unsafe {
+            let mut name: *mut FcChar8 = ptr::null_mut();
+            let found = FcPatternGetString(pattern.as_ptr() as *mut _, FC_FAMILY.as_ptr(), index, &mut name) == FcResultMatch;
+            found.then(|| CStr::from_ptr(name as *const _).to_string_lossy().into_owned())
+        }).collect()
+    }
+
+    /// Fonts covering the character, the best match first and the others
+    /// after it, for when the best match has no outline for it
+    pub fn find_for_char(c: char) -> Vec<PathBuf> {
+        let Some(fontconfig) = Fontconfig::new() else {
+            return Vec::new();
+        };
+        let mut paths: Vec<PathBuf> = char_pattern(&fontconfig, c).font_match().filename().map(PathBuf::from).into_iter().collect();
+        let mut objects = ObjectSet::new(&fontconfig);
+        objects.add(FC_FILE);
+        for font in fontconfig::list_fonts(&char_pattern(&fontconfig, c), Some(&objects)).iter() {
+            if let Some(path) = font.filename().map(PathBuf::from).filter(|path| !paths.contains(path)) {
+                paths.push(path);
+            }
+        }
+        paths
+    }
+
+    /// A pattern of the fonts covering the character
+    fn char_pattern(fontconfig: &Fontconfig, c: char) -> Pattern<'_> {
+        let mut pattern = Pattern::new(fontconfig);
+        unsafe {
+            let charset = FcCharSetCreate();
+            FcCharSetAddChar(charset, c as u32);
+            FcPatternAddCharSet(pattern.as_mut_ptr(), FC_CHARSET.as_ptr(), charset);
+            FcCharSetDestroy(charset);
+        }
+        pattern
+    }
+}
+
+/// Font discovery on Windows and macOS, where fontconfig is usually not
+/// installed, and in builds without the `system-fonts` feature: the
+/// well-known font directories are scanned and the family names are read
+/// from the font files themselves.
+#[cfg(not(all(feature = "system-fonts", unix, not(target_os = "macos"))))]
+mod system {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
+
+    use ttf_parser::{name_id, Face};
+
+    #[cfg(windows)]
+    const DEFAULT_FAMILIES: &[&str] = &["Segoe UI", "Arial", "Tahoma"];
+    #[cfg(target_os = "macos")]
+    const DEFAULT_FAMILIES: &[&str] = &["Helvetica Neue", "Helvetica", "Arial"];
+    #[cfg(not(any(windows, target_os = "macos")))]
+    const DEFAULT_FAMILIES: &[&str] = &["DejaVu Sans", "Liberation Sans", "Noto Sans"];
+
+    fn font_dirs() -> Vec<PathBuf> {
+        let mut dirs = Vec::new();
+        if cfg!(windows) {
+            let windir = env::var_os("WINDIR").unwrap_or_else(|| "C:\\Windows".into());
+            dirs.push(Path::new(&windir).join("Fonts"));
+            if let Some(local) = env::var_os("LOCALAPPDATA") {
+                dirs.push(Path::new(&local).join("Microsoft").join("Windows").join("Fonts"));
+            }
+        } else if cfg!(target_os = "macos") {
+            dirs.push(PathBuf::from("/System/Library/Fonts"));
+            dirs.push(PathBuf::from("/Library/Fonts"));
+            if let Some(home) = env::var_os("HOME") {
+                dirs.push(Path::new(&home).join("Library").join("Fonts"));
+            }
+        } else {
+            dirs.push(PathBuf::from("/usr/share/fonts"));
+            dirs.push(PathBuf::from("/usr/local/share/fonts"));
+            if let Some(data_home) = env::var_os("XDG_DATA_HOME") {
+                dirs.push(Path::new(&data_home).join("fonts"));
+            } else if let Some(home) = env::var_os("HOME") {
+                dirs.push(Path::new(&home).join(".local").join("share").join("fonts"));
+            }
+            if let Some(home) = env::var_os("HOME") {
+                dirs.push(Path::new(&home).join(".fonts"));
+            }
+        }
+        dirs
+    }
+
+    fn collect_font_files(dir: &Path, files: &mut Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                collect_font_files(&path, files);
+            } else if path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| matches!(ext.to_lowercase().as_str(), "ttf" | "otf" | "ttc"))
+            {
+                files.push(path);
+            }
+        }
+    }
+
+    fn font_files() -> &'static [PathBuf] {
+        static FILES: OnceLock<Vec<PathBuf>> = OnceLock::new();
+        FILES.get_or_init(|| {
+            let mut files = Vec::new();
+            for dir in font_dirs() {
+                collect_font_files(&dir, &mut files);
+            }
+            files.sort();
+            files
+        })
+    }
+
+    /// Finds the first font file whose parsed face satisfies `predicate`.
+    fn find_file(predicate: impl Fn(&Face) -> bool) -> Option<PathBuf> {
+        font_files().iter()
+            .find(|path| {
+                fs::read(path).ok()
+                    .and_then(|data| Face::from_slice(&data, 0).ok().map(|face| predicate(&face)))
+                    .unwrap_or(false)
+            })
+            .cloned()
+    }
+
+    fn has_family(face: &Face, family: &str) -> bool {
+        face.names().into_iter()
+            .filter(|name| name.name_id == name_id::FAMILY || name.name_id == name_id::TYPOGRAPHIC_FAMILY)
+            .filter_map(|name| name.to_string())
+            .any(|name| name.eq_ignore_ascii_case(family))
+    }
+
+    pub fn find_default() -> Option<PathBuf> {
+        DEFAULT_FAMILIES.iter().find_map(|family| {
+            find_file(|face| has_family(face, family) && face.is_bold())
+                .or_else(|| find_family(family))
+        })
+    }
+
+    pub fn find_family(family: &str) -> Option<PathBuf> {
+        find_file(|face| has_family(face, family) && !face.is_italic())
+            .or_else(|| find_file(|face| has_family(face, family)))
+    }
+
+    /// The first font with an outline for the character, the bitmaps of
+    /// color emoji fonts aren't drawn
+    pub fn find_for_char(c: char) -> Vec<PathBuf> {
+        find_file(|face| face.glyph_index(c).is_some_and(|glyph| face.glyph_bounding_box(glyph).is_some()))
+            .into_iter()
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+use serde::Deserialize;
+
+use crate::api::{describe_error, parse_api_timestamp, ApiClient};
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::filters::CommitFilter;
+use crate::source::DataSource;
+
+pub const DEFAULT_BASE_URL: &str = "https://codeberg.org";
+
+/// Commits pushed by a user of a Gitea compatible forge (Gitea, Forgejo,
+/// Codeberg), read from the activity feed of the user.
+pub struct GiteaSource {
+    pub user: String,
+    pub token: Option<String>,
+    pub base_url: String,
+}
+
+#[derive(Deserialize)]
+struct FeedEntry {
+    op_type: String,
+    created: String,
+    #[serde(default)]
+    content: String,
+}
+
+/// The `content` of a `commit_repo` entry, a JSON encoded push summary
+#[derive(Deserialize)]
+struct PushCommits {
+    #[serde(rename = "Len")]
+    len: usize,
+}
+
+impl DataSource for GiteaSource {
+    fn describe(&self) -> String {
+        format!("Gitea user {} on {}", self.user, self.base_url)
+    }
+
+    fn collect(&self, filter: &CommitFilter) -> Result<Activity> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(("Authorization", format!("token {}", token)));
+        }
+        let client = ApiClient::new(headers);
+
+        let feed_url = format!(
+            "{}/api/v1/users/{}/activities/feeds?only-performed-by=true&limit=50",
+            self.base_url.trim_end_matches('/'),
+            self.user
+        );
+        let feed: Vec<FeedEntry> = client.get_all(&feed_url)
+            .map_err(|err| Error::Api(format!("Failed to read the activity feed of {}: {}", self.user, describe_error(&err))))?;
+
+        let timestamps = feed.iter()
+            .filter(|entry| entry.op_type == "commit_repo")
+            .flat_map(|entry| {
+                let commits = serde_json::from_str::<PushCommits>(&entry.content)
+                    .map(|push| push.len)
+                    .unwrap_or(1);
+                parse_api_timestamp(&entry.created)
+                    .into_iter()
+                    .flat_map(move |timestamp| std::iter::repeat_n(timestamp, commits))
+            });
+
+        Ok(Activity::from_timestamps(timestamps, filter))
+    }
+}
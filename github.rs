@@ -0,0 +1,96 @@
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::api::{describe_error, parse_api_timestamp, ApiClient};
+use crate::collect::{commits_per_year, Activity};
+use crate::error::{Error, Result};
+use crate::filters::CommitFilter;
+use crate::log;
+use crate::source::DataSource;
+
+pub const DEFAULT_BASE_URL: &str = "https://api.github.com";
+
+/// Commits of a GitHub user, fetched through the REST API without cloning
+pub struct GitHubSource {
+    pub user: String,
+    pub token: Option<String>,
+    /// API root, `https://<host>/api/v3` for GitHub Enterprise Server
+    pub base_url: String,
+}
+
+#[derive(Deserialize)]
+struct Repo {
+    name: String,
+    full_name: String,
+    fork: bool,
+}
+
+#[derive(Deserialize)]
+struct CommitEntry {
+    commit: Commit,
+}
+
+#[derive(Deserialize)]
+struct Commit {
+    committer: Signature,
+}
+
+#[derive(Deserialize)]
+struct Signature {
+    date: String,
+}
+
+impl GitHubSource {
+    fn client(&self) -> ApiClient {
+        let mut headers = vec![("Accept", "application/vnd.github+json".to_string())];
+        if let Some(token) = &self.token {
+            headers.push(("Authorization", format!("Bearer {}", token)));
+        }
+        ApiClient::new(headers)
+    }
+}
+
+impl DataSource for GitHubSource {
+    fn describe(&self) -> String {
+        format!("GitHub user {}", self.user)
+    }
+
+    fn collect(&self, filter: &CommitFilter) -> Result<Activity> {
+        let client = self.client();
+        let api_url = self.base_url.trim_end_matches('/');
+
+        // With a token the private repositories of the user are visible too
+        let repos_url = if self.token.is_some() {
+            format!("{}/user/repos?per_page=100", api_url)
+        } else {
+            format!("{}/users/{}/repos?type=owner&per_page=100", api_url, self.user)
+        };
+        let repos: Vec<Repo> = client.get_all(&repos_url)
+            .map_err(|err| Error::Api(format!("Failed to list GitHub repositories of {}: {}", self.user, describe_error(&err))))?;
+
+        let mut timestamps = Vec::new();
+        let mut repo_commits = Vec::new();
+        // Forks mostly contain the upstream history, which would count twice
+        for repo in repos.iter().filter(|repo| !repo.fork) {
+            log::info!("Collecting commit dates for GitHub repo: {}", repo.full_name);
+            let commits_url = format!("{}/repos/{}/commits?author={}&per_page=100", api_url, repo.full_name, self.user);
+            let commits: Vec<CommitEntry> = match client.get_all(&commits_url) {
+                Ok(commits) => commits,
+                // Empty repositories answer with 409 Conflict
+                Err(err) if matches!(*err, ureq::Error::Status(409, _)) => continue,
+                Err(err) => return Err(Error::Api(format!("Failed to list commits of {}: {}", repo.full_name, describe_error(&err)))),
+            };
+
+            let repo_timestamps: Vec<NaiveDateTime> = commits.iter()
+                .filter_map(|entry| parse_api_timestamp(&entry.commit.committer.date))
+                .collect();
+            repo_commits.push((repo.name.clone(), commits_per_year(repo_timestamps.iter().filter(|timestamp| filter.matches(timestamp)))));
+            timestamps.extend(repo_timestamps);
+        }
+
+        Ok(Activity {
+            repo_commits,
+            ..Activity::from_timestamps(timestamps, filter)
+        })
+    }
+}
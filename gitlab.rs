@@ -0,0 +1,60 @@
+use serde::Deserialize;
+
+use crate::api::{describe_error, parse_api_timestamp, ApiClient};
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::filters::CommitFilter;
+use crate::source::DataSource;
+
+pub const DEFAULT_BASE_URL: &str = "https://gitlab.com";
+
+/// Commits pushed by a GitLab user, fetched from the events API. This works
+/// for self-hosted instances too and doesn't need access to every project,
+/// but GitLab only keeps events for the last three years.
+pub struct GitLabSource {
+    pub user: String,
+    pub token: Option<String>,
+    pub base_url: String,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    created_at: String,
+    push_data: Option<PushData>,
+}
+
+#[derive(Deserialize)]
+struct PushData {
+    commit_count: usize,
+}
+
+impl DataSource for GitLabSource {
+    fn describe(&self) -> String {
+        format!("GitLab user {} on {}", self.user, self.base_url)
+    }
+
+    fn collect(&self, filter: &CommitFilter) -> Result<Activity> {
+        let mut headers = Vec::new();
+        if let Some(token) = &self.token {
+            headers.push(("PRIVATE-TOKEN", token.clone()));
+        }
+        let client = ApiClient::new(headers);
+
+        let events_url = format!(
+            "{}/api/v4/users/{}/events?action=pushed&per_page=100",
+            self.base_url.trim_end_matches('/'),
+            self.user
+        );
+        let events: Vec<Event> = client.get_all(&events_url)
+            .map_err(|err| Error::Api(format!("Failed to list GitLab events of {}: {}", self.user, describe_error(&err))))?;
+
+        let timestamps = events.iter().flat_map(|event| {
+            let commits = event.push_data.as_ref().map(|push| push.commit_count).unwrap_or(0);
+            parse_api_timestamp(&event.created_at)
+                .into_iter()
+                .flat_map(move |timestamp| std::iter::repeat_n(timestamp, commits))
+        });
+
+        Ok(Activity::from_timestamps(timestamps, filter))
+    }
+}
@@ -0,0 +1,54 @@
+use chrono::NaiveDate;
+use image::{imageops, ImageBuffer, Rgba};
+
+use crate::fonts::FontChain;
+use crate::render::{draw_sharp_text, Grid};
+use crate::theme::Theme;
+
+/// Height of the title band at the top of the image, for the default
+/// label unit, see `Grid::px`
+pub const HEADER_HEIGHT: u32 = 56;
+
+/// Whose activity an image shows, drawn as a title band at the top
+#[derive(Debug, Clone)]
+pub struct Header {
+    pub author: String,
+    /// Number of repositories analyzed, when the source knows it
+    pub repos: Option<usize>,
+    /// Dates of the first and the last commit
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Header {
+    /// Second line of the header, e.g. "3 repositories · Mar 1, 2023 - Mar
+    /// 30, 2024". It has no date of its own, so that the same history
+    /// gives the same image on any day.
+    pub fn subtitle(&self) -> String {
+        let mut parts = Vec::new();
+        match self.repos {
+            Some(1) => parts.push("1 repository".to_string()),
+            Some(repos) => parts.push(format!("{} repositories", repos)),
+            None => {}
+        }
+        parts.push(format!("{} - {}", self.first.format("%b %-d, %Y"), self.last.format("%b %-d, %Y")));
+        parts.join(" · ")
+    }
+}
+
+/// Puts the header band on top of the image
+pub fn add_header(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, header: &Header, grid: &Grid, theme: &Theme, font: &FontChain) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = grid.label_unit;
+    let height = grid.px(HEADER_HEIGHT);
+    let mut with_header = ImageBuffer::from_pixel(img.width(), img.height() + height, theme.background);
+
+    let x = grid.px(5) as i32;
+    draw_sharp_text(&mut with_header, &header.author, x, (label_unit / 2) as i32, label_unit as f32 * 2.0, theme.text_primary, font);
+    draw_sharp_text(&mut with_header, &header.subtitle(), x, (label_unit * 3) as i32, label_unit as f32 * 1.1, theme.text_secondary, font);
+    for x in 0..with_header.width() {
+        with_header.put_pixel(x, height - grid.px(8), theme.separator);
+    }
+
+    imageops::replace(&mut with_header, img, 0, height as i64);
+    with_header
+}
@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+
+use crate::error::{Error, Result};
+
+/// Years the holidays of countries and yearly events of calendars are
+/// listed for
+const YEARS: RangeInclusive<i32> = 1970..=2100;
+
+/// Countries whose public holidays can be given by their code, see
+/// `country_holidays`
+const COUNTRIES: [&str; 4] = ["de", "fr", "gb", "us"];
+
+/// Days off besides weekends, given with `--holidays`
+#[derive(Debug, Default)]
+pub struct Holidays {
+    dates: HashSet<NaiveDate>,
+}
+
+impl Holidays {
+    /// Loads holidays from an iCalendar file, a file with one `YYYY-MM-DD`
+    /// date per line, or from a comma-separated list of dates and country
+    /// codes given directly on the command line, e.g. `de,2024-12-24`.
+    pub fn load(spec: &str) -> Result<Holidays> {
+        if Path::new(spec).is_file() {
+            let content = fs::read_to_string(spec)
+                .map_err(|err| Error::Input(format!("Failed to read holidays file {}: {}", spec, err)))?;
+            let dates = if content.trim_start().starts_with("BEGIN:VCALENDAR") {
+                calendar_dates(&content).map_err(|err| Error::Input(format!("Invalid calendar {}: {}", spec, err)))?
+            } else {
+                content.lines()
+                    .map(|line| line.split('#').next().unwrap_or("").trim())
+                    .filter(|line| !line.is_empty())
+                    .map(parse_date)
+                    .collect::<Result<_>>()?
+            };
+            return Ok(Holidays { dates });
+        }
+
+        let mut dates = HashSet::new();
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let country = item.to_lowercase();
+            if COUNTRIES.contains(&country.as_str()) {
+                dates.extend(YEARS.flat_map(|year| country_holidays(&country, year)));
+            } else {
+                dates.insert(parse_date(item)?);
+            }
+        }
+        Ok(Holidays { dates })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dates.is_empty()
+    }
+
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.dates.contains(date)
+    }
+
+    /// Whether nobody is expected to work on this day
+    pub fn is_day_off(&self, date: &NaiveDate) -> bool {
+        is_weekend(date) || self.contains(date)
+    }
+}
+
+pub fn is_weekend(date: &NaiveDate) -> bool {
+    matches!(date.weekday(), Weekday::Sat | Weekday::Sun)
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+        Error::Input(format!("Invalid holiday '{}', expected YYYY-MM-DD or one of the countries {}", text, COUNTRIES.join(", ")))
+    })
+}
+
+/// The days of the events of an iCalendar file, from `DTSTART` up to but
+/// not including `DTEND`. Events repeating yearly are listed up to the
+/// `UNTIL` or `COUNT` of their rule, or else up to the end of `YEARS`,
+/// other repetitions are ignored.
+fn calendar_dates(content: &str) -> std::result::Result<HashSet<NaiveDate>, String> {
+    // Long lines are folded by starting the continuation with a space
+    let unfolded = content.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut dates = HashSet::new();
+    let mut start = None;
+    let mut end = None;
+    let mut rule = None;
+    for line in unfolded.lines() {
+        let Some((name, value)) = line.split_once(':') else { continue };
+        // Parameters like VALUE=DATE follow the name after a semicolon
+        let name = name.split(';').next().unwrap_or(name);
+        match name {
+            "BEGIN" if value == "VEVENT" => (start, end, rule) = (None, None, None),
+            "DTSTART" => start = Some(calendar_date(value)?),
+            "DTEND" => end = Some(calendar_date(value)?),
+            "RRULE" => rule = yearly_rule(value)?,
+            "END" if value == "VEVENT" => {
+                let Some(start) = start else { return Err("event without DTSTART".to_string()) };
+                let days = end.map_or(1, |end| (end - start).num_days().max(1));
+                let rule = rule.take().unwrap_or(YearlyRule::ONCE);
+                let mut occurrences = 0;
+                for year in (start.year()..=start.year().max(*YEARS.end())).step_by(rule.interval) {
+                    // Yearly events on February 29 only fall on leap years
+                    let Some(first) = start.with_year(year) else { continue };
+                    if rule.until.is_some_and(|until| first > until) || rule.count == Some(occurrences) {
+                        break;
+                    }
+                    occurrences += 1;
+                    dates.extend((0..days).map(|day| first + Duration::days(day)));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(dates)
+}
+
+/// How a yearly event repeats, see `yearly_rule`
+struct YearlyRule {
+    /// Every how many years
+    interval: usize,
+    /// Number of times, the first one included
+    count: Option<usize>,
+    /// Date of the last time at the latest
+    until: Option<NaiveDate>,
+}
+
+impl YearlyRule {
+    /// An event without a rule, which happens only once
+    const ONCE: YearlyRule = YearlyRule { interval: 1, count: Some(1), until: None };
+}
+
+/// The repetition of an `RRULE` value like `FREQ=YEARLY;INTERVAL=4` or
+/// `FREQ=YEARLY;UNTIL=20191231`, none for other frequencies
+fn yearly_rule(value: &str) -> std::result::Result<Option<YearlyRule>, String> {
+    let mut yearly = false;
+    let mut rule = YearlyRule { interval: 1, count: None, until: None };
+    for part in value.split(';') {
+        let (name, value) = part.split_once('=').unwrap_or((part, ""));
+        match name {
+            "FREQ" => yearly = value == "YEARLY",
+            "INTERVAL" => {
+                rule.interval = value.parse().ok().filter(|&interval| interval > 0)
+                    .ok_or_else(|| format!("invalid INTERVAL '{}'", value))?;
+            }
+            "COUNT" => rule.count = Some(value.parse().map_err(|_| format!("invalid COUNT '{}'", value))?),
+            "UNTIL" => rule.until = Some(calendar_date(value)?),
+            _ => {}
+        }
+    }
+    Ok(yearly.then_some(rule))
+}
+
+/// The date of a `DTSTART` or `DTEND` value, e.g. 20241225 or
+/// 20241225T090000Z
+fn calendar_date(value: &str) -> std::result::Result<NaiveDate, String> {
+    value.get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| format!("invalid date '{}'", value))
+}
+
+/// The nationwide public holidays of a country in a year, without the
+/// regional ones and without days moved off weekends
+fn country_holidays(country: &str, year: i32) -> Vec<NaiveDate> {
+    let date = |month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    let easter = easter(year);
+    let easter_offset = |days| easter + Duration::days(days);
+    let nth_weekday = |month, weekday, n| NaiveDate::from_weekday_of_month_opt(year, month, weekday, n).unwrap();
+    let last_weekday = |month, weekday| {
+        NaiveDate::from_weekday_of_month_opt(year, month, weekday, 5).unwrap_or_else(|| nth_weekday(month, weekday, 4))
+    };
+    match country {
+        "de" => vec![
+            date(1, 1), easter_offset(-2), easter_offset(1), date(5, 1), easter_offset(39), easter_offset(50),
+            date(10, 3), date(12, 25), date(12, 26),
+        ],
+        "fr" => vec![
+            date(1, 1), easter_offset(1), date(5, 1), date(5, 8), easter_offset(39), easter_offset(50),
+            date(7, 14), date(8, 15), date(11, 1), date(11, 11), date(12, 25),
+        ],
+        // England and Wales
+        "gb" => vec![
+            date(1, 1), easter_offset(-2), easter_offset(1), nth_weekday(5, Weekday::Mon, 1), last_weekday(5, Weekday::Mon),
+            last_weekday(8, Weekday::Mon), date(12, 25), date(12, 26),
+        ],
+        "us" => {
+            let mut dates = vec![
+                date(1, 1), nth_weekday(1, Weekday::Mon, 3), nth_weekday(2, Weekday::Mon, 3), last_weekday(5, Weekday::Mon),
+                date(7, 4), nth_weekday(9, Weekday::Mon, 1), nth_weekday(10, Weekday::Mon, 2), date(11, 11),
+                nth_weekday(11, Weekday::Thu, 4), date(12, 25),
+            ];
+            if year >= 2021 {
+                dates.push(date(6, 19));
+            }
+            dates
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Easter Sunday of the Gregorian calendar, after the anonymous algorithm
+/// published in Nature in 1876
+fn easter(year: i32) -> NaiveDate {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn easter_sundays() {
+        assert_eq!(easter(2000), ymd(2000, 4, 23));
+        assert_eq!(easter(2008), ymd(2008, 3, 23));
+        assert_eq!(easter(2024), ymd(2024, 3, 31));
+        assert_eq!(easter(2025), ymd(2025, 4, 20));
+        assert_eq!(easter(2038), ymd(2038, 4, 25));
+    }
+
+    #[test]
+    fn country_holidays_follow_easter_and_weekdays() {
+        let de = country_holidays("de", 2024);
+        assert!(de.contains(&ymd(2024, 3, 29)) && de.contains(&ymd(2024, 4, 1)) && de.contains(&ymd(2024, 5, 20)));
+        let gb = country_holidays("gb", 2024);
+        assert!(gb.contains(&ymd(2024, 5, 6)) && gb.contains(&ymd(2024, 5, 27)) && gb.contains(&ymd(2024, 8, 26)));
+        let us = country_holidays("us", 2024);
+        assert!(us.contains(&ymd(2024, 11, 28)) && us.contains(&ymd(2024, 6, 19)));
+        assert!(!country_holidays("us", 2020).contains(&ymd(2020, 6, 19)));
+    }
+
+    #[test]
+    fn lists_mix_countries_and_dates() {
+        let holidays = Holidays::load("FR, 2024-12-24").unwrap();
+        assert!(holidays.contains(&ymd(2024, 12, 24)) && holidays.contains(&ymd(1999, 7, 14)));
+        assert!(!holidays.contains(&ymd(2024, 12, 26)));
+        assert!(holidays.is_day_off(&ymd(2024, 12, 28)));
+        assert!(matches!(Holidays::load("xx"), Err(Error::Input(_))));
+    }
+
+    #[test]
+    fn calendar_events_span_up_to_their_end() {
+        let calendar = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nDTSTART;VALUE=DATE:20241224\r\nDTEND;VALUE=DATE:2024\r\n 1227\r\n\
+            END:VEVENT\r\nBEGIN:VEVENT\r\nDTSTART:20240611T090000Z\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let mut dates: Vec<_> = calendar_dates(calendar).unwrap().into_iter().collect();
+        dates.sort();
+        assert_eq!(dates, [ymd(2024, 6, 11), ymd(2024, 12, 24), ymd(2024, 12, 25), ymd(2024, 12, 26)]);
+    }
+
+    #[test]
+    fn yearly_calendar_events_repeat_from_their_start() {
+        let calendar = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20200229\nRRULE:FREQ=YEARLY;BYMONTH=2\n\
+            END:VEVENT\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20100501\nRRULE:FREQ=MONTHLY\nEND:VEVENT\nEND:VCALENDAR\n";
+        let dates = calendar_dates(calendar).unwrap();
+        assert!(dates.contains(&ymd(2020, 2, 29)) && dates.contains(&ymd(2024, 2, 29)));
+        assert!(!dates.contains(&ymd(2016, 2, 29)));
+        assert!(dates.contains(&ymd(2010, 5, 1)) && !dates.contains(&ymd(2010, 6, 1)));
+        // Every fourth year from 2020 to 2096, as 2100 isn't a leap year
+        assert_eq!(dates.len(), 1 + 20);
+    }
+
+    #[test]
+    fn yearly_calendar_events_end_with_their_rule() {
+        let calendar = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20150704\nRRULE:FREQ=YEARLY;UNTIL=20180704T000000Z\n\
+            END:VEVENT\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20160229\nRRULE:FREQ=YEARLY;COUNT=2\nEND:VEVENT\n\
+            BEGIN:VEVENT\nDTSTART;VALUE=DATE:20101111\nRRULE:FREQ=YEARLY;INTERVAL=5;COUNT=3\nEND:VEVENT\nEND:VCALENDAR\n";
+        let mut dates: Vec<_> = calendar_dates(calendar).unwrap().into_iter().collect();
+        dates.sort();
+        assert_eq!(dates, [
+            ymd(2010, 11, 11), ymd(2015, 7, 4), ymd(2015, 11, 11), ymd(2016, 2, 29), ymd(2016, 7, 4), ymd(2017, 7, 4),
+            ymd(2018, 7, 4), ymd(2020, 2, 29), ymd(2020, 11, 11),
+        ]);
+        assert!(calendar_dates("BEGIN:VEVENT\nDTSTART:20240101\nRRULE:FREQ=YEARLY;INTERVAL=0\nEND:VEVENT\n").is_err());
+    }
+
+    #[test]
+    fn invalid_calendars_are_rejected() {
+        assert!(calendar_dates("BEGIN:VEVENT\nSUMMARY:No start\nEND:VEVENT\n").is_err());
+        assert!(calendar_dates("BEGIN:VEVENT\nDTSTART:2024-12-24\nEND:VEVENT\n").is_err());
+    }
+}
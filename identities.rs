@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::config;
+use crate::error::{Error, Result};
+
+/// People defined in `identities.toml`, each with all the names, emails
+/// and forge logins they commit as, e.g.
+///
+/// ```toml
+/// [people.me]
+/// names = ["Jane Doe"]
+/// emails = ["jane@example.com", "jane.doe@work.example"]
+/// github = "janedoe"
+/// gitlab = "jdoe"
+/// gitea = "jane"
+/// ```
+///
+/// `@me` then stands for this person as author or `--user`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Identities {
+    #[serde(default)]
+    pub people: HashMap<String, Person>,
+    /// File the identities were read from, for messages
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+// The forge logins are only used by the sources of the net feature
+#[cfg_attr(not(feature = "net"), allow(dead_code))]
+pub struct Person {
+    #[serde(default)]
+    pub names: Vec<String>,
+    #[serde(default)]
+    pub emails: Vec<String>,
+    /// Login on GitHub, used for `--source github`
+    pub github: Option<String>,
+    /// Login on GitLab, used for `--source gitlab`
+    pub gitlab: Option<String>,
+    /// Login on Gitea, Forgejo or Codeberg
+    pub gitea: Option<String>,
+}
+
+impl Identities {
+    /// Loads the file given with `--identities`, or the default one in
+    /// `$XDG_CONFIG_HOME/commits-tilewall/identities.toml` when it exists.
+    pub fn load(path: Option<&str>) -> Result<Identities> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match config::config_dir().map(|dir| dir.join("identities.toml")) {
+                Some(path) if path.is_file() => path,
+                _ => return Ok(Identities::default()),
+            },
+        };
+
+        let content = fs::read_to_string(&path)
+            .map_err(|err| Error::Input(format!("Failed to read identities file {}: {}", path.display(), err)))?;
+        let mut identities: Identities = toml::from_str(&content)
+            .map_err(|err| Error::Input(format!("Failed to parse identities file {}: {}", path.display(), err)))?;
+        for (name, person) in &identities.people {
+            if person.names.is_empty() && person.emails.is_empty() {
+                return Err(Error::Input(format!("Identity '{}' in {} has neither names nor emails", name, path.display())));
+            }
+        }
+        identities.path = path;
+        Ok(identities)
+    }
+
+    /// Looks up `@name`, giving `None` for arguments without the `@`
+    pub fn person(&self, arg: &str) -> Result<Option<(&str, &Person)>> {
+        let Some(name) = arg.strip_prefix('@') else {
+            return Ok(None);
+        };
+        match self.people.get_key_value(name) {
+            Some((name, person)) => Ok(Some((name.as_str(), person))),
+            None if self.path.as_os_str().is_empty() => {
+                Err(Error::Usage(format!("No identity '{}', as there is no identities file", name)))
+            }
+            None => Err(Error::Usage(format!("No identity '{}' in {}", name, self.path.display()))),
+        }
+    }
+
+    /// The login of `arg` on a forge, resolving `@name` to the login of
+    /// that person on the forge of `source`
+    #[cfg_attr(not(feature = "net"), allow(dead_code))]
+    pub fn forge_user(&self, arg: &str, source: &str) -> Result<String> {
+        let Some((name, person)) = self.person(arg)? else {
+            return Ok(arg.to_string());
+        };
+        let user = match source {
+            "github" => &person.github,
+            "gitlab" => &person.gitlab,
+            _ => &person.gitea,
+        };
+        user.clone().ok_or_else(|| {
+            Error::Usage(format!("Identity '{}' in {} has no {} login", name, self.path.display(), source))
+        })
+    }
+}
+
+impl Person {
+    /// Patterns for `git log --author`, one per name and email, each
+    /// matching only that exact name or email. git matches them against
+    /// `Name <email>`.
+    pub fn author_patterns(&self) -> Vec<String> {
+        let names = self.names.iter().map(|name| format!("^{} <", escape_pattern(name)));
+        let emails = self.emails.iter().map(|email| format!("<{}>$", escape_pattern(email)));
+        names.chain(emails).collect()
+    }
+
+    /// Name shown on the wall and used in file names
+    pub fn display_name<'a>(&'a self, name: &'a str) -> &'a str {
+        self.names.first().map(String::as_str).unwrap_or(name)
+    }
+}
+
+/// Escapes the characters special in the basic regular expressions of
+/// `git log --author`
+fn escape_pattern(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if matches!(c, '.' | '[' | ']' | '*' | '^' | '$' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
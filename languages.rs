@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use image::{ImageBuffer, Rgba};
+
+use crate::collect::git_output;
+use crate::error::Result;
+use crate::fonts::FontChain;
+use crate::panels::Panel;
+use crate::render::{draw_block, draw_sharp_text, Grid};
+use crate::stats::YearStats;
+use crate::theme::Theme;
+
+/// Programming language of a repository, with its color as used by
+/// GitHub's Linguist
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Language {
+    pub name: &'static str,
+    pub color: Rgba<u8>,
+}
+
+/// Language name, Linguist color and file extensions
+const LANGUAGES: &[(&str, [u8; 3], &[&str])] = &[
+    ("Assembly", [0x6e, 0x4c, 0x13], &["asm", "s", "S"]),
+    ("C", [0x55, 0x55, 0x55], &["c", "h"]),
+    ("C#", [0x17, 0x86, 0x00], &["cs"]),
+    ("C++", [0xf3, 0x4b, 0x7d], &["cc", "cpp", "cxx", "hh", "hpp", "hxx"]),
+    ("CSS", [0x56, 0x3d, 0x7c], &["css", "scss"]),
+    ("Dart", [0x00, 0xb4, 0xab], &["dart"]),
+    ("Elixir", [0x6e, 0x4a, 0x7e], &["ex", "exs"]),
+    ("Emacs Lisp", [0xc0, 0x65, 0xdb], &["el"]),
+    ("Erlang", [0xb8, 0x39, 0x98], &["erl", "hrl"]),
+    ("Go", [0x00, 0xad, 0xd8], &["go"]),
+    ("HTML", [0xe3, 0x4c, 0x26], &["htm", "html"]),
+    ("Haskell", [0x5e, 0x50, 0x86], &["hs"]),
+    ("Java", [0xb0, 0x72, 0x19], &["java"]),
+    ("JavaScript", [0xf1, 0xe0, 0x5a], &["cjs", "js", "jsx", "mjs"]),
+    ("Kotlin", [0xa9, 0x7b, 0xff], &["kt", "kts"]),
+    ("Lua", [0x00, 0x00, 0x80], &["lua"]),
+    ("Nix", [0x7e, 0x7e, 0xff], &["nix"]),
+    ("Objective-C", [0x43, 0x8e, 0xff], &["m", "mm"]),
+    ("PHP", [0x4f, 0x5d, 0x95], &["php"]),
+    ("Perl", [0x02, 0x98, 0xc3], &["pl", "pm"]),
+    ("Python", [0x35, 0x72, 0xa5], &["py", "pyi"]),
+    ("Ruby", [0x70, 0x15, 0x16], &["rb"]),
+    ("Rust", [0xde, 0xa5, 0x84], &["rs"]),
+    ("Scala", [0xc2, 0x2d, 0x40], &["scala"]),
+    ("Shell", [0x89, 0xe0, 0x51], &["bash", "sh", "zsh"]),
+    ("Swift", [0xf0, 0x51, 0x38], &["swift"]),
+    ("TypeScript", [0x31, 0x78, 0xc6], &["ts", "tsx"]),
+    ("Vim Script", [0x19, 0x9f, 0x4b], &["vim"]),
+    ("Vue", [0x41, 0xb8, 0x83], &["vue"]),
+    ("Zig", [0xec, 0x91, 0x5c], &["zig"]),
+];
+
+/// Language of a file, by its extension
+pub fn language_of(path: &str) -> Option<Language> {
+    let extension = Path::new(path).extension()?.to_str()?;
+    LANGUAGES.iter()
+        .find(|(_, _, extensions)| extensions.contains(&extension))
+        .map(|&(name, [r, g, b], _)| Language { name, color: Rgba([r, g, b, 255]) })
+}
+
+/// The language of the given name, e.g. from `YearStats::languages`
+pub fn language_named(name: &str) -> Option<Language> {
+    LANGUAGES.iter()
+        .find(|(language, _, _)| *language == name)
+        .map(|&(name, [r, g, b], _)| Language { name, color: Rgba([r, g, b, 255]) })
+}
+
+/// Languages named in the breakdown, the others are summed up
+const BREAKDOWN_LANGUAGES: usize = 3;
+
+/// Share of the lines changed per language in every year, e.g. "Rust 64%",
+/// by the extensions of the changed files, see `--languages`
+#[derive(Debug)]
+pub struct LanguageBreakdown;
+
+impl Panel for LanguageBreakdown {
+    fn measure(&self, stats: &YearStats, grid: &Grid) -> u32 {
+        if stats.languages.is_empty() { 0 } else { grid.px(100) }
+    }
+
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain) {
+        let label_unit = grid.label_unit;
+        let total: i32 = stats.languages.iter().map(|(_, lines)| lines).sum();
+        if total == 0 {
+            return;
+        }
+        let percent = |lines: i32| (lines as f64 * 100.0 / total as f64).round();
+
+        let mut rows: Vec<(String, Rgba<u8>)> = stats.languages.iter()
+            .take(BREAKDOWN_LANGUAGES)
+            .map(|(name, lines)| {
+                let color = language_named(name).map(|language| language.color).unwrap_or(theme.text_secondary);
+                (format!("{} {}%", name, percent(*lines)), color)
+            })
+            .collect();
+        if stats.languages.len() > BREAKDOWN_LANGUAGES {
+            let other: i32 = stats.languages[BREAKDOWN_LANGUAGES..].iter().map(|(_, lines)| lines).sum();
+            rows.push((format!("Other {}%", percent(other)), theme.separator));
+        }
+        for (i, (text, color)) in rows.iter().enumerate() {
+            let row_y = y + i as u32 * (label_unit + grid.px(2));
+            draw_block(img, x, row_y, label_unit, *color);
+            draw_sharp_text(img, text, (x + label_unit + grid.px(4)) as i32, row_y as i32, label_unit as f32 * 0.8, theme.text_secondary, font);
+        }
+    }
+}
+
+/// The language with the most files in the tree of the first configured
+/// branch, or of HEAD. Files of unknown languages, like documentation, are
+/// not counted.
+pub fn detect_language(repo: &str, branches: &[String]) -> Result<Option<Language>> {
+    let revision = branches.first().map(String::as_str).unwrap_or("HEAD");
+    let mut command = Command::new("git");
+    command.args(["ls-tree", "-r", "--name-only", revision]);
+    let output = git_output(&mut command, repo)?;
+
+    let mut files: HashMap<&'static str, (usize, Language)> = HashMap::new();
+    for path in output.lines() {
+        if let Some(language) = language_of(path) {
+            files.entry(language.name).or_insert((0, language)).0 += 1;
+        }
+    }
+
+    Ok(files.into_values()
+        .max_by(|(a, a_language), (b, b_language)| a.cmp(b).then(b_language.name.cmp(a_language.name)))
+        .map(|(_, language)| language))
+}
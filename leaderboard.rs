@@ -0,0 +1,282 @@
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use ab_glyph::PxScale;
+use image::{imageops, ImageBuffer, Rgba};
+
+use crate::authors;
+use crate::cache::CacheMode;
+use crate::collect::{collect_activity, Activity};
+use crate::config::Config;
+use crate::discover;
+use crate::error::{Error, Result};
+use crate::filters::{CommitFilter, YearFilter};
+use crate::fonts::FontChain;
+use crate::holidays::Holidays;
+use crate::identities::Person;
+use crate::log;
+use crate::output;
+use crate::remote;
+use crate::render::{align_years, draw_sharp_text, generate_author_walls, AuthorWall, RenderOptions};
+use crate::stats::{year_stats, Levels};
+use crate::theme::Theme;
+
+/// Contributors ranked when `--top` isn't given
+const DEFAULT_TOP: usize = 10;
+
+/// Height of a row of the ranking table
+const ROW_HEIGHT: u32 = 22;
+
+/// Space between the columns of the ranking table
+const COLUMN_SPACING: u32 = 24;
+
+/// What the contributors are ranked by, given with `--by`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ranking {
+    Commits,
+    /// Insertions and deletions
+    Lines,
+}
+
+impl Ranking {
+    pub fn parse(ranking: &str) -> Result<Ranking> {
+        match ranking {
+            "commits" => Ok(Ranking::Commits),
+            "lines" => Ok(Ranking::Lines),
+            _ => Err(Error::Usage(format!("Unknown ranking '{}', expected commits or lines", ranking))),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Ranking::Commits => "commits",
+            Ranking::Lines => "lines changed",
+        }
+    }
+}
+
+/// A contributor along with the numbers it's ranked by
+struct Entry {
+    name: String,
+    activity: Activity,
+    commits: usize,
+    insertions: i32,
+    deletions: i32,
+    active_days: usize,
+}
+
+impl Entry {
+    fn score(&self, ranking: Ranking) -> i64 {
+        match ranking {
+            Ranking::Commits => self.commits as i64,
+            Ranking::Lines => self.insertions as i64 + self.deletions as i64,
+        }
+    }
+}
+
+/// Ranks the contributors of the repositories, e.g.
+/// `leaderboard --top 5 --by lines --years 2024 ~/src/project`, and
+/// renders a table of the ranking above a compact wall of every ranked
+/// contributor. The identities of a contributor are merged like for the
+/// ambiguity check, see `authors::distinct_people`.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut repos = Vec::new();
+    let mut top = DEFAULT_TOP;
+    let mut ranking = Ranking::Commits;
+    let mut filter = CommitFilter::default();
+    let mut theme = "light";
+    let mut fonts = Vec::new();
+    let mut config_path = None;
+    let mut output_path = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--top" && i + 1 < args.len() {
+            top = match args[i + 1].parse() {
+                Ok(top) if top > 0 => top,
+                _ => return Err(Error::Usage(format!("Invalid --top '{}', expected a positive number", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--by" && i + 1 < args.len() {
+            ranking = Ranking::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--years" && i + 1 < args.len() {
+            filter.years = Some(YearFilter::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--include-bots" {
+            filter.include_bots = true;
+            i += 1;
+        } else if args[i] == "--exclude-bots" && i + 1 < args.len() {
+            filter.bot_patterns.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--theme" && i + 1 < args.len() {
+            theme = &args[i + 1];
+            i += 2;
+        } else if args[i] == "--font" && i + 1 < args.len() {
+            fonts.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--config" && i + 1 < args.len() {
+            config_path = Some(args[i + 1].as_str());
+            i += 2;
+        } else if args[i] == "--output" && i + 1 < args.len() {
+            output_path = Some(PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--quiet" || args[i] == "-q" {
+            log::set_level(log::Level::Quiet);
+            i += 1;
+        } else if args[i] == "--verbose" || args[i] == "-v" {
+            log::set_level(log::Level::Verbose);
+            i += 1;
+        } else if args[i].starts_with('-') {
+            return Err(Error::Usage(format!("Unknown leaderboard option '{}'", args[i])));
+        } else {
+            repos.push(args[i].clone());
+            i += 1;
+        }
+    }
+    if repos.is_empty() {
+        return Err(Error::Usage("leaderboard needs at least one repository".to_string()));
+    }
+    if !filter.bot_patterns.is_empty() && filter.include_bots {
+        return Err(Error::Usage("--exclude-bots can't be combined with --include-bots".to_string()));
+    }
+
+    let config = Config::load(config_path)?;
+    discover::validate_repositories(&repos)?;
+    discover::resolve_repositories(&mut repos);
+    // Remote repositories are analyzed from temporary clones, which are
+    // removed again when `clones` goes out of scope
+    let mut clones = Vec::new();
+    for repo in repos.iter_mut() {
+        if remote::is_remote_url(repo) {
+            let clone = remote::clone_remote(repo, clones.len())?;
+            *repo = clone.path().to_string_lossy().into_owned();
+            clones.push(clone);
+        }
+    }
+
+    let mut identities = authors::matching_identities(&[], &repos, &config)?;
+    if !filter.include_bots {
+        identities.retain(|identity| !authors::is_bot(&identity.name, &identity.email, &filter.bot_patterns));
+    }
+    let people = authors::distinct_people(&identities);
+    log::info!("Found {} contributors in {} repositories", people.len(), repos.len());
+
+    let mut entries = Vec::new();
+    for identities in &people {
+        let person = Person {
+            names: identities.iter().map(|identity| identity.name.clone()).collect::<BTreeSet<_>>().into_iter().collect(),
+            emails: identities.iter().map(|identity| identity.email.clone()).collect::<BTreeSet<_>>().into_iter().collect(),
+            ..Default::default()
+        };
+        log::verbose!("Collecting the commits of {}", identities.iter().map(|identity| identity.to_string()).collect::<Vec<_>>().join(", "));
+        let activity = collect_activity(&person.author_patterns(), &repos, &config, &filter, CacheMode::Use)?;
+        let diff = activity.diff_stats.values().fold((0, 0), |(insertions, deletions), diff| {
+            (insertions + diff.insertions, deletions + diff.deletions)
+        });
+        entries.push(Entry {
+            name: identities[0].name.clone(),
+            commits: activity.timestamps.len(),
+            insertions: diff.0,
+            deletions: diff.1,
+            active_days: activity.commits.len(),
+            activity,
+        });
+    }
+    drop(clones);
+
+    // Ties keep the alphabetical order of the people
+    entries.retain(|entry| entry.score(ranking) > 0);
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.score(ranking)));
+    entries.truncate(top);
+    if entries.is_empty() {
+        return Err(Error::NoCommits("any author".to_string()));
+    }
+    for (rank, entry) in entries.iter().enumerate() {
+        log::info!("{}. {}: {} commits, +{} -{} lines, {} active days", rank + 1, entry.name, entry.commits, entry.insertions, entry.deletions, entry.active_days);
+    }
+
+    let font = FontChain::new(&fonts)?;
+    let theme = Theme::resolve(theme)?;
+    let img = generate_leaderboard(entries, ranking, &theme, &font);
+    let output_path = match output_path {
+        Some(path) => path,
+        None => std::env::current_dir()
+            .map_err(|err| Error::Output(format!("Failed to get the current directory: {}", err)))?
+            .join("commit_leaderboard.png"),
+    };
+    output::save_png(&img, &output_path, None, None, None)?;
+    println!("Wrote {}", output_path.display());
+    Ok(())
+}
+
+/// Renders the ranking table above the walls of the ranked contributors,
+/// which leave out the summary to stay compact
+fn generate_leaderboard(entries: Vec<Entry>, ranking: Ranking, theme: &Theme, font: &FontChain) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    // The table is filled before the activities move into the walls
+    let mut rows = vec![vec![
+        "#".to_string(),
+        "Contributor".to_string(),
+        "Commits".to_string(),
+        "Insertions".to_string(),
+        "Deletions".to_string(),
+        "Active days".to_string(),
+    ]];
+    for (rank, entry) in entries.iter().enumerate() {
+        rows.push(vec![
+            (rank + 1).to_string(),
+            entry.name.clone(),
+            entry.commits.to_string(),
+            format!("+{}", entry.insertions),
+            format!("-{}", entry.deletions),
+            entry.active_days.to_string(),
+        ]);
+    }
+    let mut walls: Vec<AuthorWall> = entries.into_iter()
+        .enumerate()
+        .map(|(rank, entry)| AuthorWall {
+            author: format!("{}. {}", rank + 1, entry.name),
+            active_years: year_stats(&entry.activity, &Holidays::default(), None, &Levels::default()),
+            activity: entry.activity,
+        })
+        .collect();
+    align_years(&mut walls);
+    let options = RenderOptions { no_stats: true, ..Default::default() };
+    let walls_img = generate_author_walls(&walls, &Holidays::default(), theme, font, &options);
+
+    let scale = PxScale::from(14.0);
+    let column_widths: Vec<u32> = (0..rows[0].len())
+        .map(|column| rows.iter().map(|row| font.text_width(&row[column], scale).ceil() as u32).max().unwrap_or(0))
+        .collect();
+
+    let title_height = 40;
+    let table_width = column_widths.iter().sum::<u32>() + COLUMN_SPACING * column_widths.len() as u32;
+    let table_height = title_height + rows.len() as u32 * ROW_HEIGHT + ROW_HEIGHT;
+    let width = walls_img.width().max(table_width + 10);
+    let mut img = ImageBuffer::from_pixel(width, table_height + walls_img.height(), theme.background);
+
+    let title = format!("Top {} contributors by {}", walls.len(), ranking.name());
+    draw_sharp_text(&mut img, &title, 5, 5, 20.0, theme.text_primary, font);
+    for (i, row) in rows.iter().enumerate() {
+        let y = (title_height + i as u32 * ROW_HEIGHT) as i32;
+        let color = if i == 0 { theme.text_secondary } else { theme.text_primary };
+        let mut x = 5;
+        for (column, text) in row.iter().enumerate() {
+            // The contributor is left aligned, the numbers right aligned
+            let text_x = if column == 1 {
+                x
+            } else {
+                x + column_widths[column] - font.text_width(text, scale).ceil() as u32
+            };
+            draw_sharp_text(&mut img, text, text_x as i32, y, 14.0, color, font);
+            x += column_widths[column] + COLUMN_SPACING;
+        }
+        if i == 0 {
+            for line_x in 5..5 + table_width - COLUMN_SPACING {
+                img.put_pixel(line_x, y as u32 + ROW_HEIGHT - 4, theme.separator);
+            }
+        }
+    }
+    imageops::replace(&mut img, &walls_img, 0, table_height as i64);
+
+    img
+}
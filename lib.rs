@@ -0,0 +1,62 @@
+//! The wall renderer behind the `commits-tilewall` command. Besides the
+//! modules the command is built from, the crate exposes what custom summary
+//! panels need: a `Panel` draws a year from its `YearStats`, which are
+//! computed from the `Activity` collected from the repositories, and is
+//! added to the wall with `RenderOptions::register_panel`.
+
+#[cfg(feature = "net")]
+mod api;
+pub mod authors;
+pub mod badge;
+pub mod cache;
+pub mod cadence;
+pub mod collect;
+pub mod config;
+#[cfg(feature = "net")]
+pub mod crates_io;
+pub mod cumulative;
+pub mod discover;
+pub mod error;
+pub mod export;
+pub mod filters;
+pub mod fonts;
+#[cfg(feature = "net")]
+pub mod gitea;
+#[cfg(feature = "net")]
+pub mod github;
+#[cfg(feature = "net")]
+pub mod gitlab;
+pub mod header;
+pub mod holidays;
+pub mod identities;
+pub mod languages;
+pub mod locale;
+pub mod leaderboard;
+pub mod log;
+pub mod narrative;
+pub mod output;
+pub mod ownership;
+pub mod panels;
+pub mod pdf;
+pub mod poster;
+pub mod preview;
+pub mod punchcard;
+pub mod qr;
+pub mod remote;
+pub mod render;
+pub mod serve;
+mod sessions;
+pub mod source;
+pub mod stats;
+pub mod svg;
+pub mod swatches;
+pub mod theme;
+mod vector;
+pub mod watch;
+mod wellness;
+mod woff2;
+
+pub use collect::Activity;
+pub use panels::Panel;
+pub use render::RenderOptions;
+pub use stats::YearStats;
@@ -0,0 +1,370 @@
+use std::env;
+
+use chrono::{Duration, NaiveTime, Weekday};
+use pure_rust_locales::{locale_match, Locale as LocaleData};
+
+use crate::error::{Error, Result};
+use crate::stats::{Metric, LEVELS};
+
+/// Summary texts of one language, `{}` standing for the numbers
+#[derive(Debug)]
+struct Phrases {
+    /// Prefix of the locale names using them, e.g. "de_"
+    language: &'static str,
+    commits_total: &'static str,
+    files_changed: &'static str,
+    binary_files: &'static str,
+    insertions: &'static str,
+    deletions: &'static str,
+    /// "N days with" a level label
+    level_days: &'static str,
+    /// Labels of the levels of commits, lines and points, see
+    /// `Metric::level`
+    commit_levels: [&'static str; LEVELS],
+    line_levels: [&'static str; LEVELS],
+    point_levels: [&'static str; LEVELS],
+    /// Labels of levels depending on the days, `{}` standing for a number
+    /// or a range of them, see `Levels::relative`
+    commit_range: &'static str,
+    line_range: &'static str,
+    point_range: &'static str,
+    /// Notes below the legend, see `render::wall_notes`
+    working_days: &'static str,
+    weekends: &'static str,
+    weekends_holidays: &'static str,
+    first_commit: &'static str,
+    /// Sessions, sessions per week, hours and minutes
+    sessions: &'static str,
+    /// Percent, hit days, planned days and the weekdays
+    adherence: &'static str,
+    more_repos: &'static str,
+    weighted: &'static str,
+}
+
+const ENGLISH: Phrases = Phrases {
+    language: "en_",
+    commits_total: "{} commits total",
+    files_changed: "{} files changed",
+    binary_files: "{} binary files",
+    insertions: "{} insertions(+)",
+    deletions: "{} deletions(-)",
+    level_days: "{} days with {}",
+    commit_levels: ["1 commit", "2-4 commits", "5-9 commits", "10-19 commits", "20+ commits"],
+    line_levels: ["under 10 lines", "10-49 lines", "50-199 lines", "200-499 lines", "500+ lines"],
+    point_levels: ["under 2 points", "2-4 points", "5-9 points", "10-19 points", "20+ points"],
+    commit_range: "{} commits",
+    line_range: "{} lines",
+    point_range: "{} points",
+    working_days: "{}% on working days, {}% on {}",
+    weekends: "weekends",
+    weekends_holidays: "weekends/holidays",
+    first_commit: "First commit of the day: median {}, earliest {}, latest {}",
+    sessions: "{} sessions, {} per week, {}h {}m on average",
+    adherence: "Hit {}% of planned days ({} of {}, {})",
+    more_repos: "{} more",
+    weighted: "weighted: {}",
+};
+
+/// Languages the summary is translated to, others get the English texts
+const TRANSLATIONS: &[Phrases] = &[
+    Phrases {
+        language: "de_",
+        commits_total: "{} Commits insgesamt",
+        files_changed: "{} Dateien geändert",
+        binary_files: "{} Binärdateien",
+        insertions: "{} Einfügungen(+)",
+        deletions: "{} Löschungen(-)",
+        level_days: "{} Tage mit {}",
+        commit_levels: ["1 Commit", "2-4 Commits", "5-9 Commits", "10-19 Commits", "20+ Commits"],
+        line_levels: ["unter 10 Zeilen", "10-49 Zeilen", "50-199 Zeilen", "200-499 Zeilen", "500+ Zeilen"],
+        point_levels: ["unter 2 Punkten", "2-4 Punkten", "5-9 Punkten", "10-19 Punkten", "20+ Punkten"],
+        commit_range: "{} Commits",
+        line_range: "{} Zeilen",
+        point_range: "{} Punkten",
+        working_days: "{} % an Werktagen, {} % an {}",
+        weekends: "Wochenenden",
+        weekends_holidays: "Wochenenden/Feiertagen",
+        first_commit: "Erster Commit des Tages: Median {}, frühestens {}, spätestens {}",
+        sessions: "{} Sitzungen, {} pro Woche, durchschnittlich {} h {} min",
+        adherence: "{} % der geplanten Tage erreicht ({} von {}, {})",
+        more_repos: "{} weitere",
+        weighted: "gewichtet: {}",
+    },
+    Phrases {
+        language: "es_",
+        commits_total: "{} commits en total",
+        files_changed: "{} archivos cambiados",
+        binary_files: "{} archivos binarios",
+        insertions: "{} inserciones(+)",
+        deletions: "{} eliminaciones(-)",
+        level_days: "{} días con {}",
+        commit_levels: ["1 commit", "2-4 commits", "5-9 commits", "10-19 commits", "20+ commits"],
+        line_levels: ["menos de 10 líneas", "10-49 líneas", "50-199 líneas", "200-499 líneas", "500+ líneas"],
+        point_levels: ["menos de 2 puntos", "2-4 puntos", "5-9 puntos", "10-19 puntos", "20+ puntos"],
+        commit_range: "{} commits",
+        line_range: "{} líneas",
+        point_range: "{} puntos",
+        working_days: "{} % en días laborables, {} % en {}",
+        weekends: "fines de semana",
+        weekends_holidays: "fines de semana/festivos",
+        first_commit: "Primer commit del día: mediana {}, más temprano {}, más tarde {}",
+        sessions: "{} sesiones, {} por semana, {} h {} min de media",
+        adherence: "{} % de los días planificados cumplidos ({} de {}, {})",
+        more_repos: "{} más",
+        weighted: "ponderado: {}",
+    },
+    Phrases {
+        language: "fr_",
+        commits_total: "{} commits au total",
+        files_changed: "{} fichiers modifiés",
+        binary_files: "{} fichiers binaires",
+        insertions: "{} insertions(+)",
+        deletions: "{} suppressions(-)",
+        level_days: "{} jours avec {}",
+        commit_levels: ["1 commit", "2-4 commits", "5-9 commits", "10-19 commits", "20+ commits"],
+        line_levels: ["moins de 10 lignes", "10-49 lignes", "50-199 lignes", "200-499 lignes", "500+ lignes"],
+        point_levels: ["moins de 2 points", "2-4 points", "5-9 points", "10-19 points", "20+ points"],
+        commit_range: "{} commits",
+        line_range: "{} lignes",
+        point_range: "{} points",
+        working_days: "{} % les jours ouvrés, {} % les {}",
+        weekends: "week-ends",
+        weekends_holidays: "week-ends/jours fériés",
+        first_commit: "Premier commit du jour : médiane {}, au plus tôt {}, au plus tard {}",
+        sessions: "{} sessions, {} par semaine, {} h {} min en moyenne",
+        adherence: "{} % des jours prévus atteints ({} sur {}, {})",
+        more_repos: "{} de plus",
+        weighted: "pondéré : {}",
+    },
+];
+
+/// Language of the month names, the numbers and the summary, given with
+/// `--locale`. Month and weekday names and the digit grouping come from
+/// the glibc locale data, the summary texts from `TRANSLATIONS`.
+#[derive(Debug, Clone, Copy)]
+pub struct Locale {
+    data: LocaleData,
+    phrases: &'static Phrases,
+}
+
+impl Default for Locale {
+    /// English names without digit grouping, as before locales existed
+    fn default() -> Self {
+        Locale { data: LocaleData::POSIX, phrases: &ENGLISH }
+    }
+}
+
+impl Locale {
+    /// Parses a locale name like `de_DE`, `de_DE.UTF-8`, `de-DE` or just
+    /// `de`, or `system` for the locale of the environment
+    pub fn parse(name: &str) -> Result<Locale> {
+        if name == "system" {
+            return Ok(Locale::from_env());
+        }
+        Locale::lookup(name).ok_or_else(|| Error::Usage(format!("Unknown locale '{}', expected e.g. de_DE, fr or system", name)))
+    }
+
+    /// The locale of `LC_ALL`, `LC_TIME` or `LANG`, the first one set, or
+    /// the default one when it's unknown
+    fn from_env() -> Locale {
+        ["LC_ALL", "LC_TIME", "LANG"].iter()
+            .filter_map(|variable| env::var(variable).ok())
+            .find(|value| !value.is_empty())
+            .and_then(|value| Locale::lookup(&value))
+            .unwrap_or_default()
+    }
+
+    fn lookup(name: &str) -> Option<Locale> {
+        // Leaves out the encoding and any modifier, e.g. of de_DE.UTF-8
+        let name = name.split(['.', '@']).next().unwrap_or(name).replace('-', "_");
+        let data = match name.as_str() {
+            "C" | "POSIX" => LocaleData::POSIX,
+            language if !language.contains('_') => {
+                LocaleData::try_from(format!("{}_{}", language, language.to_uppercase()).as_str()).ok()?
+            }
+            name => LocaleData::try_from(name).ok()?,
+        };
+        let data_name = data.to_string();
+        let phrases = TRANSLATIONS.iter()
+            .find(|phrases| data_name.starts_with(phrases.language))
+            .unwrap_or(&ENGLISH);
+        Some(Locale { data, phrases })
+    }
+
+    /// Abbreviated name of a month, 1 being January
+    pub fn month_abbr(&self, month: u32) -> String {
+        let names = locale_match!(self.data => LC_TIME::ABMON);
+        names[month as usize - 1].to_string()
+    }
+
+    pub fn month_name(&self, month: u32) -> String {
+        let names = locale_match!(self.data => LC_TIME::MON);
+        names[month as usize - 1].to_string()
+    }
+
+    /// The first letters of the months, January first
+    pub fn month_initials(&self) -> [String; 12] {
+        let names = locale_match!(self.data => LC_TIME::ABMON);
+        std::array::from_fn(|month| names[month].chars().take(1).flat_map(char::to_uppercase).collect())
+    }
+
+    /// Abbreviated name of a weekday
+    pub fn weekday_abbr(&self, weekday: Weekday) -> String {
+        let names = locale_match!(self.data => LC_TIME::ABDAY);
+        names[weekday.num_days_from_sunday() as usize].to_string()
+    }
+
+    /// The first letters of the weekdays, Monday first
+    pub fn weekday_initials(&self) -> [String; 7] {
+        let names = locale_match!(self.data => LC_TIME::ABDAY);
+        // The names start with Sunday
+        std::array::from_fn(|day| names[(day + 1) % 7].chars().take(1).flat_map(char::to_uppercase).collect())
+    }
+
+    /// A number with the digits grouped by thousands, e.g. 12.345 in German
+    pub fn number(&self, number: impl Into<i64>) -> String {
+        self.localized(&number.into().to_string())
+    }
+
+    /// A number rounded to `decimals`, e.g. 1.234,5 in German
+    pub fn decimal(&self, number: f64, decimals: usize) -> String {
+        self.localized(&format!("{:.*}", decimals, number))
+    }
+
+    /// A number with as many decimals as it needs, like a weight
+    pub fn factor(&self, number: f64) -> String {
+        self.localized(&number.to_string())
+    }
+
+    /// A number formatted by Rust, e.g. "-1234.5", with the digit grouping
+    /// and the decimal point of the locale
+    fn localized(&self, plain: &str) -> String {
+        // Non-breaking spaces are missing from many fonts
+        let separator = locale_match!(self.data => LC_NUMERIC::THOUSANDS_SEP).replace(['\u{a0}', '\u{202f}'], " ");
+        let (sign, unsigned) = plain.strip_prefix('-').map_or(("", plain), |unsigned| ("-", unsigned));
+        let (digits, decimals) = unsigned.split_once('.').map_or((unsigned, None), |(digits, decimals)| (digits, Some(decimals)));
+        let mut grouped = sign.to_string();
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(&separator);
+            }
+            grouped.push(digit);
+        }
+        if let Some(decimals) = decimals {
+            grouped.push_str(locale_match!(self.data => LC_NUMERIC::DECIMAL_POINT));
+            grouped.push_str(decimals);
+        }
+        grouped
+    }
+
+    pub fn commits_total(&self, commits: i32) -> String {
+        fill(self.phrases.commits_total, &[self.number(commits)])
+    }
+
+    pub fn files_changed(&self, files: i32) -> String {
+        fill(self.phrases.files_changed, &[self.number(files)])
+    }
+
+    pub fn binary_files(&self, files: i32) -> String {
+        fill(self.phrases.binary_files, &[self.number(files)])
+    }
+
+    pub fn insertions(&self, lines: i32) -> String {
+        fill(self.phrases.insertions, &[self.number(lines)])
+    }
+
+    pub fn deletions(&self, lines: i32) -> String {
+        fill(self.phrases.deletions, &[self.number(lines)])
+    }
+
+    /// Share of the commits on working days and on the other days
+    pub fn working_days(&self, working_percent: f64, holidays: bool) -> String {
+        let days_off = if holidays { self.phrases.weekends_holidays } else { self.phrases.weekends };
+        let percent = |percent: f64| self.number(percent.round() as i64);
+        fill(self.phrases.working_days, &[percent(working_percent), percent(100.0 - working_percent), days_off.to_string()])
+    }
+
+    pub fn first_commit(&self, median: NaiveTime, earliest: NaiveTime, latest: NaiveTime) -> String {
+        let times = [median, earliest, latest].map(|time| time.format("%H:%M").to_string());
+        fill(self.phrases.first_commit, &times)
+    }
+
+    pub fn sessions(&self, sessions: usize, per_week: f64, average_length: Duration) -> String {
+        let minutes = average_length.num_minutes();
+        fill(self.phrases.sessions, &[
+            self.number(sessions as i64),
+            self.decimal(per_week, 1),
+            self.number(minutes / 60),
+            format!("{:02}", minutes % 60),
+        ])
+    }
+
+    /// How many of the planned `weekdays` had commits, see `--cadence`
+    pub fn adherence(&self, percent: f64, hit_days: usize, planned_days: usize, weekdays: &[Weekday]) -> String {
+        let weekdays: Vec<String> = weekdays.iter().map(|&weekday| self.weekday_abbr(weekday)).collect();
+        fill(self.phrases.adherence, &[
+            self.number(percent.round() as i64),
+            self.number(hit_days as i64),
+            self.number(planned_days as i64),
+            weekdays.join(", "),
+        ])
+    }
+
+    /// The repositories left out of the breakdown of the commits
+    pub fn more_repos(&self, repos: usize) -> String {
+        fill(self.phrases.more_repos, &[self.number(repos as i64)])
+    }
+
+    /// The repositories whose commits count more or less, see `--config`
+    pub fn weighted(&self, weights: &[(String, f64)]) -> String {
+        let weights: Vec<String> = weights.iter()
+            .map(|(name, weight)| format!("{} x{}", name, self.factor(*weight)))
+            .collect();
+        fill(self.phrases.weighted, &[weights.join(", ")])
+    }
+
+    /// How much wider than the English ones the numbers at the top of the
+    /// summary get, as measured by `text_width`
+    pub fn widening(&self, text_width: impl Fn(&str) -> f32) -> u32 {
+        let number = [self.number(10000)];
+        let english = [ENGLISH.commits_total, ENGLISH.files_changed, ENGLISH.binary_files, ENGLISH.insertions, ENGLISH.deletions];
+        let translated = [self.phrases.commits_total, self.phrases.files_changed, self.phrases.binary_files, self.phrases.insertions, self.phrases.deletions];
+        english.iter().zip(translated)
+            .map(|(english, translated)| text_width(&fill(translated, &number)) - text_width(&fill(english, &number)))
+            .fold(0.0, f32::max)
+            .ceil() as u32
+    }
+
+    /// Legend entry of a level, e.g. "12 days with 2-4 commits", `level`
+    /// counting from 0. Levels normalized per year are labeled by the
+    /// smallest and largest value of their days in `range`.
+    pub fn level_days(&self, days: usize, metric: Metric, level: usize, range: Option<(f64, f64)>) -> String {
+        let (labels, range_label) = match metric {
+            Metric::Commits => (&self.phrases.commit_levels, self.phrases.commit_range),
+            Metric::Lines => (&self.phrases.line_levels, self.phrases.line_range),
+            Metric::Weighted { .. } => (&self.phrases.point_levels, self.phrases.point_range),
+        };
+        let label = match range.map(|(low, high)| (low.round() as i64, high.round() as i64)) {
+            None => labels[level].to_string(),
+            Some((1, 1)) if metric == Metric::Commits => labels[0].to_string(),
+            Some((low, high)) if low == high => fill(range_label, &[self.number(low)]),
+            Some((low, high)) => fill(range_label, &[format!("{}-{}", self.number(low), self.number(high))]),
+        };
+        fill(self.phrases.level_days, &[self.number(days as i64), label])
+    }
+}
+
+/// Replaces the `{}` of `template` by `values`, in order
+fn fill(template: &str, values: &[String]) -> String {
+    let mut text = String::new();
+    let mut values = values.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        text.push_str(first);
+    }
+    for part in parts {
+        text.push_str(values.next().map(String::as_str).unwrap_or_default());
+        text.push_str(part);
+    }
+    text
+}
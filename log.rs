@@ -0,0 +1,74 @@
+use std::process::Command;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much is printed while collecting and rendering, see `--quiet` and
+/// `--verbose`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    /// Only errors and the path of the written file
+    Quiet,
+    Normal,
+    /// Also the git commands and the commits of every repository
+    Verbose,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Normal as u8);
+
+pub fn set_level(level: Level) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    LEVEL.load(Ordering::Relaxed) >= level as u8
+}
+
+/// Prints a progress message, unless `--quiet` is given
+#[macro_export]
+#[doc(hidden)]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a message only with `--verbose`
+#[macro_export]
+#[doc(hidden)]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Verbose) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Prints a warning to stderr, unless `--quiet` is given
+#[macro_export]
+#[doc(hidden)]
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Normal) {
+            eprintln!("Warning: {}", format!($($arg)*));
+        }
+    };
+}
+
+pub use crate::{info, verbose, warning};
+
+/// Shows a command about to be run, with `--verbose`
+pub fn command(command: &Command) {
+    if !enabled(Level::Verbose) {
+        return;
+    }
+    let mut line = command.get_program().to_string_lossy().into_owned();
+    for arg in command.get_args() {
+        line.push(' ');
+        line.push_str(&arg.to_string_lossy());
+    }
+    match command.get_current_dir() {
+        Some(dir) => println!("Running `{}` in {}", line, dir.display()),
+        None => println!("Running `{}`", line),
+    }
+}
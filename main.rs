@@ -1,486 +1,1315 @@
-use std::process::Command;
-use image::{ImageBuffer, Rgba};
-use chrono::{NaiveDate, Datelike, Month};
-use imageproc::drawing::draw_text_mut;
-use rusttype::{Font, Scale};
-use std::fs::File;
-use std::io::Read;
-use fontconfig::Fontconfig;
-
-#[derive(Debug)]
-struct Theme {
-    background: Rgba<u8>,
-    text_primary: Rgba<u8>,
-    text_secondary: Rgba<u8>,
-    separator: Rgba<u8>,
-    commit_colors: [Rgba<u8>; 6],  // [no_commit, 1, 2-4, 5-9, 10-19, 20+]
+use commits_tilewall::{authors, badge, cache, cadence, collect, config, cumulative, discover, error, export, filters, fonts, header, holidays, identities, languages, leaderboard, locale, log, narrative, output, ownership, panels, pdf, poster, preview, punchcard, qr, remote, render, serve, source, stats, svg, swatches, theme, watch};
+#[cfg(feature = "net")]
+use commits_tilewall::{crates_io, gitea, github, gitlab};
+
+use badge::Badge;
+use cache::CacheMode;
+use cadence::Cadence;
+use collect::{count_commits, Activity};
+use config::Config;
+use cumulative::CumulativePanel;
+use error::{Error, Result};
+use export::{Export, StatsFile};
+use filters::{CommitFilter, TimeFilter, YearFilter};
+use fonts::FontChain;
+#[cfg(feature = "net")]
+use gitea::GiteaSource;
+#[cfg(feature = "net")]
+use github::GitHubSource;
+#[cfg(feature = "net")]
+use gitlab::GitLabSource;
+use header::Header;
+use holidays::Holidays;
+use identities::Identities;
+use languages::LanguageBreakdown;
+use locale::Locale;
+use output::WallMetadata;
+use ownership::OwnershipPanel;
+use panels::MonthlyBars;
+use poster::Paper;
+use render::{align_years, generate_author_walls, generate_commit_image, generate_comparison, generate_repo_walls, shared_levels, Annotation, AuthorWall, Granularity, Layout, MonthLabels, RenderOptions, RepoWall, TileStyle};
+use source::{DataSource, GitSource};
+use stats::{year_stats, ColorScale, Levels, Metric, Normalize, YearStats};
+use svg::SvgFonts;
+use theme::Theme;
+
+use chrono::{Datelike, NaiveDate};
+use image::RgbaImage;
+
+use std::env;
+use std::io::{IsTerminal, Write};
+
+// Scans beyond these sizes can take a long time, so ask before starting them.
+const LARGE_SCAN_REPOS: usize = 50;
+const LARGE_SCAN_COMMITS: u64 = 100_000;
+
+/// Name shown for the wall of `--all-authors`
+const ALL_AUTHORS: &str = "All authors";
+
+/// Asks before scanning many repositories or commits, unless `--yes` is
+/// given
+fn confirm_large_scan(repos: &[String], config: &Config, assume_yes: bool) -> Result<()> {
+    let estimates: Vec<(&String, u64)> = repos.iter()
+        .map(|repo| Ok((repo, count_commits(repo, config.branches(repo))?)))
+        .collect::<Result<_>>()?;
+
+    let is_large = repos.len() > LARGE_SCAN_REPOS ||
+                   estimates.iter().any(|&(_, count)| count > LARGE_SCAN_COMMITS);
+    if !is_large {
+        return Ok(());
+    }
+
+    // Like the question, the listing goes to stderr, so that it doesn't
+    // end up in the output of a script
+    let total: u64 = estimates.iter().map(|&(_, count)| count).sum();
+    eprintln!("This is a large scan: {} repositories, {} commits in total", repos.len(), total);
+    for (repo, count) in &estimates {
+        eprintln!("  {:>10} commits  {}", count, repo);
+    }
+
+    if assume_yes {
+        return Ok(());
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::Cancelled("Refusing to start a large scan without confirmation, pass --yes to proceed".to_string()));
+    }
+
+    eprint!("Continue? [y/N] ");
+    let mut answer = String::new();
+    std::io::stderr().flush()
+        .and_then(|_| std::io::stdin().read_line(&mut answer))
+        .map_err(|err| Error::Input(format!("Failed to read the answer: {}", err)))?;
+    match answer.trim().to_lowercase().as_str() {
+        "y" | "yes" => Ok(()),
+        _ => Err(Error::Cancelled("The large scan was not confirmed".to_string())),
+    }
 }
 
-impl Theme {
-    fn dark() -> Self {
-        Theme {
-            background: Rgba([30, 30, 30, 255]),
-            text_primary: Rgba([255, 255, 255, 255]),
-            text_secondary: Rgba([200, 200, 200, 255]),
-            separator: Rgba([70, 70, 70, 255]),
-            commit_colors: [
-                Rgba([50, 50, 50, 255]),      // no commits
-                Rgba([40, 160, 40, 255]),     // 1 commit - brighter to be visible
-                Rgba([60, 200, 60, 255]),     // 2-4 commits
-                Rgba([80, 240, 80, 255]),     // 5-9 commits
-                Rgba([120, 255, 120, 255]),   // 10-19 commits
-                Rgba([160, 255, 160, 255]),   // 20+ commits
-            ],
-        }
-    }
-
-    fn light() -> Self {
-        Theme {
-            background: Rgba([255, 255, 255, 255]),
-            text_primary: Rgba([50, 50, 50, 255]),
-            text_secondary: Rgba([100, 100, 100, 255]),
-            separator: Rgba([220, 220, 220, 255]),
-            commit_colors: [
-                Rgba([240, 240, 240, 255]),   // no commits
-                Rgba([140, 240, 140, 255]),   // 1 commit - more distinct
-                Rgba([100, 220, 100, 255]),   // 2-4 commits
-                Rgba([60, 200, 60, 255]),     // 5-9 commits
-                Rgba([40, 180, 40, 255]),     // 10-19 commits
-                Rgba([20, 160, 20, 255]),     // 20+ commits
-            ],
-        }
-    }
-
-    fn github() -> Self {
-        Theme {
-            background: Rgba([255, 255, 255, 255]),
-            text_primary: Rgba([24, 23, 23, 255]),
-            text_secondary: Rgba([87, 96, 106, 255]),
-            separator: Rgba([235, 237, 240, 255]),
-            commit_colors: [
-                Rgba([235, 237, 240, 255]),   // no commits
-                Rgba([155, 233, 168, 255]),   // 1 commit - GitHub's actual color
-                Rgba([100, 220, 123, 255]),   // 2-4 commits
-                Rgba([64, 196, 99, 255]),     // 5-9 commits
-                Rgba([48, 161, 78, 255]),     // 10-19 commits
-                Rgba([33, 110, 57, 255]),     // 20+ commits
-            ],
-        }
+/// Refuses authors matching the commits of several people, unless
+/// `--allow-ambiguous` is given, so that strangers' commits aren't merged
+/// into the wall without notice
+fn check_ambiguous_author(author: &str, repos: &[String], config: &Config, allow_ambiguous: bool) -> Result<()> {
+    let mut identities = authors::matching_identities(&[author.to_string()], repos, config)?;
+    // Bots are left out or included with `--include-bots` on their own
+    identities.retain(|identity| !authors::is_bot(&identity.name, &identity.email, &[]));
+    let people = authors::distinct_people(&identities);
+    if people.len() < 2 {
+        return Ok(());
+    }
+
+    let mut message = format!("'{}' matches the commits of {} different people:", author, people.len());
+    for person in &people {
+        let identities: Vec<String> = person.iter().map(ToString::to_string).collect();
+        message.push_str(&format!("\n  {}", identities.join(", ")));
     }
+    if !allow_ambiguous {
+        return Err(Error::Usage(format!("{}\nPass a more specific author, like an email address, or --allow-ambiguous to count them all", message)));
+    }
+    log::warning!("{}", message);
+    Ok(())
 }
 
-fn load_system_font() -> Font<'static> {
-    let fontconfig = Fontconfig::new().unwrap();
-    let font = fontconfig
-        .find("sans-bold", None)
-        .or_else(|| fontconfig.find("sans", None))
-        .expect("Could not find a sans font on the system");
-    
-    let font_path = font.path;
-    
-    let mut font_file = File::open(font_path)
-        .expect("Failed to open font file");
-    let mut font_data = Vec::new();
-    font_file.read_to_end(&mut font_data)
-        .expect("Failed to read font file");
-    
-    Font::try_from_vec(font_data)
-        .expect("Failed to load font")
+/// The name shown for an author and the patterns of `git log --author`
+/// matching their commits, `@name` standing for all names and emails of a
+/// person in the identities file
+fn resolve_author(author: &str, identities: &Identities) -> Result<(String, Vec<String>)> {
+    Ok(match identities.person(author)? {
+        Some((name, person)) => (person.display_name(name).to_string(), person.author_patterns()),
+        None => (author.to_string(), vec![author.to_string()]),
+    })
 }
 
-fn get_commit_color(commit_count: i32, theme: &Theme) -> Rgba<u8> {
-    let colors = &theme.commit_colors;
-    match commit_count {
-        0 => colors[0],
-        1 => colors[1],
-        2..=4 => colors[2],
-        5..=9 => colors[3],
-        10..=19 => colors[4],
-        _ => colors[5],
+/// Tells how many commits of the author are missing from the walls and
+/// why, so that the numbers can be matched with `git log`
+fn report_skipped<'a>(walls: impl IntoIterator<Item = (&'a Activity, &'a [YearStats])>, filter: &CommitFilter) {
+    let mut skipped = collect::SkippedCommits::default();
+    let mut sparse = 0;
+    let mut counted = 0;
+    for (activity, active_years) in walls {
+        skipped.add(&activity.skipped);
+        counted += activity.timestamps.len();
+        // Years with too few commits aren't drawn
+        sparse += activity.timestamps.iter()
+            .filter(|timestamp| !active_years.iter().any(|stats| stats.year == timestamp.year() && stats.commits > 0))
+            .count();
+    }
+
+    let mut reasons = Vec::new();
+    if skipped.years > 0 {
+        reasons.push(format!("{} outside --years", skipped.years));
+    }
+    if skipped.only > 0 {
+        reasons.push(format!("{} not matching --only", skipped.only));
+    }
+    if skipped.bots > 0 && !filter.bot_patterns.is_empty() {
+        reasons.push(format!("{} by bots like dependabot or renovate or matching --exclude-bots", skipped.bots));
+    } else if skipped.bots > 0 {
+        reasons.push(format!("{} by bots like dependabot or renovate, pass --include-bots to count them", skipped.bots));
     }
+    if skipped.reverts > 0 {
+        reasons.push(format!("{} reverting other commits", skipped.reverts));
+    }
+    if sparse > 0 {
+        reasons.push(format!("{} in years with fewer than {} commits", sparse, stats::MIN_YEAR_COMMITS));
+    }
+    if !reasons.is_empty() {
+        let total = counted + skipped.years + skipped.only + skipped.bots + skipped.reverts;
+        let left_out = total - counted + sparse;
+        log::info!("Left out {} of {} commits: {}", left_out, total, reasons.join("; "));
+    }
+}
+
+fn print_usage(program: &str) -> ! {
+    eprintln!("Usage: {} [generate] <author> <repo|url> [repo2|url2...] [--recursive <dir>...] [--unshallow] [--no-cache|--refresh|--incremental] [--theme <theme>] [--font <family|path>...] [--supersample-text] [--svg-fonts embed|system] [--config <file>] [--identities <file>] [--holidays <file|dates|country>] [--wellness] [--no-legend] [--no-stats] [--narrative] [--history-gaps] [--allow-ambiguous] [--include-bots] [--exclude-bots <pattern>...] [--include-coauthored] [--stats-exclude <pathspec>...] [--exclude-ext <extensions>] [--month-labels abbr|full|numeric|none] [--month-labels-below] [--month-totals] [--locale <locale>] [--months-per-row <n>] [--layout months|weeks [--no-weekday-labels]] [--preset readme|banner|square] [--block-size <px>] [--gap <px>] [--month-gap <px>] [--year-gap <px>] [--corner-radius <px>] [--empty-days solid|none|outline|dotted] [--highlight-weekends] [--sparkline] [--cadence <days>] [--metric commits|lines|weighted[=<weights>]] [--normalize global|per-year] [--color-scale percentile|log] [--granularity day|halfday] [--only <filter>...] [--years <years>] [--grep <pattern> [--invert-grep]] [--no-reverts] [--per-repo] [--append-to <png>] [--output <file>] [--print a1|a2|a3|a4|a5|letter|tabloid [--dpi <dpi>]] [--punchcard] [--qr <url>] [--crate <name>] [--show-tags] [--annotate <date>:<text>...] [--languages] [--ownership] [--cumulative] [--monthly-bars] [--badge commits|streak] [--export markdown] [--format png|svg|pdf|sixel|json|text[,...] [--page-per-year]] [--dry-run] [--preview kitty|iterm|sixel] [--watch [--interval <seconds>]] [--quiet|--verbose] [--yes]", program);
+    eprintln!("       {} [generate] --compare <author> <author> <repo|url>... [options...]", program);
+    eprintln!("       {} [generate] --author <author> --author <author>... <repo|url>... [options...]", program);
+    eprintln!("       {} [generate] --all-authors <repo|url>... [options...]", program);
+    eprintln!("       {} [generate] --source github|gitlab|gitea --user <login> [--token <token>] [--base-url <url>] [options...]", program);
+    eprintln!("       {} stats <author> <repo|url>... [options...]", program);
+    eprintln!("       {} export markdown|json <author> <repo|url>... [options...]", program);
+    eprintln!("       {} render --load <stats.json> [options...]", program);
+    eprintln!("       {} list-authors <repo|url>... [--recursive <dir>...] [--include-bots] [--config <file>]", program);
+    eprintln!("       {} run --workspace <name> [options...]", program);
+    eprintln!("       {} serve --listen <address:port> [--interval <seconds>] [--refresh-secret <secret>] <author> <repo|url>... [options...]", program);
+    eprintln!("       {} leaderboard <repo|url>... [--top <n>] [--by commits|lines] [--years <years>] [--include-bots] [--exclude-bots <pattern>...] [--theme <theme>] [--font <family|path>...] [--config <file>] [--output <file>]", program);
+    eprintln!("       {} preview --serve --theme <file.toml> [--listen <address:port>] [--font <family|path>...]", program);
+    eprintln!("       {} themes [--preview [kitty|iterm|sixel]] [--output <file>] [--font <family|path>...]", program);
+    eprintln!("       {} schema [layout|stats]", program);
+    eprintln!("Available themes: light (default), dark, github, a theme of the themes directory, or a .toml file");
+    // Same status as `Error::Usage`
+    std::process::exit(2);
 }
 
-fn draw_sharp_text(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, text: &str, x: i32, y: i32, size: f32, color: Rgba<u8>, font: &Font) {
-    let scale = Scale {
-        x: size,
-        y: size,
+fn collect_from(source: &dyn DataSource, filter: &CommitFilter) -> Result<Activity> {
+    log::info!("Collecting commits from {}", source.describe());
+    source.collect(filter)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let result = match args.get(1).map(String::as_str) {
+        Some("generate") => generate_wall(&subcommand_args(&args, &[])),
+        Some("stats") => stats(&args),
+        Some("export") => export_stats(&args),
+        Some("list-authors") => list_authors(&args),
+        Some("run") => run_workspace(&args),
+        Some("serve") => serve::run(&args, generate),
+        Some("leaderboard") => leaderboard::run(&args),
+        Some("preview") => serve::run_preview(&args),
+        Some("themes") => swatches::run(&args),
+        Some("render") => render(&args),
+        Some("schema") => schema(&args),
+        // The arguments of `generate` without naming it
+        _ => generate_wall(&args),
     };
-    
-    draw_text_mut(
-        img,
-        color,
-        x,
-        y,
-        scale,
-        font,
-        text
-    );
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(err.exit_code());
+    }
 }
 
-fn draw_block(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, size: u32, color: Rgba<u8>) {
-    for by in 0..size {
-        for bx in 0..size {
-            let pixel_x = x + bx;
-            let pixel_y = y + by;
-            
-            if pixel_x < img.width() && pixel_y < img.height() {
-                img.put_pixel(pixel_x, pixel_y, color);
-            }
+/// The arguments of a subcommand standing for `generate` with `extra`
+/// options, as if `generate` had been given them without the subcommand
+fn subcommand_args(args: &[String], extra: &[&str]) -> Vec<String> {
+    std::iter::once(args[0].clone())
+        .chain(args[2..].iter().cloned())
+        .chain(extra.iter().map(ToString::to_string))
+        .collect()
+}
+
+/// Renders the wall once, or again whenever new commits come in with
+/// `--watch`
+fn generate_wall(args: &[String]) -> Result<()> {
+    match watch::split_args(args)? {
+        (args, Some(interval)) => watch::run(&args, interval, generate),
+        (args, None) => generate(&args).map(drop),
+    }
+}
+
+/// Prints the numbers of every year instead of drawing the wall, e.g.
+/// `stats jane ~/src/project`, the same as `--dry-run`
+fn stats(args: &[String]) -> Result<()> {
+    generate_wall(&subcommand_args(args, &["--dry-run"]))
+}
+
+/// Draws a wall again from the stats written with `--format json`, e.g.
+/// `render --load commit_image_jane.json --theme dark`, without collecting
+/// the commits
+fn render(args: &[String]) -> Result<()> {
+    if !args.iter().any(|arg| arg == "--load") {
+        print_usage(&args[0]);
+    }
+    generate_wall(&subcommand_args(args, &[]))
+}
+
+/// The JSON Schemas of the files other tools may read, by the name
+/// `schema` takes
+const SCHEMAS: [(&str, &str); 2] = [
+    ("layout", output::METADATA_SCHEMA),
+    ("stats", export::STATS_SCHEMA),
+];
+
+/// Prints the JSON Schema named by the argument, e.g. `schema stats`, or
+/// all of them as one object by their names
+fn schema(args: &[String]) -> Result<()> {
+    match args.get(2) {
+        Some(name) => {
+            let (_, schema) = SCHEMAS.iter()
+                .find(|(schema_name, _)| schema_name == name)
+                .ok_or_else(|| Error::Usage(format!("Unknown schema '{}', expected layout or stats", name)))?;
+            print!("{}", schema);
+        }
+        None => {
+            let schemas: serde_json::Map<String, serde_json::Value> = SCHEMAS.iter()
+                .map(|(name, schema)| (name.to_string(), serde_json::from_str(schema).expect("The schemas are valid JSON")))
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&schemas).expect("Failed to serialize the schemas"));
         }
     }
+    Ok(())
 }
 
-fn generate_commit_image(author: &str, repos: &[String], theme_name: &str) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
-    let theme = match theme_name.to_lowercase().as_str() {
-        "dark" => Theme::dark(),
-        "github" => Theme::github(),
-        _ => Theme::light(),  // default to light theme
+/// Writes the numbers of every year to a file, e.g.
+/// `export markdown jane ~/src/project`, the same as `--export markdown`
+/// along with the wall, or `--format json` on its own
+fn export_stats(args: &[String]) -> Result<()> {
+    let Some(format) = args.get(2) else { print_usage(&args[0]) };
+    let extra = match format.as_str() {
+        "json" if args.iter().any(|arg| arg == "--format") => {
+            return Err(Error::Usage("export json writes the numbers as JSON and can't be combined with --format".to_string()));
+        }
+        "json" => ["--format", "json"],
+        "markdown" | "md" => ["--export", "markdown"],
+        _ => return Err(Error::Usage(format!("Unknown export format '{}', expected markdown or json", format))),
     };
+    // Leaves out both the subcommand and the format
+    generate_wall(&subcommand_args(&subcommand_args(args, &[]), &extra))
+}
 
-    let block_size: u32 = 10;
-    let space_size: u32 = 2;
-    let year_spacing: u32 = 20;
-    let month_grid_width: u32 = 4;  // 4 columns per month
-    let month_grid_height: u32 = 8;  // 8 rows per month (to fit 31 days)
-    let month_label_height: u32 = block_size * 2;  // Scale with block size
-    let year_height: u32 = month_grid_height * (block_size + space_size) + month_label_height;
-    let year_label_width: u32 = block_size * 5;  // Scale with block size
-    let summary_width: u32 = block_size * 45;  // Increased width further
-    let month_spacing: u32 = space_size * 3;  // Additional spacing between months
-
-    // Load system font
-    let font = load_system_font();
-    
-    // Collect commit dates and stats at the start
-    let mut commit_dates: Vec<NaiveDate> = Vec::new();
-    let mut commit_stats = std::collections::HashMap::new();
-
-    for repo in repos {
-        // Collect dates
-        println!("Collecting commit dates for repo: {}", repo);
-        let output = Command::new("git")
-            .arg("log")
-            .arg("--author")
-            .arg(author)
-            .arg("--pretty=format:%cd")
-            .arg("--date=short")
-            .current_dir(repo)
-            .output()
-            .expect("Failed to execute git command");
-
-        let commits = String::from_utf8_lossy(&output.stdout);
-        for line in commits.lines() {
-            if let Ok(date) = NaiveDate::parse_from_str(line, "%Y-%m-%d") {
-                commit_dates.push(date);
-            }
+/// Lists the people who committed to the repositories, e.g.
+/// `list-authors ~/src/project`, one per line with all their names and
+/// emails, to find the author to pass. The identities are merged like for
+/// the ambiguity check, see `authors::distinct_people`.
+fn list_authors(args: &[String]) -> Result<()> {
+    let mut repos = Vec::new();
+    let mut recursive_dirs = Vec::new();
+    let mut include_bots = false;
+    let mut config_path = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--recursive" && i + 1 < args.len() {
+            recursive_dirs.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--include-bots" {
+            include_bots = true;
+            i += 1;
+        } else if args[i] == "--config" && i + 1 < args.len() {
+            config_path = Some(args[i + 1].as_str());
+            i += 2;
+        } else if args[i] == "--quiet" || args[i] == "-q" {
+            log::set_level(log::Level::Quiet);
+            i += 1;
+        } else if args[i] == "--verbose" || args[i] == "-v" {
+            log::set_level(log::Level::Verbose);
+            i += 1;
+        } else if args[i].starts_with('-') {
+            return Err(Error::Usage(format!("Unknown list-authors option '{}'", args[i])));
+        } else {
+            repos.push(args[i].clone());
+            i += 1;
         }
+    }
+    for dir in &recursive_dirs {
+        let dir = config::resolve_path(dir);
+        let found = discover::find_repositories(&dir);
+        log::info!("Found {} repositories in {}", found.len(), dir.display());
+        repos.extend(found.iter().map(|repo| repo.to_string_lossy().into_owned()));
+    }
+    if repos.is_empty() {
+        return Err(Error::Usage("list-authors needs at least one repository".to_string()));
+    }
 
-        // Collect stats
-        let stats_output = Command::new("git")
-            .args(&[
-                "log",
-                "--author", author,
-                "--pretty=format:%cd",
-                "--date=short",
-                "--numstat",
-            ])
-            .current_dir(repo)
-            .output()
-            .expect("Failed to execute git command");
-
-        let stats = String::from_utf8_lossy(&stats_output.stdout);
-        let mut current_date: Option<NaiveDate> = None;
-        
-        for line in stats.lines() {
-            if let Ok(date) = NaiveDate::parse_from_str(line, "%Y-%m-%d") {
-                current_date = Some(date);
-            } else if let Some(date) = current_date {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() == 3 && parts[0] != "-" && parts[1] != "-" {
-                    if let (Ok(added), Ok(deleted)) = (parts[0].parse::<i32>(), parts[1].parse::<i32>()) {
-                        let entry = commit_stats.entry(date).or_insert((0, 0, 0));
-                        entry.0 += 1;           // files
-                        entry.1 += added;       // additions
-                        entry.2 += deleted;     // deletions
-                    }
-                }
+    let config = Config::load(config_path)?;
+    discover::validate_repositories(&repos)?;
+    discover::resolve_repositories(&mut repos);
+    // Remote repositories are analyzed from temporary clones, which are
+    // removed again when `clones` goes out of scope
+    let mut clones = Vec::new();
+    for repo in repos.iter_mut() {
+        if remote::is_remote_url(repo) {
+            let clone = remote::clone_remote(repo, clones.len())?;
+            *repo = clone.path().to_string_lossy().into_owned();
+            clones.push(clone);
+        }
+    }
+
+    let mut identities = authors::matching_identities(&[], &repos, &config)?;
+    if !include_bots {
+        identities.retain(|identity| !authors::is_bot(&identity.name, &identity.email, &[]));
+    }
+    for person in authors::distinct_people(&identities) {
+        let identities: Vec<String> = person.iter().map(ToString::to_string).collect();
+        println!("{}", identities.join(", "));
+    }
+    Ok(())
+}
+
+/// Renders every wall of a workspace, e.g. `run --workspace oss --yes`.
+/// Options besides `--workspace` are passed on to every wall. A failing
+/// wall doesn't stop the others, the status of the first failure is the
+/// exit status, see `Error::FailedWalls`.
+fn run_workspace(args: &[String]) -> Result<()> {
+    let mut workspace = None;
+    let mut shared_args = Vec::new();
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--workspace" && i + 1 < args.len() {
+            workspace = Some(args[i + 1].as_str());
+            i += 2;
+        } else {
+            // Messages of the workspace itself follow the logging level too
+            if args[i] == "--quiet" || args[i] == "-q" {
+                log::set_level(log::Level::Quiet);
+            } else if args[i] == "--verbose" || args[i] == "-v" {
+                log::set_level(log::Level::Verbose);
             }
+            shared_args.push(args[i].clone());
+            i += 1;
         }
     }
+    let Some(workspace) = workspace else { print_usage(&args[0]) };
+    if shared_args.iter().any(|arg| arg == "--watch") {
+        return Err(Error::Usage("--watch can't be combined with run --workspace".to_string()));
+    }
+    let config_path = shared_args.iter()
+        .position(|arg| arg == "--config")
+        .and_then(|i| shared_args.get(i + 1))
+        .map(String::as_str);
+    let config = Config::load(config_path)?;
 
-    // Create a map to count commits per day
-    let mut commit_count_per_day = std::collections::HashMap::new();
-    for date in commit_dates {
-        *commit_count_per_day.entry(date).or_insert(0) += 1;
+    let jobs = config.workspace(workspace)?;
+    let mut failures = Vec::new();
+    // The walls sharing authors and repositories only scan them once
+    collect::sharing_git_outputs(|| {
+        for job in jobs {
+            log::info!("Rendering the wall of {}", job.author);
+            let job_args: Vec<String> = std::iter::once(args[0].clone())
+                .chain(job.args())
+                .chain(shared_args.iter().cloned())
+                .collect();
+            if let Err(err) = generate(&job_args) {
+                eprintln!("Error: {}", err);
+                failures.push(err);
+            }
+        }
+    });
+    match failures.len() {
+        0 => Ok(()),
+        failed => Err(Error::FailedWalls(failed, Box::new(failures.swap_remove(0)))),
     }
+}
+
+/// Renders the wall, returning the local repositories the commits were
+/// read from for `--watch`
+fn generate(args: &[String]) -> Result<Vec<String>> {
 
-    // Find years that have commits and count commits per year
-    let mut year_commit_counts: std::collections::HashMap<i32, i32> = std::collections::HashMap::new();
-    for (date, count) in &commit_count_per_day {
-        *year_commit_counts.entry(date.year()).or_insert(0) += count;
+    let mut positional = Vec::new();
+    let mut theme = "light";
+    let mut fonts = Vec::new();
+    let mut supersample_text = false;
+    let mut svg_fonts = SvgFonts::default();
+    let mut assume_yes = false;
+    let mut config_path = None;
+    let mut identities_path = None;
+    let mut holidays = Holidays::default();
+    let mut options = RenderOptions::default();
+    let mut source = "git";
+    let mut load = None;
+    let mut user = None;
+    let mut token = None;
+    let mut base_url = None;
+    let mut recursive_dirs = Vec::new();
+    let mut per_repo = false;
+    let mut append_to = None;
+    let mut punchcard = false;
+    let mut formats = vec!["png"];
+    let mut preview = None;
+    let mut paper = None;
+    let mut page_per_year = false;
+    let mut dry_run = false;
+    let mut dpi = None;
+    let mut narrative = false;
+    let mut output_path = None;
+    let mut history_gaps = false;
+    let mut unshallow = false;
+    let mut cache = CacheMode::Use;
+    let mut allow_ambiguous = false;
+    let mut badge = None;
+    let mut export = None;
+    let mut compare = Vec::new();
+    let mut stacked = Vec::new();
+    let mut all_authors = false;
+    let mut crate_name = None;
+    let mut ownership = false;
+    let mut cumulative = false;
+    let mut monthly_bars = false;
+    let mut show_tags = false;
+    let mut language_breakdown = false;
+
+    let mut i = 1;
+    while i < args.len() {
+        if args[i] == "--theme" && i + 1 < args.len() {
+            theme = &args[i + 1];
+            i += 2;
+        } else if args[i] == "--font" && i + 1 < args.len() {
+            fonts.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--supersample-text" {
+            supersample_text = true;
+            i += 1;
+        } else if args[i] == "--svg-fonts" && i + 1 < args.len() {
+            svg_fonts = SvgFonts::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--config" && i + 1 < args.len() {
+            config_path = Some(args[i + 1].as_str());
+            i += 2;
+        } else if args[i] == "--identities" && i + 1 < args.len() {
+            identities_path = Some(args[i + 1].as_str());
+            i += 2;
+        } else if args[i] == "--holidays" && i + 1 < args.len() {
+            holidays = Holidays::load(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--source" && i + 1 < args.len() {
+            source = &args[i + 1];
+            i += 2;
+        } else if args[i] == "--load" && i + 1 < args.len() {
+            load = Some(std::path::PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--user" && i + 1 < args.len() {
+            user = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--token" && i + 1 < args.len() {
+            token = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--cadence" && i + 1 < args.len() {
+            options.cadence = Some(Cadence::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--recursive" && i + 1 < args.len() {
+            recursive_dirs.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--only" && i + 1 < args.len() {
+            options.filter.only.push(TimeFilter::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--years" && i + 1 < args.len() {
+            options.filter.years = Some(YearFilter::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--append-to" && i + 1 < args.len() {
+            append_to = Some(std::path::PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--output" && i + 1 < args.len() {
+            output_path = Some(std::path::PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--format" && i + 1 < args.len() {
+            formats = args[i + 1].split(',').map(str::trim).collect();
+            formats.sort_unstable();
+            formats.dedup();
+            i += 2;
+        } else if args[i] == "--preview" && i + 1 < args.len() {
+            preview = Some(preview::Protocol::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--dry-run" {
+            dry_run = true;
+            i += 1;
+        } else if args[i] == "--page-per-year" {
+            page_per_year = true;
+            i += 1;
+        } else if args[i] == "--print" && i + 1 < args.len() {
+            paper = Some(Paper::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--dpi" && i + 1 < args.len() {
+            dpi = match args[i + 1].parse() {
+                Ok(dpi @ 72..=600) => Some(dpi),
+                _ => return Err(Error::Usage(format!("Invalid --dpi {}, expected 72 to 600", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--qr" && i + 1 < args.len() {
+            qr::validate(&args[i + 1])?;
+            options.qr = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--badge" && i + 1 < args.len() {
+            badge = Some(Badge::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--compare" && i + 2 < args.len() {
+            compare = vec![args[i + 1].clone(), args[i + 2].clone()];
+            i += 3;
+        } else if args[i] == "--author" && i + 1 < args.len() {
+            stacked.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--metric" && i + 1 < args.len() {
+            options.metric = Metric::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--normalize" && i + 1 < args.len() {
+            options.normalize = Normalize::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--color-scale" && i + 1 < args.len() {
+            options.color_scale = Some(ColorScale::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--granularity" && i + 1 < args.len() {
+            options.granularity = Granularity::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--crate" && i + 1 < args.len() {
+            crate_name = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--languages" {
+            language_breakdown = true;
+            i += 1;
+        } else if args[i] == "--show-tags" {
+            show_tags = true;
+            i += 1;
+        } else if args[i] == "--ownership" {
+            ownership = true;
+            i += 1;
+        } else if args[i] == "--cumulative" {
+            cumulative = true;
+            i += 1;
+        } else if args[i] == "--monthly-bars" {
+            monthly_bars = true;
+            i += 1;
+        } else if args[i] == "--all-authors" {
+            all_authors = true;
+            i += 1;
+        } else if args[i] == "--export" && i + 1 < args.len() {
+            export = Some(Export::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--month-labels" && i + 1 < args.len() {
+            options.month_labels = MonthLabels::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--months-per-row" && i + 1 < args.len() {
+            options.months_per_row = match args[i + 1].parse() {
+                Ok(months @ 1..=12) => Some(months),
+                _ => return Err(Error::Usage(format!("Invalid --months-per-row {}, expected 1 to 12", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--layout" && i + 1 < args.len() {
+            options.layout = Layout::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--no-weekday-labels" {
+            options.no_weekday_labels = true;
+            i += 1;
+        } else if args[i] == "--block-size" && i + 1 < args.len() {
+            options.grid.block_size = match args[i + 1].parse() {
+                Ok(size @ 2..=40) => size,
+                _ => return Err(Error::Usage(format!("Invalid --block-size {}, expected 2 to 40", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--gap" && i + 1 < args.len() {
+            options.grid.gap = match args[i + 1].parse() {
+                Ok(gap @ 0..=20) => gap,
+                _ => return Err(Error::Usage(format!("Invalid --gap {}, expected 0 to 20", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--month-gap" && i + 1 < args.len() {
+            options.grid.month_gap = match args[i + 1].parse() {
+                Ok(gap @ 0..=40) => gap,
+                _ => return Err(Error::Usage(format!("Invalid --month-gap {}, expected 0 to 40", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--year-gap" && i + 1 < args.len() {
+            // The separator line needs a pixel of its own
+            options.grid.year_gap = match args[i + 1].parse() {
+                Ok(gap @ 2..=100) => gap,
+                _ => return Err(Error::Usage(format!("Invalid --year-gap {}, expected 2 to 100", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--corner-radius" && i + 1 < args.len() {
+            options.grid.corner_radius = match args[i + 1].parse() {
+                Ok(radius @ 0..=20) => radius,
+                _ => return Err(Error::Usage(format!("Invalid --corner-radius {}, expected 0 to 20", args[i + 1]))),
+            };
+            i += 2;
+        } else if args[i] == "--preset" && i + 1 < args.len() {
+            options.apply_preset(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--empty-days" && i + 1 < args.len() {
+            options.empty_days = TileStyle::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--annotate" && i + 1 < args.len() {
+            options.annotations.push(Annotation::parse(&args[i + 1])?);
+            i += 2;
+        } else if args[i] == "--highlight-weekends" {
+            options.highlight_weekends = true;
+            i += 1;
+        } else if args[i] == "--sparkline" {
+            options.sparkline = true;
+            i += 1;
+        } else if args[i] == "--locale" && i + 1 < args.len() {
+            options.locale = Locale::parse(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--month-totals" {
+            options.month_totals = true;
+            i += 1;
+        } else if args[i] == "--month-labels-below" {
+            options.month_labels_below = true;
+            i += 1;
+        } else if args[i] == "--base-url" && i + 1 < args.len() {
+            base_url = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--wellness" {
+            options.wellness = true;
+            i += 1;
+        } else if args[i] == "--no-stats" {
+            options.no_stats = true;
+            i += 1;
+        } else if args[i] == "--no-legend" {
+            options.no_legend = true;
+            i += 1;
+        } else if args[i] == "--narrative" {
+            narrative = true;
+            i += 1;
+        } else if args[i] == "--unshallow" {
+            unshallow = true;
+            i += 1;
+        } else if args[i] == "--no-cache" {
+            cache = CacheMode::Off;
+            i += 1;
+        } else if args[i] == "--refresh" {
+            cache = CacheMode::Refresh;
+            i += 1;
+        } else if args[i] == "--incremental" {
+            cache = CacheMode::Incremental;
+            i += 1;
+        } else if args[i] == "--include-bots" {
+            options.filter.include_bots = true;
+            i += 1;
+        } else if args[i] == "--exclude-bots" && i + 1 < args.len() {
+            options.filter.bot_patterns.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--no-reverts" {
+            options.filter.no_reverts = true;
+            i += 1;
+        } else if args[i] == "--include-coauthored" {
+            options.filter.include_coauthored = true;
+            i += 1;
+        } else if args[i] == "--grep" && i + 1 < args.len() {
+            options.filter.grep = Some(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--invert-grep" {
+            options.filter.invert_grep = true;
+            i += 1;
+        } else if args[i] == "--stats-exclude" && i + 1 < args.len() {
+            options.filter.stats_exclude.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--exclude-ext" && i + 1 < args.len() {
+            // Pathspecs match across directories unless they say otherwise
+            for extension in args[i + 1].split(',').map(|extension| extension.trim().trim_start_matches('.')) {
+                if extension.is_empty() {
+                    return Err(Error::Usage(format!("Invalid --exclude-ext '{}', expected extensions like min.js,svg", args[i + 1])));
+                }
+                options.filter.stats_exclude.push(format!("*.{}", extension));
+            }
+            i += 2;
+        } else if args[i] == "--allow-ambiguous" {
+            allow_ambiguous = true;
+            i += 1;
+        } else if args[i] == "--history-gaps" {
+            history_gaps = true;
+            i += 1;
+        } else if args[i] == "--per-repo" {
+            per_repo = true;
+            i += 1;
+        } else if args[i] == "--punchcard" {
+            punchcard = true;
+            i += 1;
+        } else if args[i] == "--quiet" || args[i] == "-q" {
+            log::set_level(log::Level::Quiet);
+            i += 1;
+        } else if args[i] == "--verbose" || args[i] == "-v" {
+            log::set_level(log::Level::Verbose);
+            i += 1;
+        } else if args[i] == "--yes" || args[i] == "-y" {
+            assume_yes = true;
+            i += 1;
+        } else {
+            positional.push(args[i].clone());
+            i += 1;
+        }
     }
 
-    // Get years with significant activity (more than 5 commits)
-    let min_commits = 5;
-    let mut active_years: Vec<i32> = year_commit_counts
-        .iter()
-        .filter(|&(_, count)| *count >= min_commits)
-        .map(|(year, _)| *year)
-        .collect();
-    active_years.sort_unstable_by(|a, b| b.cmp(a));  // Sort in descending order
-
-    println!("Found commits in years: {:?}", active_years);
-    println!("Commit counts per year: {:?}", 
-        active_years.iter()
-            .map(|year| (year, year_commit_counts.get(year).unwrap_or(&0)))
-            .collect::<Vec<_>>());
-
-    // If no commits found, return a minimal image
-    if active_years.is_empty() {
-        println!("No commits found!");
-        return ImageBuffer::new(1, 1);
-    }
-
-    // Calculate image dimensions based on active years only
-    let years_count = active_years.len() as u32;
-    let width = year_label_width + 
-                12 * (month_grid_width * (block_size + space_size) + month_spacing) + 
-                summary_width + 
-                space_size * 4;  // Extra padding
-    let height = (year_height + year_spacing) * years_count;
-    
-    let mut img = ImageBuffer::new(width, height);
-
-    // Fill background
-    for pixel in img.pixels_mut() {
-        *pixel = theme.background;
-    }
-
-    // Fill the image based on commit counts
-    for (year_index, &year) in active_years.iter().enumerate() {
-        let year_offset = (year_index as u32) * (year_height + year_spacing);
-        
-        // Draw year text in dark color
-        let year_text = year.to_string();
-        draw_sharp_text(
-            &mut img,
-            &year_text,
-            5,
-            (year_offset + (year_height / 2)) as i32 - (block_size as i32 / 2),
-            block_size as f32 * 1.6,
-            theme.text_primary,
-            &font
-        );
-
-        // Process each month
-        for month in 1..=12 {
-            let month_x_offset = year_label_width + 
-                                (month - 1) as u32 * (month_grid_width * (block_size + space_size) + month_spacing);
-
-            // Draw month abbreviation in dark color
-            if let Some(month_name) = Month::try_from(month as u8).ok() {
-                let month_abbr = month_name.name().chars().take(3).collect::<String>();
-                draw_sharp_text(
-                    &mut img,
-                    &month_abbr,
-                    month_x_offset as i32,
-                    year_offset as i32,
-                    block_size as f32 * 1.2,
-                    theme.text_secondary,
-                    &font
-                );
+    // Only the numbers are printed, nothing is drawn
+    if dry_run {
+        if args.iter().any(|arg| arg == "--format") || badge.is_some() {
+            return Err(Error::Usage("--dry-run prints the numbers without writing any file and can't be combined with --format or --badge".to_string()));
+        }
+        formats = vec!["text"];
+    }
+    stats::check_source_date_epoch()?;
+    let config = Config::load(config_path)?;
+    let identities = Identities::load(identities_path)?;
+    // The commits come from a stats file instead of being collected, see
+    // `render`
+    if load.is_some() {
+        if source != "git" || !positional.is_empty() || !recursive_dirs.is_empty() {
+            return Err(Error::Usage("--load draws the commits of a stats file and can't be combined with repositories, --recursive or --source".to_string()));
+        }
+        if !options.filter.only.is_empty() || options.filter.years.is_some() {
+            return Err(Error::Usage("--load draws the commits as they were saved and can't be combined with --only or --years".to_string()));
+        }
+        source = "load";
+    }
+    if per_repo && source != "git" {
+        return Err(Error::Usage("--per-repo is only supported for git repositories".to_string()));
+    }
+    if per_repo && append_to.is_some() {
+        return Err(Error::Usage("--append-to can't be combined with --per-repo".to_string()));
+    }
+    if punchcard && (per_repo || append_to.is_some()) {
+        return Err(Error::Usage("--punchcard can't be combined with --per-repo or --append-to".to_string()));
+    }
+    if badge.is_some() && (per_repo || punchcard || append_to.is_some() || preview.is_some() || formats.contains(&"sixel")) {
+        return Err(Error::Usage("--badge can't be combined with --per-repo, --punchcard, --append-to, --preview or --format sixel".to_string()));
+    }
+    if paper.is_some() && (badge.is_some() || append_to.is_some() || formats != ["png"]) {
+        return Err(Error::Usage("--print only writes PNG images and can't be combined with --badge, --append-to or --format".to_string()));
+    }
+    if page_per_year && !formats.contains(&"pdf") {
+        return Err(Error::Usage("--page-per-year needs --format pdf".to_string()));
+    }
+    if dpi.is_some() && paper.is_none() {
+        return Err(Error::Usage("--dpi needs --print".to_string()));
+    }
+    if !compare.is_empty() && !stacked.is_empty() {
+        return Err(Error::Usage("--compare can't be combined with --author".to_string()));
+    }
+    if all_authors && !(compare.is_empty() && stacked.is_empty()) {
+        return Err(Error::Usage("--all-authors can't be combined with --compare or --author".to_string()));
+    }
+    if options.metric.uses_lines() && (source != "git" || punchcard || badge.is_some()) {
+        return Err(Error::Usage("--metric lines and weighted only work with git repositories and can't be combined with --punchcard or --badge".to_string()));
+    }
+    if (options.normalize == Normalize::PerYear || options.color_scale.is_some()) && (punchcard || badge.is_some()) {
+        return Err(Error::Usage("--normalize per-year and --color-scale can't be combined with --punchcard or --badge".to_string()));
+    }
+    if options.granularity == Granularity::HalfDay && options.metric.uses_lines() {
+        return Err(Error::Usage("--granularity halfday can't be combined with --metric lines or weighted".to_string()));
+    }
+    if language_breakdown {
+        if source != "git" || !(compare.is_empty() && stacked.is_empty()) || per_repo || punchcard || badge.is_some() || options.no_stats || formats.contains(&"svg") || formats.contains(&"pdf") {
+            return Err(Error::Usage("--languages only works with a single wall of git repositories and can't be combined with --compare, --author, --per-repo, --punchcard, --badge, --no-stats or --format svg or pdf".to_string()));
+        }
+        options.register_panel(LanguageBreakdown);
+    }
+    if monthly_bars {
+        if punchcard || badge.is_some() || options.no_stats || formats.contains(&"svg") || formats.contains(&"pdf") {
+            return Err(Error::Usage("--monthly-bars can't be combined with --punchcard, --badge, --no-stats or --format svg or pdf".to_string()));
+        }
+        options.register_panel(MonthlyBars(options.locale));
+    }
+    if ownership && (source != "git" || all_authors || !(compare.is_empty() && stacked.is_empty()) || per_repo || punchcard || badge.is_some() || options.no_stats || formats.contains(&"svg") || formats.contains(&"pdf")) {
+        return Err(Error::Usage("--ownership only works with a single wall of git repositories and can't be combined with --all-authors, --compare, --author, --per-repo, --punchcard, --badge, --no-stats or --format svg or pdf".to_string()));
+    }
+    if cumulative && (!(compare.is_empty() && stacked.is_empty()) || per_repo || punchcard || badge.is_some() || options.no_stats || formats.contains(&"svg") || formats.contains(&"pdf")) {
+        return Err(Error::Usage("--cumulative only works with a single wall and can't be combined with --compare, --author, --per-repo, --punchcard, --badge, --no-stats or --format svg or pdf".to_string()));
+    }
+    if show_tags && (source != "git" || per_repo) {
+        return Err(Error::Usage("--show-tags only works with git repositories and can't be combined with --per-repo".to_string()));
+    }
+    if all_authors && source != "git" {
+        return Err(Error::Usage("--all-authors only works with git repositories".to_string()));
+    }
+    if !options.filter.bot_patterns.is_empty() && (!all_authors || options.filter.include_bots) {
+        return Err(Error::Usage("--exclude-bots only works with --all-authors and can't be combined with --include-bots".to_string()));
+    }
+    if options.filter.invert_grep && options.filter.grep.is_none() {
+        return Err(Error::Usage("--invert-grep needs a --grep pattern".to_string()));
+    }
+    if (options.filter.grep.is_some() || options.filter.no_reverts) && source != "git" {
+        return Err(Error::Usage("--grep and --no-reverts only work with git repositories".to_string()));
+    }
+    if options.filter.include_coauthored && (source != "git" || all_authors) {
+        return Err(Error::Usage("--include-coauthored only works with git repositories and can't be combined with --all-authors".to_string()));
+    }
+    if !(compare.is_empty() && stacked.is_empty()) && (source != "git" || per_repo || punchcard || badge.is_some() || append_to.is_some() || export.is_some() || formats.iter().any(|&format| format == "svg" || format == "pdf" || format == "json" || format == "text")) {
+        return Err(Error::Usage("--compare and --author only work with git repositories and can't be combined with --per-repo, --punchcard, --badge, --append-to, --export or --format svg, pdf, json or text".to_string()));
+    }
+    if export.is_some() && (per_repo || punchcard || badge.is_some() || append_to.is_some() || !(formats.contains(&"png") || formats.contains(&"svg"))) {
+        return Err(Error::Usage("--export can't be combined with --per-repo, --punchcard, --badge or --append-to, and needs --format png or svg".to_string()));
+    }
+    if options.layout == Layout::Weeks && options.months_per_row.is_some() {
+        return Err(Error::Usage("--layout weeks has a single row of weeks and can't be combined with --months-per-row or --preset square".to_string()));
+    }
+    for &format in &formats {
+        match format {
+            "png" => {}
+            "sixel" if append_to.is_none() => {}
+            "sixel" => {
+                return Err(Error::Usage("--format sixel can't be combined with --append-to".to_string()));
+            }
+            "svg" | "pdf" | "json" | "text" if !(per_repo || punchcard || append_to.is_some() || preview.is_some()) => {}
+            "svg" | "pdf" | "json" | "text" => {
+                return Err(Error::Usage(format!("--format {} can't be combined with --per-repo, --punchcard, --append-to or --preview", format)));
+            }
+            _ => {
+                return Err(Error::Usage(format!("Unknown format '{}', expected png, svg, pdf, sixel, json or text", format)));
             }
+        }
+    }
+    if svg_fonts == SvgFonts::Embed && !formats.contains(&"svg") {
+        return Err(Error::Usage("--svg-fonts embed needs --format svg".to_string()));
+    }
+    if let Some(crate_name) = crate_name {
+        #[cfg(feature = "net")]
+        {
+            options.releases = crates_io::releases(&crate_name)?;
+            log::info!("Found {} releases of {} on crates.io", options.releases.len(), crate_name);
+        }
+        #[cfg(not(feature = "net"))]
+        return Err(Error::Usage(format!("--crate {} needs a build with the net feature", crate_name)));
+    }
+    let mut repo_walls = Vec::new();
+    let mut author_walls = Vec::new();
+    #[cfg_attr(not(feature = "net"), allow(unused_assignments))]
+    let mut repo_count = None;
+    let mut owned_lines = None;
+    // Where the commits came from, see `Provenance`
+    let sources;
+    let mut local_repos = Vec::new();
 
-            // Draw all days in a grid
-            let days_in_month = match month {
-                1 => 31, // January
-                2 => if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) { 29 } else { 28 }, // February
-                3 => 31, // March
-                4 => 30, // April
-                5 => 31, // May
-                6 => 30, // June
-                7 => 31, // July
-                8 => 31, // August
-                9 => 30, // September
-                10 => 31, // October
-                11 => 30, // November
-                12 => 31, // December
-                _ => 0, // Invalid month
+    let (author, activity) = match source {
+        "git" => {
+            // With --compare, --author or --all-authors every positional
+            // argument is a repository
+            let authors = if all_authors {
+                Vec::new()
+            } else if !compare.is_empty() {
+                compare.clone()
+            } else if !stacked.is_empty() {
+                stacked.clone()
+            } else if !positional.is_empty() {
+                vec![positional.remove(0)]
+            } else {
+                Vec::new()
             };
+            if (authors.is_empty() && !all_authors) || (positional.is_empty() && recursive_dirs.is_empty()) {
+                print_usage(&args[0]);
+            }
+            let resolved: Vec<(String, Vec<String>)> = if all_authors {
+                // No --author pattern makes git log list every commit
+                vec![(ALL_AUTHORS.to_string(), Vec::new())]
+            } else {
+                authors.iter()
+                    .map(|author| resolve_author(author, &identities))
+                    .collect::<Result<_>>()?
+            };
+            let (author, patterns) = resolved[0].clone();
+            let mut repos = positional;
 
-            for day in 1..=days_in_month {  // Adjusted to use days_in_month
-                let col = (day - 1) % month_grid_width;
-                let row = (day - 1) / month_grid_width;
-
-                // Only draw if within bounds
-                if row < month_grid_height && day <= days_in_month {  // Ensure we only draw within the grid height and valid days
-                    let x = month_x_offset + col * (block_size + space_size);
-                    let y = year_offset + month_label_height + row * (block_size + space_size);
-
-                    // Set color based on number of commits
-                    let color_value = if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
-                        if let Some(&count) = commit_count_per_day.get(&date) {
-                            get_commit_color(count, &theme)
-                        } else {
-                            get_commit_color(0, &theme)
-                        }
+            for dir in &recursive_dirs {
+                let dir = config::resolve_path(dir);
+                let found = discover::find_repositories(&dir);
+                log::info!("Found {} repositories in {}", found.len(), dir.display());
+                repos.extend(found.iter().map(|repo| repo.to_string_lossy().into_owned()));
+            }
+            if repos.is_empty() {
+                return Err(Error::Usage("No git repositories found".to_string()));
+            }
+            discover::validate_repositories(&repos)?;
+            discover::resolve_repositories(&mut repos);
+            sources = repos.clone();
+            local_repos = repos.iter().filter(|repo| !remote::is_remote_url(repo)).cloned().collect();
+            for repo in &repos {
+                if !remote::is_remote_url(repo) && discover::is_shallow(repo)? {
+                    if unshallow {
+                        discover::unshallow(repo)?;
                     } else {
-                        theme.commit_colors[0]  // Use no-commit color for invalid dates
-                    };
-
-                    // Draw the block
-                    for by in 0..block_size {
-                        for bx in 0..block_size {
-                            let pixel_x = x + bx;
-                            let pixel_y = y + by;
-
-                            if pixel_x < img.width() && pixel_y < img.height() {
-                                img.put_pixel(pixel_x, pixel_y, color_value);
-                            }
+                        log::warning!("{} is a shallow clone, commits before its cut-off are missing. Pass --unshallow to fetch the full history", repo);
+                    }
+                }
+            }
+
+            // Remote repositories are analyzed from temporary clones, which are
+            // removed again when `clones` goes out of scope
+            let mut clones = Vec::new();
+            for repo in repos.iter_mut() {
+                if remote::is_remote_url(repo) {
+                    let clone = remote::clone_remote(repo, clones.len())?;
+                    *repo = clone.path().to_string_lossy().into_owned();
+                    clones.push(clone);
+                }
+            }
+
+            for author in authors.iter().filter(|author| !author.starts_with('@')) {
+                check_ambiguous_author(author, &repos, &config, allow_ambiguous)?;
+            }
+            // The clones are removed when the scan isn't confirmed too
+            confirm_large_scan(&repos, &config, assume_yes)?;
+
+            repo_count = Some(repos.len());
+            if show_tags {
+                for repo in &repos {
+                    let tags = collect::tags(repo)?;
+                    log::info!("Found {} tags in {}", tags.len(), repo);
+                    options.releases.extend(tags);
+                }
+            }
+            if resolved.len() > 1 {
+                let activities = resolved.iter()
+                    .map(|(_, patterns)| collect_from(&GitSource { authors: patterns, repos: &repos, config: &config, cache }, &options.filter))
+                    .collect::<Result<Vec<_>>>()?;
+                // The colors stand for the same values on all walls
+                let levels = shared_levels(&activities, &options);
+                for ((author, _), activity) in resolved.iter().zip(activities) {
+                    let active_years = year_stats(&activity, &holidays, options.cadence.as_ref(), &levels);
+                    author_walls.push(AuthorWall { author: author.clone(), activity, active_years });
+                }
+                align_years(&mut author_walls);
+                let names: Vec<&str> = author_walls.iter().map(|wall| wall.author.as_str()).collect();
+                let separator = if compare.is_empty() { ", " } else { " vs " };
+                (names.join(separator), Activity::default())
+            } else if per_repo {
+                // Every repository gets its own wall, so there is no merged
+                // activity
+                let activities = repos.iter()
+                    .map(|repo| collect_from(&GitSource { authors: &patterns, repos: std::slice::from_ref(repo), config: &config, cache }, &options.filter))
+                    .collect::<Result<Vec<_>>>()?;
+                let levels = shared_levels(&activities, &options);
+                for (repo, activity) in repos.iter().zip(activities) {
+                    let active_years = year_stats(&activity, &holidays, options.cadence.as_ref(), &levels);
+                    if active_years.is_empty() {
+                        log::info!("No commits found in {}", repo);
+                        continue;
+                    }
+                    repo_walls.push(RepoWall {
+                        name: collect::repo_name(repo),
+                        language: languages::detect_language(repo, config.branches(repo))?,
+                        activity,
+                        active_years,
+                    });
+                }
+                (author, Activity::default())
+            } else {
+                let mut activity = collect_from(&GitSource { authors: &patterns, repos: &repos, config: &config, cache }, &options.filter)?;
+                if history_gaps {
+                    for repo in &repos {
+                        if let Some(date) = collect::find_import(repo, config.branches(repo))? {
+                            log::info!("The history of {} starts with an import on {}", repo, date);
+                            activity.imports.push((collect::repo_name(repo), date));
                         }
                     }
                 }
+                // Blamed before the temporary clones are removed
+                if ownership {
+                    owned_lines = Some(ownership::ownership(&patterns, &repos, cache)?);
+                }
+                (author, activity)
             }
         }
+        "load" => {
+            let path = load.expect("--load sets the source");
+            let stats = StatsFile::load(&path)?;
+            log::info!("Loaded the commits of {} from {}", stats.author, path.display());
+            sources = vec![path.display().to_string()];
+            repo_count = stats.repositories;
+            (stats.author.clone(), stats.activity())
+        }
+        #[cfg(feature = "net")]
+        "github" => {
+            let Some(user) = user else { print_usage(&args[0]) };
+            let user = identities.forge_user(&user, source)?;
+            let token = token.or_else(|| env::var("GITHUB_TOKEN").ok());
+            let base_url = base_url.unwrap_or_else(|| github::DEFAULT_BASE_URL.to_string());
+            let github = GitHubSource { user: user.clone(), token, base_url };
+            sources = vec![github.describe()];
+            let activity = collect_from(&github, &options.filter)?;
+            repo_count = Some(activity.repo_commits.len());
+            (user, activity)
+        }
+        #[cfg(feature = "net")]
+        "gitlab" => {
+            let Some(user) = user else { print_usage(&args[0]) };
+            let user = identities.forge_user(&user, source)?;
+            let token = token.or_else(|| env::var("GITLAB_TOKEN").ok());
+            let base_url = base_url.unwrap_or_else(|| gitlab::DEFAULT_BASE_URL.to_string());
+            let gitlab = GitLabSource { user: user.clone(), token, base_url };
+            sources = vec![gitlab.describe()];
+            let activity = collect_from(&gitlab, &options.filter)?;
+            (user, activity)
+        }
+        #[cfg(feature = "net")]
+        "gitea" | "forgejo" | "codeberg" => {
+            let Some(user) = user else { print_usage(&args[0]) };
+            let user = identities.forge_user(&user, source)?;
+            let token = token.or_else(|| env::var("GITEA_TOKEN").ok());
+            let base_url = base_url.unwrap_or_else(|| gitea::DEFAULT_BASE_URL.to_string());
+            let gitea = GiteaSource { user: user.clone(), token, base_url };
+            sources = vec![gitea.describe()];
+            let activity = collect_from(&gitea, &options.filter)?;
+            (user, activity)
+        }
+        #[cfg(not(feature = "net"))]
+        "github" | "gitlab" | "gitea" | "forgejo" | "codeberg" => {
+            let _ = (user, token, base_url);
+            return Err(Error::Usage(format!("--source {} needs a build with the net feature", source)));
+        }
+        _ => {
+            return Err(Error::Usage(format!("Unknown source '{}', expected github, gitlab, gitea, forgejo or codeberg", source)));
+        }
+    };
+
+    // Load the requested fonts, falling back to the system sans font
+    let font = FontChain::new(&fonts)?.with_supersampling(supersample_text);
+    let theme_name = theme;
+    let theme = Theme::resolve(theme_name)?;
 
-        // Draw year separator line in light gray
-        if year_index > 0 {
-            for x in 0..width {
-                let line_y = year_offset - (year_spacing / 2);
-                img.put_pixel(x, line_y, theme.separator);  // Light gray line
+    let current_dir = std::env::current_dir()
+        .map_err(|err| Error::Output(format!("Failed to get the current directory: {}", err)))?;
+    log::verbose!("Current directory: {}", current_dir.display());
+    let kind = if punchcard {
+        "punchcard"
+    } else if badge.is_some() {
+        "badge"
+    } else {
+        "image"
+    };
+    let file_stem = format!("commit_{}_{}", kind, output::file_name_slug(&author));
+
+    // Whose activity is shown, over which period
+    let dates: Vec<NaiveDate> = activity.timestamps.iter()
+        .chain(repo_walls.iter().flat_map(|wall| &wall.activity.timestamps))
+        .chain(author_walls.iter().flat_map(|wall| &wall.activity.timestamps))
+        .map(|timestamp| timestamp.date())
+        .collect();
+    let mut header = match (dates.iter().min(), dates.iter().max()) {
+        (Some(&first), Some(&last)) => Some(Header {
+            author: author.clone(),
+            repos: repo_count,
+            first,
+            last,
+        }),
+        _ => None,
+    };
+
+    // Report written once the image is, see `--export`
+    let mut report = None;
+    // Draws the wall again at the resolution of the paper for `--print`
+    let draw: Box<dyn Fn(&RenderOptions) -> RgbaImage + '_>;
+    let (img, metadata) = if punchcard {
+        log::info!("Found {} commits", activity.timestamps.len());
+        if activity.timestamps.is_empty() {
+            return Err(Error::NoCommits(author));
+        }
+        draw = Box::new(|options| punchcard::generate_punchcard(&activity, &options.grid, &theme, &font));
+        (draw(&options), None)
+    } else if per_repo {
+        for wall in &repo_walls {
+            log::info!("Commit counts per year in {}: {:?}",
+                wall.name,
+                wall.active_years.iter()
+                    .map(|stats| (stats.year, stats.commits))
+                    .collect::<Vec<_>>());
+        }
+        report_skipped(repo_walls.iter().map(|wall| (&wall.activity, wall.active_years.as_slice())), &options.filter);
+        if repo_walls.is_empty() {
+            return Err(Error::NoCommits(author));
+        }
+        draw = Box::new(|options| generate_repo_walls(&repo_walls, &holidays, &theme, &font, options));
+        (draw(&options), None)
+    } else if !author_walls.is_empty() {
+        for wall in &author_walls {
+            log::info!("Commit counts per year of {}: {:?}",
+                wall.author,
+                wall.active_years.iter()
+                    .map(|stats| (stats.year, stats.commits))
+                    .collect::<Vec<_>>());
+        }
+        report_skipped(author_walls.iter().map(|wall| (&wall.activity, wall.active_years.as_slice())), &options.filter);
+        if author_walls[0].active_years.is_empty() {
+            return Err(Error::NoCommits(author));
+        }
+        if compare.is_empty() {
+            draw = Box::new(|options| generate_author_walls(&author_walls, &holidays, &theme, &font, options));
+        } else {
+            draw = Box::new(|options| generate_comparison(&author_walls, &holidays, &theme, &font, options));
+        }
+        (draw(&options), None)
+    } else {
+        let levels = Levels::new(&activity, options.metric, options.normalize, options.color_scale);
+        let mut active_years = year_stats(&activity, &holidays, options.cadence.as_ref(), &levels);
+        log::info!("Found commits in years: {:?}", active_years.iter().map(|stats| stats.year).collect::<Vec<_>>());
+        log::info!("Commit counts per year: {:?}",
+            active_years.iter()
+                .map(|stats| (stats.year, stats.commits))
+                .collect::<Vec<_>>());
+        report_skipped([(&activity, active_years.as_slice())], &options.filter);
+        if active_years.is_empty() {
+            return Err(Error::NoCommits(author));
+        }
+        // Next to the newest year shown, which may be older than the newest
+        // commit
+        if let Some(repos) = owned_lines.take() {
+            options.register_panel(OwnershipPanel::new(active_years[0].year, repos));
+        }
+        if cumulative {
+            if let Some(panel) = CumulativePanel::new(active_years[0].year, &activity, options.locale) {
+                options.register_panel(panel);
             }
         }
-
-        // Draw summary on the right side
-        let summary_x = width - summary_width - space_size * 2;
-        let stats_x = summary_x;  // Stats start at the same x position
-        let legend_x = summary_x + block_size * 8;  // Color legend starts after stats
-
-        // Count total commits and stats for the year
-        let year_total: i32 = commit_count_per_day.iter()
-            .filter(|(date, _)| date.year() == year)
-            .map(|(_, &count)| count)
-            .sum();
-
-        let year_stats = commit_stats.iter()
-            .filter(|(date, _)| date.year() == year)
-            .fold((0, 0, 0), |acc, (_, &(files, added, deleted))| {
-                (acc.0 + files, acc.1 + added, acc.2 + deleted)
-            });
-
-        // Calculate commit level counts
-        let level_counts = [
-            commit_count_per_day.iter()
-                .filter(|(date, &count)| date.year() == year && count == 1)
-                .count(),
-            commit_count_per_day.iter()
-                .filter(|(date, &count)| date.year() == year && (2..=4).contains(&count))
-                .count(),
-            commit_count_per_day.iter()
-                .filter(|(date, &count)| date.year() == year && (5..=9).contains(&count))
-                .count(),
-            commit_count_per_day.iter()
-                .filter(|(date, &count)| date.year() == year && (10..=19).contains(&count))
-                .count(),
-            commit_count_per_day.iter()
-                .filter(|(date, &count)| date.year() == year && count >= 20)
-                .count(),
-        ];
-
-        // Draw summary text with stats
-        let summary_lines = [
-            format!("{} commits total", year_total),
-            format!("{} files changed", year_stats.0),
-            format!("{} insertions(+)", year_stats.1),
-            format!("{} deletions(-)", year_stats.2),
-        ];
-
-        for (i, text) in summary_lines.iter().enumerate() {
-            draw_sharp_text(
-                &mut img,
-                text,
-                stats_x as i32,
-                (year_offset + block_size + i as u32 * (block_size + space_size)) as i32,
-                block_size as f32 * 0.8,
-                theme.text_primary,
-                &font
-            );
-        }
-
-        // Adjusted position for commit level counts
-        let level_start_y = year_offset + block_size + space_size;
-
-        for (i, &count) in level_counts.iter().enumerate() {
-            if count > 0 && 
-               legend_x + block_size <= width && 
-               level_start_y + (i as u32 * (block_size + space_size)) + block_size <= height {
-                
-                // Draw colored square
-                draw_block(
-                    &mut img,
-                    legend_x,
-                    level_start_y + (i as u32 * (block_size + space_size)),
-                    block_size,
-                    theme.commit_colors[i + 1]
-                );
-
-                // Draw count text
-                let level_text = match i {
-                    0 => format!("{} days with 1 commit", count),
-                    1 => format!("{} days with 2-4 commits", count),
-                    2 => format!("{} days with 5-9 commits", count),
-                    3 => format!("{} days with 10-19 commits", count),
-                    _ => format!("{} days with 20+ commits", count),
-                };
-
-                // Draw text only if there's enough space
-                let text_x = legend_x + block_size + space_size * 2;
-                if text_x + block_size * 15 <= width {
-                    draw_sharp_text(
-                        &mut img,
-                        &level_text,
-                        text_x as i32,
-                        (level_start_y + (i as u32 * (block_size + space_size))) as i32,
-                        block_size as f32 * 0.8,
-                        theme.text_secondary,
-                        &font
-                    );
-                }
+        if narrative {
+            for (i, stats) in active_years.iter().enumerate() {
+                println!();
+                println!("{}", narrative::year_narrative(stats, active_years.get(i + 1), &activity));
             }
+            println!();
         }
-    }
 
-    img
-}
+        // A badge is written instead of the wall
+        if let Some(badge) = badge {
+            let (label, message) = badge.texts(&activity, &active_years, stats::today());
+            let svg = badge::generate_badge_svg(&label, &message, &theme, &font);
+            let output_path = output_path.unwrap_or_else(|| current_dir.join(format!("{}.svg", file_stem)));
+            std::fs::write(&output_path, svg)
+                .map_err(|err| Error::Output(format!("Failed to save the badge to {}: {}", output_path.display(), err)))?;
+            println!("Wrote {}", output_path.display());
+            return Ok(local_repos);
+        }
 
-use std::env;
+        // Only render the years since the newest archived one, which is
+        // rendered again as it may have been incomplete
+        let archive = append_to.as_ref().map(|path| output::load_png(path)).transpose()?;
+        if let Some((_, archived)) = &archive {
+            if archived.theme != theme_name {
+                return Err(Error::Usage(format!("The archived image uses the {} theme, pass --theme {} to append to it", archived.theme, archived.theme)));
+            }
+            let newest = archived.newest_year().unwrap_or(i32::MIN);
+            active_years.retain(|stats| stats.year >= newest);
+            if active_years.is_empty() {
+                log::info!("No new years to append to {}", append_to.as_ref().unwrap().display());
+                return Ok(local_repos);
+            }
+            log::info!("Appending years {:?}", active_years.iter().map(|stats| stats.year).collect::<Vec<_>>());
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 3 {
-        eprintln!("Usage: {} <author> <repo1> [repo2...] [--theme <theme>]", args[0]);
-        eprintln!("Available themes: light (default), dark, github");
-        std::process::exit(1);
-    }
+            if let (Some(header), Some(first_commit)) = (&mut header, archived.first_commit) {
+                header.first = header.first.min(first_commit);
+            }
+        }
 
-    let author = &args[1];
-    let mut repos = Vec::new();
-    let mut theme = "light";
+        // Every format is written from the same collected commits, named
+        // after --output with the extension of the format when there are
+        // several
+        let format_path = |extension: &str| match &output_path {
+            Some(path) if formats.len() > 1 => path.with_extension(extension),
+            Some(path) => path.clone(),
+            None => current_dir.join(format!("{}.{}", file_stem, extension)),
+        };
+        if formats.contains(&"json") {
+            let json = StatsFile::new(&author, header.as_ref(), &activity, &active_years).to_json();
+            let json_path = format_path("json");
+            std::fs::write(&json_path, json)
+                .map_err(|err| Error::Output(format!("Failed to save the stats to {}: {}", json_path.display(), err)))?;
+            println!("Wrote {}", json_path.display());
+        }
+        if formats.contains(&"text") {
+            println!();
+            print!("{}", export::text_stats(&author, header.as_ref(), &activity, &active_years));
+        }
+        if formats.contains(&"pdf") {
+            let pdf = pdf::generate_commit_pdf(&activity, &active_years, header.as_ref(), &holidays, &theme, &font, &options, page_per_year)?;
+            let pdf_path = format_path("pdf");
+            std::fs::write(&pdf_path, pdf)
+                .map_err(|err| Error::Output(format!("Failed to save the image to {}: {}", pdf_path.display(), err)))?;
+            println!("Wrote {}", pdf_path.display());
+        }
+        let raster = formats.contains(&"png") || formats.contains(&"sixel");
+        if formats.contains(&"svg") {
+            let svg = svg::generate_commit_svg(&activity, &active_years, header.as_ref(), &holidays, &theme, &font, &options, svg_fonts)?;
+            let svg_path = format_path("svg");
+            std::fs::write(&svg_path, svg)
+                .map_err(|err| Error::Output(format!("Failed to save the image to {}: {}", svg_path.display(), err)))?;
+            println!("Wrote {}", svg_path.display());
+            // The report embeds the PNG when there is one
+            if let Some(export) = export.filter(|_| !formats.contains(&"png")) {
+                export.report(&svg_path, &author, header.as_ref(), &activity, &active_years).save()?;
+            }
+        }
+        if !raster {
+            return Ok(local_repos);
+        }
+        if let Some(export) = export.filter(|_| formats.contains(&"png")) {
+            report = Some(export.report(&format_path("png"), &author, header.as_ref(), &activity, &active_years));
+        }
 
-    let mut i = 2;
-    while i < args.len() {
-        if args[i] == "--theme" && i + 1 < args.len() {
-            theme = &args[i + 1];
-            i += 2;
-        } else {
-            repos.push(args[i].clone());
-            i += 1;
+        let years: Vec<i32> = active_years.iter().map(|stats| stats.year).collect();
+        let (activity, holidays, theme, font) = (&activity, &holidays, &theme, &font);
+        draw = Box::new(move |options| generate_commit_image(activity, &active_years, &levels, holidays, theme, font, options));
+        let img = draw(&options);
+        let metadata = WallMetadata::for_wall(theme_name, &years, img.height());
+        match &archive {
+            Some((archived_img, archived)) => {
+                let (img, metadata) = output::append_rows(archived_img, archived, &img, &metadata, options.grid.year_gap, theme.separator)?;
+                (img, Some(metadata))
+            }
+            None => (img, Some(metadata)),
+        }
+    };
+    let metadata = match &header {
+        Some(header) => metadata.map(|metadata| metadata.with_header(header)),
+        None => metadata,
+    };
+    let frame = |img: RgbaImage, options: &RenderOptions| {
+        let img = match &header {
+            Some(header) => header::add_header(&img, header, &options.grid, &theme, &font),
+            None => img,
+        };
+        match &options.qr {
+            Some(url) => qr::add_qr_footer(&img, url, &options.grid, &theme, &font),
+            None => img,
+        }
+    };
+    let img = frame(img, &options);
+    // The wall is drawn again at the scale that fills the paper, so that the
+    // tiles and the text are as sharp as the printer prints them. Its
+    // layout changes with the scale, so nothing can be appended to a poster.
+    let dpi = paper.map(|_| dpi.unwrap_or(poster::DEFAULT_DPI));
+    let (img, metadata) = match (paper, dpi) {
+        (Some(paper), Some(dpi)) => {
+            let scale = poster::scale(img.width(), img.height(), paper, dpi);
+            options.grid = options.grid.scaled(scale);
+            let page = poster::place_on_paper(&frame(draw(&options), &options), paper, dpi, theme.background);
+            log::info!("Rendered the image at {:.2} times its size on {}x{} pixels for {} paper at {} dpi", scale, page.width(), page.height(), paper.name, dpi);
+            (page, None)
+        }
+        _ => (img, metadata),
+    };
+    if formats.contains(&"sixel") {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(output::encode_sixel(&img).as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|err| Error::Output(format!("Failed to write the image to stdout: {}", err)))?;
+        if !formats.contains(&"png") {
+            return Ok(local_repos);
         }
     }
 
-    let img = generate_commit_image(author, &repos, theme);
-    let current_dir = std::env::current_dir().expect("Failed to get current directory");
-    println!("Current directory: {}", current_dir.display());
-    let output_path = format!("commit_image_{}.png", author.replace(" ", "_"));
-    img.save(current_dir.join(&output_path)).expect("Failed to save the image");
+    let output_path = match (output_path, &append_to) {
+        (Some(path), _) if formats.len() > 1 => path.with_extension("png"),
+        (Some(path), _) => path,
+        (None, Some(path)) => path.clone(),
+        (None, None) => current_dir.join(format!("{}.png", file_stem)),
+    };
+    let provenance = output::Provenance {
+        author: author.clone(),
+        sources,
+        period: header.as_ref().map(|header| (header.first, header.last)),
+        theme: theme_name.to_string(),
+    };
+    output::save_png(&img, &output_path, metadata.as_ref(), Some(&provenance), dpi)?;
+    println!("Wrote {}", output_path.display());
+    if let Some(report) = report {
+        report.save()?;
+    }
+
+    if let Some(protocol) = preview {
+        preview::show(&img, protocol)?;
+    }
+    Ok(local_repos)
 }
@@ -0,0 +1,97 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{Datelike, Duration, Month, NaiveDate, Weekday};
+
+use crate::collect::Activity;
+use crate::stats::YearStats;
+
+/// A paragraph summarizing one year in prose, for `--narrative`, e.g. "In
+/// 2023 you made 812 commits across 9 repositories, peaking in October
+/// with 143 commits."
+pub fn year_narrative(stats: &YearStats, previous: Option<&YearStats>, activity: &Activity) -> String {
+    let year = stats.year;
+    let mut sentences = Vec::new();
+
+    let repos = activity.repo_commits.iter()
+        .filter(|(_, years)| years.get(&year).is_some_and(|&count| count > 0))
+        .count();
+    let across = if repos > 1 { format!(" across {} repositories", repos) } else { String::new() };
+
+    let mut months: HashMap<u32, i32> = HashMap::new();
+    for (date, &count) in activity.commits.iter().filter(|(date, _)| date.year() == year) {
+        *months.entry(date.month()).or_insert(0) += count;
+    }
+    let peak = months.iter()
+        .max_by_key(|&(&month, &count)| (count, std::cmp::Reverse(month)))
+        .and_then(|(&month, &count)| Month::try_from(month as u8).ok().map(|month| (month, count)));
+    match peak {
+        Some((month, count)) => sentences.push(format!(
+            "In {} you made {} commits{}, peaking in {} with {} commits.",
+            year, stats.commits, across, month.name(), count
+        )),
+        None => sentences.push(format!("In {} you made {} commits{}.", year, stats.commits, across)),
+    }
+
+    if let Some(previous) = previous.filter(|previous| previous.commits > 0) {
+        let change = (stats.commits - previous.commits) as f64 * 100.0 / previous.commits as f64;
+        if change.abs() < 5.0 {
+            sentences.push(format!("That is about as many as in {}.", previous.year));
+        } else {
+            let direction = if change > 0.0 { "more" } else { "fewer" };
+            sentences.push(format!("That is {:.0}% {} than in {}.", change.abs(), direction, previous.year));
+        }
+    }
+
+    if activity.has_diff_stats && stats.diff.files > 0 {
+        sentences.push(format!(
+            "Together they added {} and removed {} lines.",
+            stats.diff.insertions, stats.diff.deletions
+        ));
+    }
+
+    let busiest = (0..7)
+        .max_by_key(|&day| (stats.weekday_commits[day], std::cmp::Reverse(day)))
+        .map(|day| Weekday::try_from(day as u8).expect("weekday index is below 7"));
+    if let Some(weekday) = busiest {
+        let weekend_percent = stats.day_off_commits as f64 * 100.0 / stats.commits.max(1) as f64;
+        sentences.push(format!(
+            "{} was your busiest day of the week, and {:.0}% of the commits were made on days off.",
+            weekday_name(weekday), weekend_percent
+        ));
+    }
+
+    let streak = longest_streak(activity.commits.keys().filter(|date| date.year() == year).copied().collect());
+    if streak > 1 {
+        sentences.push(format!("Your longest streak lasted {} days in a row.", streak));
+    }
+
+    sentences.join(" ")
+}
+
+/// Most consecutive days with commits
+pub fn longest_streak(dates: BTreeSet<NaiveDate>) -> i64 {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut last: Option<NaiveDate> = None;
+    for date in dates {
+        current = match last {
+            Some(last) if date - last == Duration::days(1) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        last = Some(date);
+    }
+    longest
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
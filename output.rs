@@ -0,0 +1,383 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+use chrono::NaiveDate;
+use image::{imageops, ImageBuffer, Rgba};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+use crate::header::{Header, HEADER_HEIGHT};
+
+/// An image along with the layout of its wall
+type Wall = (ImageBuffer<Rgba<u8>, Vec<u8>>, WallMetadata);
+
+/// Keyword of the PNG text chunk holding the `WallMetadata`
+const METADATA_KEYWORD: &str = "commits-tilewall";
+
+/// Version of the `WallMetadata` format, see `METADATA_SCHEMA`
+pub const METADATA_VERSION: u32 = 1;
+
+/// JSON Schema of the `WallMetadata`, printed by the `schema` command
+pub const METADATA_SCHEMA: &str = include_str!("schema/wall-metadata.schema.json");
+
+/// Layout of a rendered wall, embedded in the PNG so that later runs can
+/// add rows to it with `--append-to`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WallMetadata {
+    /// Images written before the format was versioned have none
+    #[serde(default = "first_version")]
+    pub version: u32,
+    pub theme: String,
+    /// Year rows from top to bottom
+    pub rows: Vec<YearRow>,
+    /// First commit shown in the header, the archived rows may go back
+    /// further than the history collected when appending
+    #[serde(default)]
+    pub first_commit: Option<NaiveDate>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct YearRow {
+    pub year: i32,
+    pub y: u32,
+    pub height: u32,
+}
+
+/// How an image was made, written along with it as text chunks so that
+/// an image found later can be traced back to its commits
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    pub author: String,
+    /// The repositories as given, remote ones by their URL, or the forge
+    /// account the commits came from
+    pub sources: Vec<String>,
+    /// Dates of the first and the last commit shown
+    pub period: Option<(NaiveDate, NaiveDate)>,
+    pub theme: String,
+}
+
+impl Provenance {
+    /// Keywords and texts of the chunks, the predefined keywords of the
+    /// PNG specification where one fits
+    fn texts(&self) -> Vec<(&'static str, String)> {
+        let mut texts = vec![
+            ("Title", format!("Commits of {}", self.author)),
+            ("Author", self.author.clone()),
+            ("Source", self.sources.join("\n")),
+        ];
+        if let Some((first, last)) = self.period {
+            texts.push(("Period", format!("{} - {}", first, last)));
+        }
+        texts.push(("Theme", self.theme.clone()));
+        texts.push(("Software", format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))));
+        texts
+    }
+}
+
+impl WallMetadata {
+    /// Layout of a wall rendered by `generate_commit_image`, which gives all
+    /// years the same height
+    pub fn for_wall(theme: &str, years: &[i32], image_height: u32) -> WallMetadata {
+        let height = image_height / years.len().max(1) as u32;
+        let rows = years.iter()
+            .enumerate()
+            .map(|(index, &year)| YearRow { year, y: index as u32 * height, height })
+            .collect();
+        WallMetadata { version: METADATA_VERSION, theme: theme.to_string(), rows, first_commit: None }
+    }
+
+    /// Layout after putting the header band on top, see `header::add_header`
+    pub fn with_header(mut self, header: &Header) -> WallMetadata {
+        for row in &mut self.rows {
+            row.y += HEADER_HEIGHT;
+        }
+        self.first_commit = Some(header.first);
+        self
+    }
+
+    pub fn newest_year(&self) -> Option<i32> {
+        self.rows.iter().map(|row| row.year).max()
+    }
+}
+
+/// Encodes the image as PNG, with the layout of the wall in a text chunk
+pub fn encode_png(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, metadata: Option<&WallMetadata>) -> Result<Vec<u8>> {
+    encode_png_at(img, metadata, None, None)
+}
+
+/// Like `encode_png`, noting how the image was made and the resolution it
+/// is meant to be printed at, see `--dpi`
+fn encode_png_at(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, metadata: Option<&WallMetadata>, provenance: Option<&Provenance>, dpi: Option<u32>) -> Result<Vec<u8>> {
+    let failed = |err: &dyn std::fmt::Display| Error::Output(format!("Failed to encode the image: {}", err));
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, img.width(), img.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    if let Some(dpi) = dpi {
+        // PNG counts the pixels per meter
+        let per_meter = (dpi as f64 / 0.0254).round() as u32;
+        encoder.set_pixel_dims(Some(png::PixelDimensions { xppu: per_meter, yppu: per_meter, unit: png::Unit::Meter }));
+    }
+    if let Some(metadata) = metadata {
+        let text = serde_json::to_string(metadata).map_err(|err| failed(&err))?;
+        encoder.add_text_chunk(METADATA_KEYWORD.to_string(), text).map_err(|err| failed(&err))?;
+    }
+    for (keyword, text) in provenance.map(Provenance::texts).unwrap_or_default() {
+        // tEXt chunks only hold Latin-1, iTXt chunks hold UTF-8
+        let added = if text.chars().all(|c| (c as u32) < 0x100) {
+            encoder.add_text_chunk(keyword.to_string(), text)
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), text)
+        };
+        added.map_err(|err| failed(&err))?;
+    }
+
+    encoder.write_header()
+        .and_then(|mut writer| writer.write_image_data(img.as_raw()))
+        .map_err(|err| failed(&err))?;
+    Ok(data)
+}
+
+/// Longest part of a file name taken from a name, in characters, which
+/// keeps the whole file name below the 255 bytes most file systems allow
+const MAX_SLUG_CHARS: usize = 50;
+
+/// A name like an author made safe for a file name, e.g. `Jane_Doe` for
+/// `Jane Doe`. Letters and digits of any script are kept along with
+/// `-`, `.` and `@`, anything else like slashes, colons or parentheses
+/// becomes a single underscore.
+pub fn file_name_slug(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || matches!(c, '-' | '.' | '@') {
+            slug.push(c);
+        } else if !slug.ends_with('_') {
+            slug.push('_');
+        }
+    }
+    // Leading dots would hide the file, trailing ones are dropped on
+    // Windows
+    let slug: String = slug.trim_matches(['_', '.']).chars().take(MAX_SLUG_CHARS).collect();
+    let slug = slug.trim_end_matches(['_', '.']);
+    if slug.is_empty() {
+        "unnamed".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+pub fn save_png(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, path: &Path, metadata: Option<&WallMetadata>, provenance: Option<&Provenance>, dpi: Option<u32>) -> Result<()> {
+    fs::write(path, encode_png_at(img, metadata, provenance, dpi)?)
+        .map_err(|err| Error::Output(format!("Failed to save the image to {}: {}", path.display(), err)))
+}
+
+/// Sixel graphics, with the 256 most used colors as palette and every other
+/// color mapped to the closest of them. Transparent pixels are drawn on
+/// white.
+pub fn encode_sixel(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> String {
+    let opaque = |pixel: &Rgba<u8>| -> [u8; 3] {
+        let alpha = pixel[3] as u32;
+        [0, 1, 2].map(|c| ((pixel[c] as u32 * alpha + 255 * (255 - alpha)) / 255) as u8)
+    };
+
+    let mut usage: HashMap<[u8; 3], usize> = HashMap::new();
+    for pixel in img.pixels() {
+        *usage.entry(opaque(pixel)).or_insert(0) += 1;
+    }
+    let mut colors: Vec<([u8; 3], usize)> = usage.into_iter().collect();
+    colors.sort_unstable_by_key(|&(color, count)| (Reverse(count), color));
+    let palette: Vec<[u8; 3]> = colors.iter().take(256).map(|&(color, _)| color).collect();
+    let distance = |a: &[u8; 3], b: &[u8; 3]| -> i32 {
+        (0..3).map(|c| (a[c] as i32 - b[c] as i32).pow(2)).sum()
+    };
+    let index: HashMap<[u8; 3], usize> = colors.iter()
+        .map(|(color, _)| {
+            let closest = (0..palette.len()).min_by_key(|&i| distance(color, &palette[i])).unwrap_or(0);
+            (*color, closest)
+        })
+        .collect();
+
+    let mut escape = format!("\x1bPq\"1;1;{};{}", img.width(), img.height());
+    for (i, color) in palette.iter().enumerate() {
+        let percent = |value: u8| value as u32 * 100 / 255;
+        escape.push_str(&format!("#{};2;{};{};{}", i, percent(color[0]), percent(color[1]), percent(color[2])));
+    }
+
+    let palette_size = palette.len();
+    for band in (0..img.height()).step_by(6) {
+        let rows = (img.height() - band).min(6);
+
+        // Sixel bits of every column per color used in this band
+        let mut columns: Vec<Option<Vec<u8>>> = vec![None; palette_size];
+        for x in 0..img.width() {
+            for row in 0..rows {
+                let color = index[&opaque(img.get_pixel(x, band + row))];
+                columns[color].get_or_insert_with(|| vec![0; img.width() as usize])[x as usize] |= 1 << row;
+            }
+        }
+
+        for (color, bits) in columns.iter().enumerate() {
+            let Some(bits) = bits else { continue };
+            escape.push_str(&format!("#{}", color));
+            let mut i = 0;
+            while i < bits.len() {
+                let run = bits[i..].iter().take_while(|&&b| b == bits[i]).count();
+                let c = (63 + bits[i]) as char;
+                if run > 3 {
+                    escape.push_str(&format!("!{}{}", run, c));
+                } else {
+                    escape.extend(std::iter::repeat_n(c, run));
+                }
+                i += run;
+            }
+            escape.push('$');
+        }
+        escape.push('-');
+    }
+
+    escape.push_str("\x1b\\");
+    escape
+}
+
+/// Loads an image written by `save_png`, along with its layout
+pub fn load_png(path: &Path) -> Result<Wall> {
+    let file = File::open(path)
+        .map_err(|err| Error::Input(format!("Failed to open {}: {}", path.display(), err)))?;
+    let reader = png::Decoder::new(file).read_info()
+        .map_err(|err| Error::Input(format!("Failed to read {}: {}", path.display(), err)))?;
+
+    let metadata: WallMetadata = reader.info().uncompressed_latin1_text.iter()
+        .find(|chunk| chunk.keyword == METADATA_KEYWORD)
+        .ok_or_else(|| Error::Input(format!("{} has no layout metadata, only images of a single wall can be appended to", path.display())))
+        .and_then(|chunk| serde_json::from_str(&chunk.text)
+            .map_err(|err| Error::Input(format!("Invalid layout metadata in {}: {}", path.display(), err))))?;
+    if metadata.version > METADATA_VERSION {
+        return Err(Error::Input(format!("{} was written by a newer version of commits-tilewall (layout version {})", path.display(), metadata.version)));
+    }
+
+    let img = image::open(path)
+        .map_err(|err| Error::Input(format!("Failed to read {}: {}", path.display(), err)))?
+        .to_rgba8();
+    Ok((img, metadata))
+}
+
+/// Puts the freshly rendered rows on top of the archived ones, dropping
+/// archived rows of years that were rendered again. The separator is drawn
+/// in the middle of the `year_gap` between the fresh and the archived rows.
+pub fn append_rows(
+    archived: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    archived_metadata: &WallMetadata,
+    fresh: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    fresh_metadata: &WallMetadata,
+    year_gap: u32,
+    separator: Rgba<u8>,
+) -> Result<Wall> {
+    if archived.width() != fresh.width() {
+        return Err(Error::Input(format!("The new rows are {} pixels wide, but the archived image is {} pixels wide", fresh.width(), archived.width())));
+    }
+
+    let kept: Vec<&YearRow> = archived_metadata.rows.iter()
+        .filter(|row| !fresh_metadata.rows.iter().any(|fresh_row| fresh_row.year == row.year))
+        .collect();
+    let height = fresh.height() + kept.iter().map(|row| row.height).sum::<u32>();
+
+    let mut img = ImageBuffer::new(fresh.width(), height);
+    imageops::replace(&mut img, fresh, 0, 0);
+    let mut rows = fresh_metadata.rows.clone();
+    let mut y = fresh.height();
+    if !kept.is_empty() {
+        // The separator above the first archived row belongs to the row
+        // before it, which was not kept
+        for x in 0..img.width() {
+            img.put_pixel(x, y - year_gap / 2, separator);
+        }
+    }
+    for row in kept {
+        let archived_row = imageops::crop_imm(archived, 0, row.y, archived.width(), row.height);
+        imageops::replace(&mut img, &*archived_row, 0, y as i64);
+        rows.push(YearRow { year: row.year, y, height: row.height });
+        y += row.height;
+    }
+
+    Ok((img, WallMetadata { rows, first_commit: archived_metadata.first_commit, ..fresh_metadata.clone() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::process;
+
+    use super::*;
+
+    /// Writes the image to a file of its own and loads it again
+    fn round_trip(name: &str, data: &[u8]) -> Result<Wall> {
+        let path = env::temp_dir().join(format!("commits-tilewall-test-{}-{}.png", process::id(), name));
+        fs::write(&path, data).unwrap();
+        let loaded = load_png(&path);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn layouts_round_trip_through_the_png() {
+        let img = ImageBuffer::from_pixel(4, 6, Rgba([1, 2, 3, 255]));
+        let metadata = WallMetadata::for_wall("github", &[2024, 2023, 2022], 6);
+        let (loaded_img, loaded) = round_trip("layout", &encode_png(&img, Some(&metadata)).unwrap()).unwrap();
+
+        assert_eq!(loaded_img, img);
+        assert_eq!(loaded.theme, "github");
+        let rows: Vec<_> = loaded.rows.iter().map(|row| (row.year, row.y, row.height)).collect();
+        assert_eq!(rows, [(2024, 0, 2), (2023, 2, 2), (2022, 4, 2)]);
+        assert_eq!(loaded.newest_year(), Some(2024));
+    }
+
+    #[test]
+    fn images_without_a_layout_are_rejected() {
+        let img = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let loaded = round_trip("no-layout", &encode_png(&img, None).unwrap());
+        assert!(matches!(loaded, Err(Error::Input(_))));
+    }
+    #[test]
+    fn layouts_before_the_version_are_the_first_one() {
+        let metadata: WallMetadata = serde_json::from_str(r#"{"theme":"github","rows":[{"year":2024,"y":0,"height":200}]}"#).unwrap();
+        assert_eq!(metadata.version, 1);
+        assert!(metadata.first_commit.is_none());
+        assert!(serde_json::from_str::<WallMetadata>(r#"{"theme":"github","rows":[],"columns":4}"#).is_err());
+    }
+
+    #[test]
+    fn layouts_of_newer_versions_are_rejected() {
+        let img = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let metadata = WallMetadata { version: METADATA_VERSION + 1, ..WallMetadata::for_wall("github", &[2024], 2) };
+        let loaded = round_trip("newer", &encode_png(&img, Some(&metadata)).unwrap());
+        assert!(matches!(loaded, Err(Error::Input(message)) if message.contains("newer version")));
+    }
+    #[test]
+    fn provenance_is_written_in_text_chunks() {
+        let img = ImageBuffer::from_pixel(2, 2, Rgba([0, 0, 0, 255]));
+        let provenance = Provenance {
+            author: "Jürgen Müller".to_string(),
+            sources: vec!["https://example.com/tilewall.git".to_string(), "../dotfiles".to_string()],
+            period: NaiveDate::from_ymd_opt(2020, 1, 6).zip(NaiveDate::from_ymd_opt(2024, 12, 20)),
+            theme: "小森".to_string(),
+        };
+        let data = encode_png_at(&img, None, Some(&provenance), None).unwrap();
+        let reader = png::Decoder::new(data.as_slice()).read_info().unwrap();
+        let info = reader.info();
+
+        let latin1: HashMap<_, _> = info.uncompressed_latin1_text.iter().map(|chunk| (chunk.keyword.as_str(), chunk.text.as_str())).collect();
+        assert_eq!(latin1["Author"], "Jürgen Müller");
+        assert_eq!(latin1["Source"], "https://example.com/tilewall.git\n../dotfiles");
+        assert_eq!(latin1["Period"], "2020-01-06 - 2024-12-20");
+        let theme = info.utf8_text.iter().find(|chunk| chunk.keyword == "Theme").unwrap();
+        assert_eq!(theme.get_text().unwrap(), "小森");
+    }
+}
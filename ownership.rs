@@ -0,0 +1,161 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Command;
+
+use image::{ImageBuffer, Rgba};
+
+use crate::cache::{self, CacheMode};
+use crate::collect::{repo_name, run_git};
+use crate::error::Result;
+use crate::fonts::FontChain;
+use crate::log;
+use crate::panels::Panel;
+use crate::render::{draw_sharp_text, Grid};
+use crate::stats::YearStats;
+use crate::theme::Theme;
+
+/// The tree of a repository without any commits, to count the lines of
+/// every file at HEAD with `git diff --numstat`
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Repositories listed in the panel, the others are summed up in a line
+const MAX_REPOS: usize = 4;
+
+/// Width of the panel, names longer than `MAX_NAME` are cut. The sizes
+/// are scaled with the labels, see `Grid::px`.
+const PANEL_WIDTH: u32 = 160;
+const MAX_NAME: usize = 16;
+const BAR_WIDTH: u32 = 30;
+
+/// The lines at HEAD of a repository last changed by the author, see
+/// `--ownership`
+#[derive(Debug, Clone)]
+pub struct Ownership {
+    pub repo: String,
+    pub owned: u64,
+    pub total: u64,
+}
+
+impl Ownership {
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.owned as f64 * 100.0 / self.total as f64
+        }
+    }
+}
+
+/// Blames every text file at HEAD of the repositories, cached per HEAD as
+/// it takes a while in larger repositories
+pub fn ownership(authors: &[String], repos: &[String], cache_mode: CacheMode) -> Result<Vec<Ownership>> {
+    repos.iter().map(|repo| {
+        let (owned, total) = cache::ownership(authors, repo, cache_mode, || blame(authors, repo))?;
+        log::info!("Owned {} of {} lines at HEAD of {}", owned, total, repo);
+        Ok(Ownership { repo: repo.clone(), owned, total })
+    }).collect()
+}
+
+/// Lines at HEAD of `repo` last changed by a commit of `authors`, and all
+/// lines of the text files at HEAD
+fn blame(authors: &[String], repo: &str) -> Result<(u64, u64)> {
+    let mut command = Command::new("git");
+    command
+        .args(["log", "--format=%H"])
+        .args(authors.iter().flat_map(|author| ["--author", author]))
+        .args(["HEAD", "--"]);
+    let own_commits: HashSet<String> = run_git(&mut command, repo)?.lines().map(str::to_string).collect();
+
+    // Binary files are listed with - instead of their lines
+    let mut command = Command::new("git");
+    command.args(["diff", "--numstat", "-z", EMPTY_TREE, "HEAD", "--"]);
+    let files: Vec<String> = run_git(&mut command, repo)?
+        .split('\0')
+        .filter_map(|entry| {
+            let mut fields = entry.splitn(3, '\t');
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(added), Some(_), Some(path)) if added != "-" && added != "0" => Some(path.to_string()),
+                _ => None,
+            }
+        })
+        .collect();
+    log::verbose!("Blaming {} files in {}", files.len(), repo);
+
+    let mut owned = 0;
+    let mut total = 0;
+    for file in &files {
+        let mut command = Command::new("git");
+        command.args(["blame", "--porcelain", "HEAD", "--"]).arg(file);
+        let mut lines: HashMap<String, u64> = HashMap::new();
+        for line in run_git(&mut command, repo)?.lines() {
+            // Every line of the file is preceded by "<sha> <line> <line>"
+            // and, when it starts a group, the size of the group
+            let fields: Vec<&str> = line.split(' ').collect();
+            if (fields.len() == 3 || fields.len() == 4) && fields[0].len() == 40 && fields[0].bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                *lines.entry(fields[0].to_string()).or_insert(0) += 1;
+            }
+        }
+        for (commit, count) in lines {
+            if own_commits.contains(&commit) {
+                owned += count;
+            }
+            total += count;
+        }
+    }
+    Ok((owned, total))
+}
+
+/// The share of the lines at HEAD owned by the author, drawn next to the
+/// summary of the newest year only, as it describes the current tree
+#[derive(Debug)]
+pub struct OwnershipPanel {
+    pub year: i32,
+    /// Ordered by the owned share, the largest first
+    pub repos: Vec<Ownership>,
+}
+
+impl OwnershipPanel {
+    pub fn new(year: i32, mut repos: Vec<Ownership>) -> OwnershipPanel {
+        repos.sort_by(|a, b| b.percent().total_cmp(&a.percent()));
+        OwnershipPanel { year, repos }
+    }
+}
+
+impl Panel for OwnershipPanel {
+    fn measure(&self, stats: &YearStats, grid: &Grid) -> u32 {
+        if stats.year == self.year { grid.px(PANEL_WIDTH) } else { 0 }
+    }
+
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain) {
+        if stats.year != self.year {
+            return;
+        }
+        let line_height = grid.px(12);
+        let text_size = grid.label_unit as f32 * 0.8;
+        let bar_width = grid.px(BAR_WIDTH);
+        draw_sharp_text(img, "Lines owned at HEAD", x as i32, y as i32, text_size, theme.text_primary, font);
+
+        for (i, ownership) in self.repos.iter().take(MAX_REPOS).enumerate() {
+            let line_y = y + (i as u32 + 1) * line_height;
+            let filled = (ownership.percent() / 100.0 * bar_width as f64).round() as u32;
+            for bx in 0..bar_width {
+                for by in grid.px(1)..line_height - grid.px(3) {
+                    if x + bx < img.width() && line_y + by < img.height() {
+                        let color = if bx < filled { theme.commit_colors[4] } else { theme.commit_colors[0] };
+                        img.put_pixel(x + bx, line_y + by, color);
+                    }
+                }
+            }
+            let mut name = repo_name(&ownership.repo);
+            if name.chars().count() > MAX_NAME {
+                name = name.chars().take(MAX_NAME - 1).chain(['…']).collect();
+            }
+            let text = format!("{:.0}% {}", ownership.percent(), name);
+            draw_sharp_text(img, &text, (x + bar_width + grid.px(4)) as i32, line_y as i32, text_size, theme.text_secondary, font);
+        }
+        if self.repos.len() > MAX_REPOS {
+            let line_y = y + (MAX_REPOS as u32 + 1) * line_height;
+            let text = format!("and {} more", self.repos.len() - MAX_REPOS);
+            draw_sharp_text(img, &text, x as i32, line_y as i32, text_size, theme.text_secondary, font);
+        }
+    }
+}
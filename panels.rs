@@ -0,0 +1,146 @@
+use std::fmt;
+
+use image::{ImageBuffer, Rgba};
+use imageproc::drawing::draw_antialiased_line_segment_mut;
+use imageproc::pixelops::interpolate;
+
+use crate::fonts::FontChain;
+use crate::locale::Locale;
+use crate::render::{draw_sharp_text, Grid};
+use crate::stats::YearStats;
+use crate::theme::Theme;
+
+/// A panel drawn in the summary of every year. Besides the built-in weekday
+/// chart, panels added with `RenderOptions::register_panel` are drawn to the right of
+/// the summary, in the order they were added.
+pub trait Panel: fmt::Debug {
+    /// Width of the panel in pixels for the given year and grid, whose
+    /// `label_unit` sizes the text like in the summary. The panel gets the
+    /// widest of all years, so that the panels of all years line up.
+    fn measure(&self, stats: &YearStats, grid: &Grid) -> u32;
+
+    /// Draws the panel with its top left corner at `x`, `y`. The panel may
+    /// use the height of the year row below `y`, which grows with the tiles
+    /// of `grid`.
+    #[allow(clippy::too_many_arguments)]
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain);
+}
+
+/// Bars of the commits per weekday, Monday first, scaled to the busiest
+/// weekday and labeled with the initials of the weekdays in the locale
+#[derive(Debug)]
+pub struct WeekdayChart(pub Locale);
+
+impl Panel for WeekdayChart {
+    fn measure(&self, _stats: &YearStats, grid: &Grid) -> u32 {
+        7 * (grid.label_unit + grid.px(4))
+    }
+
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain) {
+        let label_unit = grid.label_unit;
+        let bar_width = label_unit + grid.px(2);
+        let bar_spacing = grid.px(2);
+        let max_bar_height = label_unit * 4;
+        let max = stats.weekday_commits.iter().copied().max().unwrap_or(0).max(1);
+        let initials = self.0.weekday_initials();
+
+        for (i, &count) in stats.weekday_commits.iter().enumerate() {
+            let bar_x = x + i as u32 * (bar_width + bar_spacing);
+            let bar_height = (count as u32 * max_bar_height).div_ceil(max as u32);
+            for by in max_bar_height - bar_height..max_bar_height {
+                for bx in 0..bar_width {
+                    if bar_x + bx < img.width() && y + by < img.height() {
+                        img.put_pixel(bar_x + bx, y + by, theme.commit_colors[4]);
+                    }
+                }
+            }
+
+            draw_sharp_text(
+                img,
+                &initials[i],
+                (bar_x + bar_width / 4) as i32,
+                (y + max_bar_height + grid.px(1)) as i32,
+                label_unit as f32 * 0.8,
+                theme.text_secondary,
+                font
+            );
+        }
+    }
+}
+
+/// Bars of the commits per month, January first, scaled to the busiest
+/// month of the year and labeled with the initials of the months in the
+/// locale, see `--monthly-bars`
+#[derive(Debug)]
+pub struct MonthlyBars(pub Locale);
+
+impl Panel for MonthlyBars {
+    fn measure(&self, _stats: &YearStats, grid: &Grid) -> u32 {
+        12 * grid.label_unit
+    }
+
+    fn render(&self, img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, stats: &YearStats, grid: &Grid, theme: &Theme, font: &FontChain) {
+        let label_unit = grid.label_unit;
+        let bar_width = label_unit - grid.px(2);
+        let bar_spacing = grid.px(2);
+        let max_bar_height = label_unit * 4;
+        let max = stats.month_commits.iter().copied().max().unwrap_or(0).max(1);
+        let initials = self.0.month_initials();
+
+        for (i, &count) in stats.month_commits.iter().enumerate() {
+            let bar_x = x + i as u32 * (bar_width + bar_spacing);
+            let bar_height = (count as u32 * max_bar_height).div_ceil(max as u32);
+            for by in max_bar_height - bar_height..max_bar_height {
+                for bx in 0..bar_width {
+                    if bar_x + bx < img.width() && y + by < img.height() {
+                        img.put_pixel(bar_x + bx, y + by, theme.commit_colors[4]);
+                    }
+                }
+            }
+
+            draw_sharp_text(
+                img,
+                &initials[i],
+                (bar_x + grid.px(1)) as i32,
+                (y + max_bar_height + grid.px(1)) as i32,
+                label_unit as f32 * 0.8,
+                theme.text_secondary,
+                font
+            );
+        }
+    }
+}
+
+/// Draws the values as a line through the box of `width` and `height` at
+/// `x`, `y`, spread evenly from its left to its right edge and scaled so
+/// that the largest value reaches the top. With `fill`, the area below the
+/// line is filled as well.
+#[allow(clippy::too_many_arguments)]
+pub fn draw_line_chart(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, width: u32, height: u32, values: &[f32], line: Rgba<u8>, fill: Option<Rgba<u8>>) {
+    if values.len() < 2 || width < 2 || height < 2 {
+        return;
+    }
+    let max = values.iter().copied().fold(0.0, f32::max);
+    let max = if max > 0.0 { max } else { 1.0 };
+    let step = (width - 1) as f32 / (values.len() - 1) as f32;
+    let value_y = |value: f32| y as f32 + (height - 1) as f32 * (1.0 - value / max);
+
+    if let Some(fill) = fill {
+        for column in 0..width {
+            // The value at the column, interpolated between its neighbors
+            let position = column as f32 / step;
+            let i = (position.floor() as usize).min(values.len() - 2);
+            let value = values[i] + (values[i + 1] - values[i]) * (position - i as f32);
+            for py in value_y(value).round() as u32..y + height {
+                if x + column < img.width() && py < img.height() {
+                    img.put_pixel(x + column, py, fill);
+                }
+            }
+        }
+    }
+    for (i, pair) in values.windows(2).enumerate() {
+        let start = ((x as f32 + i as f32 * step).round() as i32, value_y(pair[0]).round() as i32);
+        let end = ((x as f32 + (i + 1) as f32 * step).round() as i32, value_y(pair[1]).round() as i32);
+        draw_antialiased_line_segment_mut(img, start, end, line, interpolate);
+    }
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use ab_glyph::PxScale;
+use image::Rgba;
+use ttf_parser::{name_id, Face, GlyphId};
+
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::fonts::FontChain;
+use crate::header::Header;
+use crate::holidays::Holidays;
+use crate::qr::{MODULE_SIZE, QUIET_ZONE};
+use crate::render::RenderOptions;
+use crate::stats::{YearStats, LEVELS};
+use crate::theme::Theme;
+use crate::vector::{draw_wall, wall_layout, Canvas, Corner, Style};
+
+/// Points per pixel, taking the pixels of the PNG as CSS pixels of 1/96
+/// inch like the SVG
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+/// Share of the text color in the tint of weekends, like in the PNG
+const WEEKEND_TINT: f32 = 0.2;
+
+/// The glyphs drawn with every font of the chain, by the index of the
+/// font, with the characters they stand for
+type UsedGlyphs = BTreeMap<usize, BTreeMap<u16, char>>;
+
+/// Draws on the content stream of a PDF page. The text is set in the fonts
+/// of the chain, like in the PNG, which are embedded with the glyphs drawn,
+/// see `embedded_font`.
+struct PdfCanvas<'a> {
+    content: String,
+    theme: &'a Theme,
+    font: &'a FontChain,
+    glyphs: &'a mut UsedGlyphs,
+    /// Size of the day tiles and the radius of their corners, see
+    /// `render::Grid`
+    block_size: u32,
+    corner_radius: u32,
+}
+
+impl PdfCanvas<'_> {
+    fn color(&self, style: Style) -> Rgba<u8> {
+        match style {
+            Style::Primary | Style::Holiday => self.theme.text_primary,
+            Style::Secondary | Style::Weekend => self.theme.text_secondary,
+            Style::Separator => self.theme.separator,
+            Style::Level(level) => self.theme.commit_colors[level],
+            Style::Gap | Style::Dotted => self.theme.commit_colors[0],
+            Style::Sparkline => self.theme.commit_colors[LEVELS],
+        }
+    }
+
+    fn fill_color(&mut self, style: Style) {
+        let [r, g, b, _] = self.color(style).0;
+        writeln!(self.content, "{} {} {} rg", component(r), component(g), component(b)).unwrap();
+    }
+
+    fn stroke_color(&mut self, style: Style) {
+        let [r, g, b, _] = self.color(style).0;
+        writeln!(self.content, "{} {} {} RG", component(r), component(g), component(b)).unwrap();
+    }
+}
+
+impl Canvas for PdfCanvas<'_> {
+    fn begin_group(&mut self, x: u32, y: u32) {
+        writeln!(self.content, "q 1 0 0 1 {} {} cm", x, y).unwrap();
+    }
+
+    fn end_group(&mut self) {
+        self.content.push_str("Q\n");
+    }
+
+    fn text(&mut self, style: Style, x: u32, y: u32, size: f32, content: &str) {
+        self.fill_color(style);
+        // The page is flipped to count from the top, the text is flipped
+        // back
+        let scale = PxScale::from(size);
+        let baseline = y as f32 + self.font.ascent(scale);
+        write!(self.content, "BT 1 0 0 -1 {} {:.1} Tm", x, baseline).unwrap();
+        for run in self.font.glyph_runs(content, scale) {
+            let used = self.glyphs.entry(run.font).or_default();
+            write!(self.content, " /F{} {:.2} Tf [<", run.font, run.em_size).unwrap();
+            for (glyph, c, kern) in run.glyphs {
+                used.entry(glyph).or_insert(c);
+                // Kerning moves the glyph by thousandths of an em, to the
+                // left for positive numbers
+                if kern != 0.0 {
+                    write!(self.content, ">{:.0}<", -kern * 1000.0 / run.em_size).unwrap();
+                }
+                write!(self.content, "{:04x}", glyph).unwrap();
+            }
+            self.content.push_str(">] TJ");
+        }
+        self.content.push_str(" ET\n");
+    }
+
+    fn tile(&mut self, style: Style, x: u32, y: u32, half: bool) {
+        let height = if half { self.block_size / 2 } else { self.block_size };
+        let radius = self.corner_radius.min(height / 2);
+        let path = rounded_rect(x as f32, y as f32, self.block_size as f32, height as f32, radius as f32);
+        self.fill_color(style);
+        if style == Style::Weekend {
+            writeln!(self.content, "/Tint gs {} f /Opaque gs", path).unwrap();
+        } else {
+            writeln!(self.content, "{} f", path).unwrap();
+        }
+    }
+
+    fn border(&mut self, style: Style, x: u32, y: u32, size: u32) {
+        self.stroke_color(style);
+        let dash = if style == Style::Dotted { "[1] 0 d" } else { "[] 0 d" };
+        // The stroke runs half a pixel inside the tile, and so does its
+        // rounding
+        let radius = (self.corner_radius as f32 - 0.5).max(0.0);
+        writeln!(self.content, "1 w {} {} S", dash, rounded_rect(x as f32 + 0.5, y as f32 + 0.5, (size - 1) as f32, (size - 1) as f32, radius)).unwrap();
+    }
+
+    fn rect(&mut self, style: Style, x: u32, y: u32, width: u32, height: u32) {
+        self.fill_color(style);
+        writeln!(self.content, "{} {} {} {} re f", x, y, width, height).unwrap();
+    }
+
+    fn corner(&mut self, style: Style, x: u32, y: u32, size: u32, corner: Corner) {
+        self.fill_color(style);
+        let half = size / 2;
+        match corner {
+            Corner::UpperRight => writeln!(self.content, "{} {} m {} {} l {} {} l h f", x + half, y, x + size, y, x + size, y + half).unwrap(),
+            Corner::LowerLeft => writeln!(self.content, "{} {} m {} {} l {} {} l h f", x, y + half, x, y + size, x + half, y + size).unwrap(),
+        }
+    }
+
+    fn line(&mut self, style: Style, y: i32, width: u32) {
+        self.stroke_color(style);
+        writeln!(self.content, "[] 0 d 1 w 0 {1} m {0} {1} l S", width, y).unwrap();
+    }
+
+    fn polyline(&mut self, style: Style, points: &[(f32, f32)]) {
+        self.stroke_color(style);
+        let path: Vec<String> = points.iter()
+            .enumerate()
+            .map(|(i, (x, y))| format!("{:.1} {:.1} {}", x, y, if i == 0 { "m" } else { "l" }))
+            .collect();
+        writeln!(self.content, "[] 0 d 1 w {} S", path.join(" ")).unwrap();
+    }
+
+    fn qr_code(&mut self, size: u32, modules: &[bool], modules_width: u32) {
+        writeln!(self.content, "1 1 1 rg 0 0 {0} {0} re f 0 0 0 rg", size).unwrap();
+        for (i, _) in modules.iter().enumerate().filter(|(_, &dark)| dark) {
+            let x = (QUIET_ZONE + i as u32 % modules_width) * MODULE_SIZE;
+            let y = (QUIET_ZONE + i as u32 / modules_width) * MODULE_SIZE;
+            writeln!(self.content, "{} {} {2} {2} re", x, y, MODULE_SIZE).unwrap();
+        }
+        self.content.push_str("f\n");
+    }
+}
+
+/// The path of a rectangle with its corners rounded by `radius` as Bézier
+/// curves, or a plain rectangle without a radius
+fn rounded_rect(x: f32, y: f32, width: f32, height: f32, radius: f32) -> String {
+    if radius <= 0.0 {
+        return format!("{} {} {} {} re", x, y, width, height);
+    }
+    // Distance of the control points from the ends of a quarter circle
+    let k = radius * 0.5523;
+    let (right, bottom) = (x + width, y + height);
+    let mut path = String::new();
+    write!(path, "{} {} m {} {} l ", x + radius, y, right - radius, y).unwrap();
+    write!(path, "{} {} {} {} {} {} c ", right - radius + k, y, right, y + radius - k, right, y + radius).unwrap();
+    write!(path, "{} {} l ", right, bottom - radius).unwrap();
+    write!(path, "{} {} {} {} {} {} c ", right, bottom - radius + k, right - radius + k, bottom, right - radius, bottom).unwrap();
+    write!(path, "{} {} l ", x + radius, bottom).unwrap();
+    write!(path, "{} {} {} {} {} {} c ", x + radius - k, bottom, x, bottom - radius + k, x, bottom - radius).unwrap();
+    write!(path, "{} {} l ", x, y + radius).unwrap();
+    write!(path, "{} {} {} {} {} {} c h", x, y + radius - k, x + radius - k, y, x + radius, y).unwrap();
+    path
+}
+
+/// A color component as a PDF number from 0 to 1
+fn component(value: u8) -> String {
+    let value = format!("{:.3}", value as f32 / 255.0);
+    value.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+/// Renders the wall as a PDF of vector shapes with the same layout as the
+/// SVG, on one page or with `page_per_year` on one page for every year
+#[allow(clippy::too_many_arguments)]
+pub fn generate_commit_pdf(activity: &Activity, active_years: &[YearStats], header: Option<&Header>, holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions, page_per_year: bool) -> Result<Vec<u8>> {
+    let walls: Vec<&[YearStats]> = if page_per_year {
+        active_years.chunks(1).collect()
+    } else {
+        vec![active_years]
+    };
+    let mut glyphs = UsedGlyphs::new();
+    let mut pages = Vec::new();
+    for years in walls {
+        let layout = wall_layout(activity, years, header, holidays, font, options);
+        let mut canvas = PdfCanvas {
+            content: String::new(),
+            theme,
+            font,
+            glyphs: &mut glyphs,
+            block_size: options.grid.block_size,
+            corner_radius: options.grid.tile_radius(),
+        };
+        // Counts from the top left corner in pixels, like the SVG
+        writeln!(canvas.content, "{0} 0 0 -{0} 0 {1} cm", POINTS_PER_PIXEL, layout.height as f32 * POINTS_PER_PIXEL).unwrap();
+        let [r, g, b, _] = theme.background.0;
+        writeln!(canvas.content, "{} {} {} rg 0 0 {} {} re f", component(r), component(g), component(b), layout.width, layout.height).unwrap();
+        draw_wall(&mut canvas, &layout, activity, years, header, holidays, font, options);
+        pages.push((layout.width, layout.height, canvas.content));
+    }
+
+    // The fonts follow the catalog, the page tree and the pages
+    let mut fonts = String::new();
+    let mut font_objects = Vec::new();
+    for (&index, glyphs) in &glyphs {
+        let first = 3 + 2 * pages.len() + font_objects.len();
+        write!(fonts, "/F{} {} 0 R ", index, first).unwrap();
+        font_objects.extend(embedded_font(font, index, glyphs, first)?);
+    }
+    Ok(document(&pages, &fonts, font_objects))
+}
+
+/// The objects embedding a font of the chain with only the glyphs drawn,
+/// numbered from `first`: the Type 0 font the text refers to, the CID font
+/// below it, its descriptor, the subset of the font file and the map of
+/// the glyphs to their characters, for copying the text. The glyph ids
+/// are the character codes of the text.
+fn embedded_font(font: &FontChain, index: usize, glyphs: &BTreeMap<u16, char>, first: usize) -> Result<Vec<Vec<u8>>> {
+    let ids: Vec<u16> = glyphs.keys().copied().collect();
+    let data = font.subset(index, &ids)?;
+    let face = Face::from_slice(&data, 0)
+        .map_err(|err| Error::Input(format!("Failed to read the subset of the font for embedding: {}", err)))?;
+    // Fonts with CFF outlines are embedded as OpenType, the others as
+    // TrueType
+    let cff = data.starts_with(b"OTTO");
+    let units = |value: f32| (value * 1000.0 / face.units_per_em() as f32).round();
+    let name: String = face.names().into_iter()
+        .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect();
+    // Subsets are named with a tag of six capitals, one per font here
+    let name = format!("TWALL{}+{}", (b'A' + (index % 26) as u8) as char, if name.is_empty() { "Font" } else { &name });
+
+    let widths: Vec<String> = ids.iter()
+        .map(|&id| format!("{} [{}]", id, units(face.glyph_hor_advance(GlyphId(id)).unwrap_or(0) as f32)))
+        .collect();
+    let bbox = face.global_bounding_box();
+    let (subtype, font_file) = if cff { ("CIDFontType0", "FontFile3") } else { ("CIDFontType2", "FontFile2") };
+
+    let type0 = format!(
+        "<< /Type /Font /Subtype /Type0 /BaseFont /{} /Encoding /Identity-H /DescendantFonts [{} 0 R] /ToUnicode {} 0 R >>",
+        name, first + 1, first + 4
+    );
+    let cid_font = format!(
+        "<< /Type /Font /Subtype /{} /BaseFont /{} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {} 0 R{} /W [{}] >>",
+        subtype, name, first + 2, if cff { "" } else { " /CIDToGIDMap /Identity" }, widths.join(" ")
+    );
+    let descriptor = format!(
+        "<< /Type /FontDescriptor /FontName /{} /Flags 4 /FontBBox [{} {} {} {}] /ItalicAngle {} /Ascent {} /Descent {} /CapHeight {} /StemV 80 /{} {} 0 R >>",
+        name,
+        units(bbox.x_min as f32), units(bbox.y_min as f32), units(bbox.x_max as f32), units(bbox.y_max as f32),
+        face.italic_angle().unwrap_or(0.0),
+        units(face.ascender() as f32),
+        units(face.descender() as f32),
+        units(face.capital_height().unwrap_or(face.ascender()) as f32),
+        font_file, first + 3
+    );
+    let file_dict = if cff {
+        format!("<< /Length {} /Subtype /OpenType >>", data.len())
+    } else {
+        format!("<< /Length {0} /Length1 {0} >>", data.len())
+    };
+
+    let mut to_unicode = String::from("/CIDInit /ProcSet findresource begin 12 dict begin begincmap\n");
+    to_unicode.push_str("/CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n");
+    to_unicode.push_str("/CMapName /Adobe-Identity-UCS def /CMapType 2 def\n1 begincodespacerange <0000> <ffff> endcodespacerange\n");
+    // A block maps at most 100 codes
+    let chars: Vec<(&u16, &char)> = glyphs.iter().collect();
+    for block in chars.chunks(100) {
+        writeln!(to_unicode, "{} beginbfchar", block.len()).unwrap();
+        for (id, c) in block {
+            let utf16: String = c.encode_utf16(&mut [0; 2]).iter().map(|unit| format!("{:04x}", unit)).collect();
+            writeln!(to_unicode, "<{:04x}> <{}>", id, utf16).unwrap();
+        }
+        to_unicode.push_str("endbfchar\n");
+    }
+    to_unicode.push_str("endcmap CMapName currentdict /CMap defineresource pop end end\n");
+
+    Ok(vec![
+        type0.into_bytes(),
+        cid_font.into_bytes(),
+        descriptor.into_bytes(),
+        stream(&file_dict, &data),
+        stream(&format!("<< /Length {} >>", to_unicode.len()), to_unicode.as_bytes()),
+    ])
+}
+
+/// A stream object of the data with its dictionary
+fn stream(dictionary: &str, data: &[u8]) -> Vec<u8> {
+    let mut object = format!("{}\nstream\n", dictionary).into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// A PDF document with one page for every content stream, sized in pixels,
+/// followed by the objects of the fonts named in the resources `fonts`
+fn document(pages: &[(u32, u32, String)], fonts: &str, font_objects: Vec<Vec<u8>>) -> Vec<u8> {
+    // The catalog and the page tree come first, then every page and its
+    // content
+    let mut objects = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len()).map(|i| format!("{} 0 R", 3 + 2 * i)).collect::<Vec<_>>().join(" "),
+            pages.len()
+        ).into_bytes(),
+    ];
+    for (i, (width, height, content)) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << {}>> /ExtGState << /Tint << /ca {} >> /Opaque << /ca 1 >> >> >> /Contents {} 0 R >>",
+            *width as f32 * POINTS_PER_PIXEL,
+            *height as f32 * POINTS_PER_PIXEL,
+            fonts,
+            WEEKEND_TINT,
+            4 + 2 * i
+        ).into_bytes());
+        objects.push(stream(&format!("<< /Length {} >>", content.len()), content.as_bytes()));
+    }
+    objects.extend(font_objects);
+
+    // The comment of bytes above 127 marks the file as binary
+    let mut pdf = b"%PDF-1.6\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = Vec::new();
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+    let xref = pdf.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        // Every entry takes 20 bytes, the line break included
+        writeln!(trailer, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(trailer, "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).unwrap();
+    pdf.extend_from_slice(trailer.as_bytes());
+    pdf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cross_references_point_at_the_objects() {
+        let pages = [(10, 20, "0 0 10 20 re f\n".to_string()), (30, 40, String::new())];
+        let pdf = document(&pages, "", vec![stream("<< /Length 3 >>", &[0, 0xff, 1])]);
+
+        let text = String::from_utf8_lossy(&pdf);
+        let xref: usize = text.rsplit("startxref\n").next().unwrap().lines().next().unwrap().parse().unwrap();
+        let table = std::str::from_utf8(&pdf[xref..]).unwrap();
+        assert!(table.starts_with("xref\n0 8\n"));
+        let entries: Vec<usize> = table.lines().skip(3).take(7).map(|entry| entry[..10].parse().unwrap()).collect();
+        for (i, offset) in entries.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()));
+        }
+        assert!(text.contains("/Kids [3 0 R 5 0 R] /Count 2"));
+        assert!(text.contains("/MediaBox [0 0 22.5 30] /Resources << /Font << >>"));
+        let font_stream = b"stream\n\0\xff\x01\nendstream";
+        assert!(pdf.windows(font_stream.len()).any(|window| window == font_stream));
+    }
+}
@@ -0,0 +1,89 @@
+use image::imageops::{self, FilterType};
+use image::{ImageBuffer, Rgba};
+
+use crate::error::{Error, Result};
+
+/// Blank border left on every side of the paper, as most printers can't
+/// print up to the edge
+const MARGIN_MM: f64 = 10.0;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Resolution of `--print` when `--dpi` isn't given
+pub const DEFAULT_DPI: u32 = 300;
+
+/// A paper size of `--print`, in portrait orientation
+#[derive(Debug, Clone, Copy)]
+pub struct Paper {
+    pub name: &'static str,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+const PAPERS: [Paper; 7] = [
+    Paper { name: "a1", width_mm: 594.0, height_mm: 841.0 },
+    Paper { name: "a2", width_mm: 420.0, height_mm: 594.0 },
+    Paper { name: "a3", width_mm: 297.0, height_mm: 420.0 },
+    Paper { name: "a4", width_mm: 210.0, height_mm: 297.0 },
+    Paper { name: "a5", width_mm: 148.0, height_mm: 210.0 },
+    Paper { name: "letter", width_mm: 215.9, height_mm: 279.4 },
+    Paper { name: "tabloid", width_mm: 279.4, height_mm: 431.8 },
+];
+
+impl Paper {
+    pub fn parse(name: &str) -> Result<Paper> {
+        PAPERS.iter()
+            .find(|paper| paper.name.eq_ignore_ascii_case(name))
+            .copied()
+            .ok_or_else(|| {
+                let names: Vec<&str> = PAPERS.iter().map(|paper| paper.name).collect();
+                Error::Usage(format!("Unknown paper size '{}', expected one of {}", name, names.join(", ")))
+            })
+    }
+}
+
+fn pixels(mm: f64, dpi: u32) -> u32 {
+    (mm / MM_PER_INCH * dpi as f64).round() as u32
+}
+
+/// Size of the page in pixels for a wall of `width` and `height`, wide
+/// walls are put on the paper in landscape orientation, and the margin
+fn page(width: u32, height: u32, paper: Paper, dpi: u32) -> (u32, u32, u32) {
+    let (short, long) = (pixels(paper.width_mm, dpi), pixels(paper.height_mm, dpi));
+    let (page_width, page_height) = if width >= height { (long, short) } else { (short, long) };
+    (page_width, page_height, pixels(MARGIN_MM, dpi))
+}
+
+/// Factor by which a wall of `width` and `height` pixels is drawn larger to
+/// fill the paper at `dpi` within its margins, see `Grid::scaled`
+pub fn scale(width: u32, height: u32, paper: Paper, dpi: u32) -> f64 {
+    let (page_width, page_height, margin) = page(width, height, paper, dpi);
+    let available_width = page_width.saturating_sub(2 * margin).max(1);
+    let available_height = page_height.saturating_sub(2 * margin).max(1);
+    (available_width as f64 / width.max(1) as f64).min(available_height as f64 / height.max(1) as f64)
+}
+
+/// Centers the image, drawn at the `scale` for the paper, on a page of the
+/// background color. The sizes of the wall are rounded to whole pixels when
+/// scaling, so it may come out a little larger than the space within the
+/// margins, and is only shrunk to fit then.
+pub fn place_on_paper(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, paper: Paper, dpi: u32, background: Rgba<u8>) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (page_width, page_height, margin) = page(img.width(), img.height(), paper, dpi);
+    let available_width = page_width.saturating_sub(2 * margin).max(1);
+    let available_height = page_height.saturating_sub(2 * margin).max(1);
+
+    let mut page = ImageBuffer::from_pixel(page_width, page_height, background);
+    let fits = img.width() <= available_width && img.height() <= available_height;
+    let shrunk;
+    let img = if fits {
+        img
+    } else {
+        let scale = (available_width as f64 / img.width() as f64).min(available_height as f64 / img.height() as f64);
+        let width = ((img.width() as f64 * scale).floor() as u32).max(1);
+        let height = ((img.height() as f64 * scale).floor() as u32).max(1);
+        shrunk = imageops::resize(img, width, height, FilterType::Triangle);
+        &shrunk
+    };
+    imageops::replace(&mut page, img, ((page_width - img.width()) / 2) as i64, ((page_height - img.height()) / 2) as i64);
+    page
+}
@@ -0,0 +1,63 @@
+use std::io::Write;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{ImageBuffer, Rgba};
+
+use crate::error::{Error, Result};
+use crate::output::{encode_png, encode_sixel};
+
+/// Terminal graphics protocols for `--preview`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Kitty,
+    Iterm,
+    Sixel,
+}
+
+impl Protocol {
+    pub fn parse(name: &str) -> Result<Protocol> {
+        match name {
+            "kitty" => Ok(Protocol::Kitty),
+            "iterm" => Ok(Protocol::Iterm),
+            "sixel" => Ok(Protocol::Sixel),
+            _ => Err(Error::Usage(format!("Unknown preview protocol '{}', expected kitty, iterm or sixel", name))),
+        }
+    }
+}
+
+/// Shows the image inline in the terminal
+pub fn show(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, protocol: Protocol) -> Result<()> {
+    let escape = match protocol {
+        Protocol::Kitty => kitty(img)?,
+        Protocol::Iterm => iterm(img)?,
+        Protocol::Sixel => encode_sixel(img),
+    };
+
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(escape.as_bytes())
+        .and_then(|_| stdout.write_all(b"\n"))
+        .and_then(|_| stdout.flush())
+        .map_err(|err| Error::Output(format!("Failed to write the preview to the terminal: {}", err)))
+}
+
+/// Kitty graphics protocol, transmitting the PNG in chunks of at most 4096
+/// bytes of base64
+fn kitty(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<String> {
+    let data = STANDARD.encode(encode_png(img, None)?);
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+
+    let mut escape = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        let control = if i == 0 { format!("f=100,a=T,m={}", more) } else { format!("m={}", more) };
+        escape.push_str(&format!("\x1b_G{};{}\x1b\\", control, String::from_utf8_lossy(chunk)));
+    }
+    Ok(escape)
+}
+
+/// Inline images protocol of iTerm2, also supported by WezTerm
+fn iterm(img: &ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<String> {
+    let png = encode_png(img, None)?;
+    Ok(format!("\x1b]1337;File=inline=1;size={};preserveAspectRatio=1:{}\x07", png.len(), STANDARD.encode(&png)))
+}
@@ -0,0 +1,89 @@
+use chrono::{Datelike, Timelike, Weekday};
+use image::{ImageBuffer, Rgba};
+use imageproc::drawing::draw_filled_circle_mut;
+
+use crate::collect::Activity;
+use crate::fonts::FontChain;
+use crate::render::{draw_sharp_text, Grid};
+use crate::theme::Theme;
+
+const WEEKDAYS: [Weekday; 7] = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+
+/// Commits per weekday, Monday first, and hour of the day, in the local
+/// time of the committer
+pub fn punchcard(activity: &Activity) -> [[u32; 24]; 7] {
+    let mut counts = [[0; 24]; 7];
+    for timestamp in &activity.timestamps {
+        counts[timestamp.weekday().num_days_from_monday() as usize][timestamp.hour() as usize] += 1;
+    }
+    counts
+}
+
+/// Renders the punchcard shown with `--punchcard`: a grid of weekdays and
+/// hours with circles sized by the number of commits, like GitHub's
+/// punchcard graph
+pub fn generate_punchcard(activity: &Activity, grid: &Grid, theme: &Theme, font: &FontChain) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = grid.label_unit;
+    let cell_size: u32 = label_unit * 3;
+    let label_width: u32 = label_unit * 5;
+    let title_height: u32 = label_unit * 3;
+    let hour_label_height: u32 = label_unit * 2;
+
+    let counts = punchcard(activity);
+    let max = counts.iter().flatten().copied().max().unwrap_or(0).max(1);
+
+    let width = label_width + 24 * cell_size + label_unit * 2;
+    let height = title_height + hour_label_height + 7 * cell_size + label_unit;
+    let mut img = ImageBuffer::from_pixel(width, height, theme.background);
+
+    let title = format!("{} commits by hour of the day", activity.timestamps.len());
+    let margin = grid.px(5) as i32;
+    draw_sharp_text(&mut img, &title, margin, label_unit as i32, label_unit as f32 * 1.4, theme.text_primary, font);
+
+    let grid_y = title_height + hour_label_height;
+    for hour in (0..24).step_by(3) {
+        draw_sharp_text(
+            &mut img,
+            &format!("{:02}", hour),
+            (label_width + hour * cell_size + cell_size / 2) as i32 - margin,
+            title_height as i32,
+            label_unit as f32 * 0.9,
+            theme.text_secondary,
+            font
+        );
+    }
+
+    for (row, weekday) in WEEKDAYS.iter().enumerate() {
+        let row_y = grid_y + row as u32 * cell_size;
+        draw_sharp_text(
+            &mut img,
+            &weekday.to_string(),
+            margin,
+            (row_y + cell_size / 2) as i32 - label_unit as i32 / 2,
+            label_unit as f32 * 1.1,
+            theme.text_primary,
+            font
+        );
+
+        for hour in 0..24 {
+            let center = (
+                (label_width + hour * cell_size + cell_size / 2) as i32,
+                (row_y + cell_size / 2) as i32,
+            );
+            let count = counts[row][hour as usize];
+            if count == 0 {
+                draw_filled_circle_mut(&mut img, center, grid.px(1) as i32, theme.commit_colors[0]);
+                continue;
+            }
+
+            // Scale the area of the circle with the number of commits
+            let min_radius = grid.px(2) as f64;
+            let max_radius = (cell_size / 2) as f64 - min_radius;
+            let radius = (max_radius * (count as f64 / max as f64).sqrt()).max(min_radius);
+            let color = theme.commit_colors[(1 + count * 4 / max).min(5) as usize];
+            draw_filled_circle_mut(&mut img, center, radius.round() as i32, color);
+        }
+    }
+
+    img
+}
@@ -0,0 +1,69 @@
+use image::{imageops, ImageBuffer, Rgba};
+use qrcode::{Color, QrCode};
+
+use crate::error::{Error, Result};
+use crate::fonts::FontChain;
+use crate::render::{draw_block, draw_sharp_text, Grid};
+use crate::theme::Theme;
+
+/// Size of one QR code module in pixels, for the default label unit, see
+/// `Grid::px`
+pub const MODULE_SIZE: u32 = 3;
+/// Light border around the code that scanners need, in modules
+pub const QUIET_ZONE: u32 = 2;
+
+/// Checks that a QR code can be made for `url`, which fails when it is too
+/// long
+pub fn validate(url: &str) -> Result<()> {
+    QrCode::new(url.as_bytes())
+        .map(|_| ())
+        .map_err(|err| Error::Usage(format!("Failed to create a QR code for {}: {}", url, err)))
+}
+
+/// Dark modules of the QR code for `url`, row by row, and the number of
+/// modules per row. The URL must have passed `validate`.
+pub fn qr_modules(url: &str) -> (Vec<bool>, u32) {
+    let code = QrCode::new(url.as_bytes()).expect("the URL was validated");
+    let width = code.width() as u32;
+    let modules = code.to_colors().into_iter().map(|color| color == Color::Dark).collect();
+    (modules, width)
+}
+
+/// Size of the rendered QR code including its quiet zone, with modules of
+/// `module_size`
+pub fn qr_size(width: u32, module_size: u32) -> u32 {
+    (width + QUIET_ZONE * 2) * module_size
+}
+
+/// Puts a footer with a QR code for `url` and the URL itself below the
+/// image. The code is always black on white, as not every scanner handles
+/// inverted codes of dark themes.
+pub fn add_qr_footer(img: &ImageBuffer<Rgba<u8>, Vec<u8>>, url: &str, grid: &Grid, theme: &Theme, font: &FontChain) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = grid.label_unit;
+    let (modules, width) = qr_modules(url);
+    let module_size = grid.px(MODULE_SIZE);
+    let size = qr_size(width, module_size);
+
+    let mut with_footer = ImageBuffer::from_pixel(img.width(), img.height() + size + label_unit, theme.background);
+    imageops::replace(&mut with_footer, img, 0, 0);
+
+    let qr_x = grid.px(5);
+    let qr_y = img.height();
+    draw_block(&mut with_footer, qr_x, qr_y, size, Rgba([255, 255, 255, 255]));
+    for (i, _) in modules.iter().enumerate().filter(|(_, &dark)| dark) {
+        let x = qr_x + (QUIET_ZONE + i as u32 % width) * module_size;
+        let y = qr_y + (QUIET_ZONE + i as u32 / width) * module_size;
+        draw_block(&mut with_footer, x, y, module_size, Rgba([0, 0, 0, 255]));
+    }
+
+    draw_sharp_text(
+        &mut with_footer,
+        url,
+        (qr_x + size + label_unit) as i32,
+        (qr_y + size / 2) as i32 - label_unit as i32 / 2,
+        label_unit as f32 * 1.1,
+        theme.text_secondary,
+        font
+    );
+    with_footer
+}
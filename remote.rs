@@ -0,0 +1,80 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+
+use crate::collect::{git_process, repo_name};
+use crate::error::{Error, Result};
+use crate::log;
+
+/// A repository cloned into a temporary directory, removed again when the
+/// clone is dropped.
+pub struct TempClone {
+    /// Temporary directory holding the clone
+    root: PathBuf,
+    path: PathBuf,
+}
+
+impl TempClone {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempClone {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.root) {
+            if err.kind() == std::io::ErrorKind::NotFound {
+                return;
+            }
+            eprintln!("Failed to remove temporary clone {}: {}", self.root.display(), err);
+        }
+    }
+}
+
+/// Returns true for arguments that look like a remote repository rather
+/// than a local path: `https://…`, `ssh://…` or scp-like `git@host:owner/repo`.
+pub fn is_remote_url(arg: &str) -> bool {
+    const SCHEMES: [&str; 6] = ["https://", "http://", "ssh://", "git://", "git+ssh://", "file://"];
+    if SCHEMES.iter().any(|scheme| arg.starts_with(scheme)) {
+        return true;
+    }
+
+    // scp-like syntax needs a host before the first colon and no slash in it
+    match arg.split_once(':') {
+        Some((host, path)) => host.contains('@') && !host.contains('/') && !path.is_empty(),
+        None => false,
+    }
+}
+
+/// Returns true for the paths of clones made by `clone_remote`
+pub fn is_temporary_clone(path: &str) -> bool {
+    Path::new(path).strip_prefix(env::temp_dir())
+        .ok()
+        .and_then(|rest| rest.iter().next())
+        .is_some_and(|root| root.to_string_lossy().starts_with("commits-tilewall-"))
+}
+
+/// Clones a remote repository into a temporary directory. Only the commit
+/// and tree history is fetched up front, file contents are fetched on
+/// demand when `--numstat` needs them. The clone is named after the
+/// repository, so that labels show the same name as for local clones.
+pub fn clone_remote(url: &str, index: usize) -> Result<TempClone> {
+    let root = env::temp_dir().join(format!("commits-tilewall-{}-{}", process::id(), index));
+    let path = root.join(format!("{}.git", repo_name(url.trim_end_matches('/'))));
+    log::info!("Cloning {} into {}", url, path.display());
+
+    let clone = TempClone { root, path };
+    let mut command = Command::new("git");
+    command
+        .args(["clone", "--quiet", "--bare", "--filter=blob:none", url])
+        .arg(clone.path());
+    log::command(&command);
+    let output = git_process(&mut command)
+        .map_err(|err| Error::Git(format!("Failed to run git: {}", err)))?;
+    if !output.status.success() {
+        return Err(Error::Git(format!("Failed to clone {}: {}", url, String::from_utf8_lossy(&output.stderr).trim())));
+    }
+
+    Ok(clone)
+}
@@ -0,0 +1,1274 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use ab_glyph::PxScale;
+use chrono::{Datelike, NaiveDate, Timelike, Weekday};
+use image::{imageops, ImageBuffer, Rgba};
+use imageproc::drawing::{draw_antialiased_line_segment_mut, draw_filled_circle_mut};
+use imageproc::pixelops::interpolate;
+
+use crate::cadence::Cadence;
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::filters::CommitFilter;
+use crate::fonts::FontChain;
+use crate::holidays::{is_weekend, Holidays};
+use crate::languages::Language;
+use crate::locale::Locale;
+use crate::panels::{Panel, WeekdayChart};
+use crate::stats::{ColorScale, Levels, Metric, Normalize, YearStats, LEVELS};
+use crate::theme::Theme;
+use crate::wellness;
+
+/// Repositories listed by name in the per-year breakdown
+const MAX_BREAKDOWN_REPOS: usize = 4;
+
+/// Optional parts of the rendered wall
+#[derive(Debug, Default)]
+pub struct RenderOptions {
+    /// Annotate intense periods, see `wellness::findings`
+    pub wellness: bool,
+    /// Mark the planned days of a contribution cadence
+    pub cadence: Option<Cadence>,
+    /// Restricts the counted commits, see `--only`
+    pub filter: CommitFilter,
+    /// Leave out the color legend and narrow the summary to fit
+    pub no_legend: bool,
+    /// Leave out the whole summary, rendering only the year grids
+    pub no_stats: bool,
+    /// URL for the QR code in the footer, see `qr::add_qr_footer`
+    pub qr: Option<String>,
+    pub month_labels: MonthLabels,
+    /// Put the month labels below the grids instead of above them
+    pub month_labels_below: bool,
+    /// Print the commits of every month under its label, `--month-totals`
+    pub month_totals: bool,
+    /// Language of the month names, numbers and summary, see `--locale`
+    pub locale: Locale,
+    /// Wrap the months of a year into several rows, 12 when not given
+    pub months_per_row: Option<u32>,
+    /// How the days of a year are arranged, see `--layout`
+    pub layout: Layout,
+    /// Leave out the weekday labels left of the weeks layout,
+    /// `--no-weekday-labels`
+    pub no_weekday_labels: bool,
+    /// Custom panels drawn to the right of the summary, see
+    /// `register_panel`
+    pub(crate) panels: Vec<Box<dyn Panel>>,
+    /// How days without commits are drawn, see `--empty-days`
+    pub empty_days: TileStyle,
+    /// Tint the tiles of Saturdays and Sundays, `--highlight-weekends`
+    pub highlight_weekends: bool,
+    /// What the color of a day stands for, see `--metric`
+    pub metric: Metric,
+    /// Whether the levels are the same for all years, see `--normalize`
+    pub normalize: Normalize,
+    /// How the days are mapped to levels, see `--color-scale`
+    pub color_scale: Option<ColorScale>,
+    /// Whether a tile stands for a whole day or is split into halves, see
+    /// `--granularity`
+    pub granularity: Granularity,
+    /// Releases marked on the days they were published, see `--crate`
+    pub releases: Vec<Release>,
+    /// Milestones marked on their days and listed in the notes, see
+    /// `--annotate`
+    pub annotations: Vec<Annotation>,
+    /// Sizes of the tiles and the space between them, see `--block-size`
+    pub grid: Grid,
+    /// Plot the weekly commits below the grids of every year, `--sparkline`
+    pub sparkline: bool,
+}
+
+/// Sizes of the year grids in pixels, given with `--block-size`, `--gap`,
+/// `--month-gap` and `--year-gap`. The labels and the summary keep their
+/// size, see `label_unit`, so that they stay readable.
+#[derive(Debug, Clone, Copy)]
+pub struct Grid {
+    /// Width and height of a day tile
+    pub block_size: u32,
+    /// Space between the tiles of a month
+    pub gap: u32,
+    /// Space between two months
+    pub month_gap: u32,
+    /// Space between the rows of two years, with the separator line in its
+    /// middle
+    pub year_gap: u32,
+    /// Radius of the rounded corners of the tiles, 0 for square tiles
+    pub corner_radius: u32,
+    /// Height of a line of the labels and the summary, which the rest of
+    /// their layout is measured in. It doesn't follow `block_size`.
+    pub label_unit: u32,
+}
+
+/// Label unit of the walls on screen, see `Grid::label_unit`
+const LABEL_UNIT: u32 = 10;
+
+impl Grid {
+    /// The corner radius, at most half of the tile
+    pub fn tile_radius(&self) -> u32 {
+        self.corner_radius.min(self.block_size / 2)
+    }
+
+    /// A size of the labels given for the default label unit, like a
+    /// margin, scaled to `label_unit`
+    pub fn px(&self, pixels: u32) -> u32 {
+        (pixels * self.label_unit + LABEL_UNIT / 2) / LABEL_UNIT
+    }
+
+    /// Every size multiplied by `factor`, for walls rendered at a higher
+    /// resolution like with `--print`
+    pub fn scaled(&self, factor: f64) -> Grid {
+        let scale = |pixels: u32| (pixels as f64 * factor).round() as u32;
+        Grid {
+            block_size: scale(self.block_size).max(1),
+            gap: scale(self.gap),
+            month_gap: scale(self.month_gap),
+            year_gap: scale(self.year_gap),
+            corner_radius: scale(self.corner_radius),
+            label_unit: scale(self.label_unit).max(1),
+        }
+    }
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid { block_size: 10, gap: 2, month_gap: 6, year_gap: 20, corner_radius: 0, label_unit: LABEL_UNIT }
+    }
+}
+
+/// A published version of a project, marked in the corner of its day
+#[derive(Debug, Clone)]
+pub struct Release {
+    pub project: String,
+    pub version: String,
+    pub date: NaiveDate,
+}
+
+/// A milestone given with `--annotate`, marked in the lower left corner of
+/// its day
+#[derive(Debug, Clone)]
+pub struct Annotation {
+    pub date: NaiveDate,
+    pub text: String,
+}
+
+impl Annotation {
+    /// Parses `YYYY-MM-DD:text`, e.g. `2024-02-14:v2.0 release`
+    pub fn parse(spec: &str) -> Result<Annotation> {
+        let invalid = || Error::Usage(format!("Invalid annotation '{}', expected YYYY-MM-DD:text", spec));
+        let (date, text) = spec.split_once(':').ok_or_else(invalid)?;
+        let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").map_err(|_| invalid())?;
+        let text = text.trim().trim_matches('"').trim();
+        if text.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Annotation { date, text: text.to_string() })
+    }
+}
+
+/// How a day tile is drawn, given for days without commits with
+/// `--empty-days`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TileStyle {
+    /// A filled square
+    #[default]
+    Solid,
+    /// Nothing, leaving the background visible
+    None,
+    /// Only the border of the square
+    Outline,
+    /// Every other pixel of the border
+    Dotted,
+}
+
+impl TileStyle {
+    pub fn parse(style: &str) -> Result<TileStyle> {
+        match style {
+            "solid" => Ok(TileStyle::Solid),
+            "none" => Ok(TileStyle::None),
+            "outline" => Ok(TileStyle::Outline),
+            "dotted" => Ok(TileStyle::Dotted),
+            _ => Err(Error::Usage(format!("Unknown empty day style '{}', expected solid, none, outline or dotted", style))),
+        }
+    }
+
+    /// Style of the empty days before an imported history, which stand out
+    /// from the other empty days
+    pub fn for_gaps(self) -> TileStyle {
+        match self {
+            TileStyle::Outline => TileStyle::Dotted,
+            _ => TileStyle::Outline,
+        }
+    }
+}
+
+/// What a tile stands for, given with `--granularity`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Granularity {
+    #[default]
+    Day,
+    /// The upper half of a tile is colored by the commits before noon, the
+    /// lower half by those after
+    HalfDay,
+}
+
+impl Granularity {
+    pub fn parse(granularity: &str) -> Result<Granularity> {
+        match granularity {
+            "day" => Ok(Granularity::Day),
+            "halfday" => Ok(Granularity::HalfDay),
+            _ => Err(Error::Usage(format!("Unknown granularity '{}', expected day or halfday", granularity))),
+        }
+    }
+}
+
+/// Commits before and after noon of every day, unweighted
+pub fn half_day_commits(activity: &Activity) -> HashMap<NaiveDate, [i32; 2]> {
+    let mut halves: HashMap<NaiveDate, [i32; 2]> = HashMap::new();
+    for timestamp in &activity.timestamps {
+        halves.entry(timestamp.date()).or_default()[(timestamp.hour() >= 12) as usize] += 1;
+    }
+    halves
+}
+
+/// Style of the month labels, given with `--month-labels`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MonthLabels {
+    /// Jan, Feb, ...
+    #[default]
+    Abbr,
+    /// January, February, ...
+    Full,
+    /// 01, 02, ...
+    Numeric,
+    None,
+}
+
+impl RenderOptions {
+    /// Adds a panel to the right of the summary of every year, after the
+    /// panels added before it
+    pub fn register_panel(&mut self, panel: impl Panel + 'static) {
+        self.panels.push(Box::new(panel));
+    }
+
+    /// Applies a layout preset for a common aspect ratio, given with
+    /// `--preset`: `readme` for a wall with a narrow summary, `banner` for
+    /// a wide strip of only the grids, `square` for months wrapped into
+    /// rows of four
+    pub fn apply_preset(&mut self, preset: &str) -> Result<()> {
+        match preset {
+            "readme" => {
+                self.no_legend = true;
+            }
+            "banner" => {
+                self.no_stats = true;
+                self.month_labels = MonthLabels::None;
+            }
+            "square" => {
+                self.no_legend = true;
+                self.months_per_row = Some(4);
+            }
+            _ => return Err(Error::Usage(format!("Unknown preset '{}', expected readme, banner or square", preset))),
+        }
+        Ok(())
+    }
+}
+
+impl MonthLabels {
+    pub fn parse(style: &str) -> Result<MonthLabels> {
+        match style {
+            "abbr" => Ok(MonthLabels::Abbr),
+            "full" => Ok(MonthLabels::Full),
+            "numeric" => Ok(MonthLabels::Numeric),
+            "none" => Ok(MonthLabels::None),
+            _ => Err(Error::Usage(format!("Unknown month label style '{}', expected abbr, full, numeric or none", style))),
+        }
+    }
+
+    pub fn label(&self, month: u32, locale: &Locale) -> Option<String> {
+        match self {
+            MonthLabels::Abbr => Some(locale.month_abbr(month)),
+            MonthLabels::Full => Some(locale.month_name(month)),
+            MonthLabels::Numeric => Some(format!("{:02}", month)),
+            MonthLabels::None => None,
+        }
+    }
+}
+
+/// How the days of a year are arranged, given with `--layout`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Layout {
+    /// A grid of four columns for every month
+    #[default]
+    Months,
+    /// A column for every week and a row for every weekday, Sunday on top,
+    /// like the contribution graph of GitHub
+    Weeks,
+}
+
+impl Layout {
+    pub fn parse(layout: &str) -> Result<Layout> {
+        match layout {
+            "months" => Ok(Layout::Months),
+            "weeks" => Ok(Layout::Weeks),
+            _ => Err(Error::Usage(format!("Unknown layout '{}', expected months or weeks", layout))),
+        }
+    }
+}
+
+/// Columns of a month in the months layout, and rows enough for 31 days
+const MONTH_COLUMNS: u32 = 4;
+const MONTH_ROWS: u32 = 8;
+
+/// Columns of the weeks layout, as many weeks as a year can touch
+const WEEK_COLUMNS: u32 = 54;
+
+/// Weekdays labeled left of the weeks layout, like GitHub does
+const LABELED_WEEKDAYS: [Weekday; 3] = [Weekday::Mon, Weekday::Wed, Weekday::Fri];
+
+/// Positions of the days and the month labels within a year row, relative
+/// to the right of the year label and the top of the row, see `Layout`
+pub struct GridLayout {
+    layout: Layout,
+    grid: Grid,
+    months_per_row: u32,
+    /// Size of a month in the months layout, with the space after it and
+    /// its label
+    month_width: u32,
+    month_height: u32,
+    pub labels: MonthLabelLayout,
+    /// Width of the weekday labels left of the weeks, 0 without them
+    gutter_width: u32,
+    /// Width of the grids with the space after them
+    pub width: u32,
+    /// Height of the grids with their labels, without the sparkline
+    pub height: u32,
+}
+
+impl GridLayout {
+    pub fn new(options: &RenderOptions) -> GridLayout {
+        let grid = options.grid;
+        let step = grid.block_size + grid.gap;
+        match options.layout {
+            Layout::Months => {
+                let labels = month_label_layout(options, MONTH_ROWS * step);
+                let months_per_row = options.months_per_row.unwrap_or(12);
+                let month_width = MONTH_COLUMNS * step + grid.month_gap;
+                let month_height = MONTH_ROWS * step + labels.height;
+                let rows = 12u32.div_ceil(months_per_row);
+                let height = rows * (month_height + grid.label_unit) - grid.label_unit;
+                GridLayout { layout: options.layout, grid, months_per_row, month_width, month_height, labels, gutter_width: 0, width: months_per_row * month_width, height }
+            }
+            Layout::Weeks => {
+                let labels = month_label_layout(options, 7 * step);
+                let gutter_width = if options.no_weekday_labels { 0 } else { grid.label_unit * 3 };
+                let height = 7 * step + labels.height;
+                let width = gutter_width + WEEK_COLUMNS * step + grid.month_gap;
+                GridLayout { layout: options.layout, grid, months_per_row: 12, month_width: 0, month_height: 0, labels, gutter_width, width, height }
+            }
+        }
+    }
+
+    /// Top left corner of the tile of a day
+    pub fn day(&self, date: NaiveDate) -> (u32, u32) {
+        let step = self.grid.block_size + self.grid.gap;
+        match self.layout {
+            Layout::Months => {
+                let (month_x, month_y) = self.month_offset(date.month());
+                let day = date.day0();
+                (month_x + day % MONTH_COLUMNS * step, month_y + self.labels.grid_y + day / MONTH_COLUMNS * step)
+            }
+            Layout::Weeks => (
+                self.gutter_width + week_column(date) * step,
+                self.labels.grid_y + date.weekday().num_days_from_sunday() * step,
+            ),
+        }
+    }
+
+    /// Top left corner of the labels of a month, add `labels.label_y` or
+    /// `labels.totals_y`, and the width they may take
+    pub fn month(&self, year: i32, month: u32) -> (u32, u32, u32) {
+        let step = self.grid.block_size + self.grid.gap;
+        match self.layout {
+            Layout::Months => {
+                let (month_x, month_y) = self.month_offset(month);
+                (month_x, month_y, (MONTH_COLUMNS * step + self.grid.month_gap).saturating_sub(self.grid.gap))
+            }
+            Layout::Weeks => {
+                // A month is labeled over the week of its first day, up to
+                // the week of the next month's
+                let column = NaiveDate::from_ymd_opt(year, month, 1).map_or(0, week_column);
+                let next = NaiveDate::from_ymd_opt(year, month + 1, 1).map_or(WEEK_COLUMNS, week_column);
+                (self.gutter_width + column * step, 0, ((next - column) * step).saturating_sub(self.grid.gap))
+            }
+        }
+    }
+
+    /// The weekdays labeled left of the weeks with the top of their labels
+    pub fn weekday_labels(&self) -> Vec<(Weekday, u32)> {
+        if self.gutter_width == 0 {
+            return Vec::new();
+        }
+        let step = self.grid.block_size + self.grid.gap;
+        LABELED_WEEKDAYS.iter()
+            .map(|&weekday| {
+                let row_y = self.labels.grid_y + weekday.num_days_from_sunday() * step;
+                (weekday, (row_y + self.grid.block_size / 2).saturating_sub(self.grid.label_unit * 2 / 5))
+            })
+            .collect()
+    }
+
+    /// Left edge and width of the sparkline below the grids
+    pub fn sparkline(&self) -> (u32, u32) {
+        (self.gutter_width, self.width - self.gutter_width - self.grid.month_gap)
+    }
+
+    /// Position of the grid of a month in the months layout, when the months
+    /// are wrapped with `--months-per-row`
+    fn month_offset(&self, month: u32) -> (u32, u32) {
+        let per_row = self.months_per_row;
+        ((month - 1) % per_row * self.month_width, (month - 1) / per_row * (self.month_height + self.grid.label_unit))
+    }
+}
+
+/// Column of the week of a day in the weeks layout, counted from the week of
+/// January 1
+fn week_column(date: NaiveDate) -> u32 {
+    let january_1 = date.with_ordinal0(0).unwrap_or(date);
+    (date.ordinal0() + january_1.weekday().num_days_from_sunday()) / 7
+}
+
+/// Vertical positions within a year row, depending on where the month
+/// labels go
+pub struct MonthLabelLayout {
+    /// Height reserved for the labels
+    pub height: u32,
+    pub label_y: u32,
+    /// Position of the commits of the month, see `--month-totals`
+    pub totals_y: u32,
+    pub grid_y: u32,
+}
+
+/// Height of the sparkline below the grids of a year, see `--sparkline`
+const SPARKLINE_HEIGHT: u32 = 16;
+
+/// Height added below the grids of a year for the sparkline, with the
+/// space above it
+pub fn sparkline_height(options: &RenderOptions) -> u32 {
+    let label_unit = options.grid.label_unit;
+    if options.sparkline { label_unit + options.grid.px(SPARKLINE_HEIGHT) } else { 0 }
+}
+
+/// Points of the sparkline of the weekly commits of a year in a box of
+/// `width` at `sparkline_height`, relative to its top left corner. The weeks
+/// are counted from January 1, so the last one is one or two days long.
+pub fn sparkline_points(activity: &Activity, year: i32, width: u32, grid: &Grid) -> Vec<(f32, f32)> {
+    let mut weeks = [0; 53];
+    for (date, &count) in activity.commits.iter().filter(|(date, _)| date.year() == year) {
+        weeks[date.ordinal0() as usize / 7] += count;
+    }
+    let max = weeks.iter().copied().max().unwrap_or(0).max(1);
+    let step = width.saturating_sub(1) as f32 / (weeks.len() - 1) as f32;
+    // The line is kept half a pixel inside the box at the top and bottom
+    let height = grid.px(SPARKLINE_HEIGHT) as f32 - 1.0;
+    weeks.iter()
+        .enumerate()
+        .map(|(week, &count)| (week as f32 * step, 0.5 + height * (1.0 - count as f32 / max as f32)))
+        .collect()
+}
+
+pub fn month_label_layout(options: &RenderOptions, grid_height: u32) -> MonthLabelLayout {
+    let label_unit = options.grid.label_unit;
+    let labels_height = if options.month_labels == MonthLabels::None { 0 } else { label_unit * 2 };
+    let height = labels_height + if options.month_totals { label_unit } else { 0 };
+    if options.month_labels_below {
+        let label_y = grid_height + label_unit / 2;
+        MonthLabelLayout { height, label_y, totals_y: label_y + labels_height, grid_y: 0 }
+    } else {
+        MonthLabelLayout { height, label_y: 0, totals_y: labels_height, grid_y: height }
+    }
+}
+
+/// The numbers of a year at the top of its summary
+pub fn summary_lines(activity: &Activity, year_stats: &YearStats, locale: &Locale) -> Vec<String> {
+    let mut lines = vec![locale.commits_total(year_stats.commits)];
+    if activity.has_diff_stats {
+        lines.push(locale.files_changed(year_stats.diff.files));
+        if year_stats.diff.binary_files > 0 {
+            lines.push(locale.binary_files(year_stats.diff.binary_files));
+        }
+        lines.push(locale.insertions(year_stats.diff.insertions));
+        lines.push(locale.deletions(year_stats.diff.deletions));
+    }
+    lines
+}
+
+/// Commits per year and month, weighted like the days
+pub fn month_totals(activity: &Activity) -> HashMap<(i32, u32), i32> {
+    let mut totals = HashMap::new();
+    for (date, &count) in &activity.commits {
+        *totals.entry((date.year(), date.month())).or_insert(0) += count;
+    }
+    totals
+}
+
+/// Font size of a month label, shrunk when it is wider than its month
+pub fn month_label_size(label: &str, available_width: u32, grid: &Grid, font: &FontChain) -> f32 {
+    let size = grid.label_unit as f32 * 1.2;
+    let width = font.text_width(label, PxScale::from(size));
+    if width > available_width as f32 {
+        size * available_width as f32 / width
+    } else {
+        size
+    }
+}
+
+/// Positions in the summary panel on the right, relative to its left edge
+pub struct SummaryLayout {
+    pub width: u32,
+    pub legend_x: Option<u32>,
+    pub chart_x: u32,
+}
+
+/// Without the legend, the summary panel is only as wide as its widest
+/// note or the weekday chart
+pub fn summary_layout(year_notes: &[Vec<String>], font: &FontChain, options: &RenderOptions) -> SummaryLayout {
+    let label_unit = options.grid.label_unit;
+    if options.no_stats {
+        return SummaryLayout { width: 0, legend_x: None, chart_x: 0 };
+    }
+    let scale = PxScale::from(label_unit as f32 * 0.8);
+    // The columns right of the numbers move over for longer translations
+    let widening = options.locale.widening(|text| font.text_width(text, scale));
+    if !options.no_legend {
+        return SummaryLayout {
+            width: label_unit * 45 + widening,
+            legend_x: Some(label_unit * 8 + widening),
+            chart_x: label_unit * 28 + widening,
+        };
+    }
+
+    let chart_x = label_unit * 8 + widening;
+    let chart_width = WeekdayChart(options.locale).measure(&YearStats::default(), &options.grid);
+    let notes_width = year_notes.iter()
+        .flatten()
+        .map(|note| font.text_width(note, scale).ceil() as u32)
+        .max()
+        .unwrap_or(0);
+    SummaryLayout {
+        width: (chart_x + chart_width).max(notes_width) + label_unit,
+        legend_x: None,
+        chart_x,
+    }
+}
+
+/// The wall of a single repository, shown with `--per-repo`
+pub struct RepoWall {
+    pub name: String,
+    pub language: Option<Language>,
+    pub activity: Activity,
+    pub active_years: Vec<YearStats>,
+}
+
+pub fn draw_sharp_text(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, text: &str, x: i32, y: i32, size: f32, color: Rgba<u8>, font: &FontChain) {
+    let scale = PxScale {
+        x: size,
+        y: size,
+    };
+    
+    font.draw_text(
+        img,
+        text,
+        x,
+        y,
+        scale,
+        color
+    );
+}
+
+/// Share of the pixel at `px`, `py` covered by a `width` by `height`
+/// rectangle with corners rounded by `radius`. Pixels in the corners are
+/// sampled 4 by 4 times, so that the rounding is smooth.
+fn rounded_rect_coverage(px: u32, py: u32, width: u32, height: u32, radius: u32) -> f32 {
+    const SAMPLES: u32 = 4;
+    if px >= width || py >= height {
+        return 0.0;
+    }
+    let in_corner = (px < radius || px + radius >= width) && (py < radius || py + radius >= height);
+    if !in_corner {
+        return 1.0;
+    }
+    let radius = radius as f32;
+    let mut inside = 0;
+    for sy in 0..SAMPLES {
+        for sx in 0..SAMPLES {
+            let x = px as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+            let y = py as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+            // Distance to the center of the nearest corner
+            let dx = x - x.clamp(radius, width as f32 - radius);
+            let dy = y - y.clamp(radius, height as f32 - radius);
+            if dx * dx + dy * dy <= radius * radius {
+                inside += 1;
+            }
+        }
+    }
+    inside as f32 / (SAMPLES * SAMPLES) as f32
+}
+
+/// Mixes `color` into the pixel by the share it covers
+fn blend_pixel(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, color: Rgba<u8>, coverage: f32) {
+    if coverage <= 0.0 || x >= img.width() || y >= img.height() {
+        return;
+    }
+    if coverage >= 1.0 {
+        img.put_pixel(x, y, color);
+        return;
+    }
+    let pixel = img.get_pixel_mut(x, y);
+    for channel in 0..4 {
+        pixel[channel] = (pixel[channel] as f32 * (1.0 - coverage) + color[channel] as f32 * coverage).round() as u8;
+    }
+}
+
+/// Draws a day tile in the given style, see `TileStyle`, with its corners
+/// rounded by `radius`
+fn draw_tile(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, size: u32, radius: u32, color: Rgba<u8>, style: TileStyle) {
+    for by in 0..size {
+        for bx in 0..size {
+            let fill = rounded_rect_coverage(bx, by, size, size, radius);
+            // The border is what is left of the tile without the tile one
+            // pixel smaller
+            let inner = if bx > 0 && by > 0 { rounded_rect_coverage(bx - 1, by - 1, size - 2, size - 2, radius.saturating_sub(1)) } else { 0.0 };
+            let coverage = match style {
+                TileStyle::Solid => fill,
+                TileStyle::None => 0.0,
+                TileStyle::Outline => fill - inner,
+                TileStyle::Dotted if (bx + by) % 2 == 0 => fill - inner,
+                TileStyle::Dotted => 0.0,
+            };
+            blend_pixel(img, x + bx, y + by, color, coverage);
+        }
+    }
+}
+
+/// Draws a day tile split into an upper and a lower half, leaving out
+/// halves without commits unless the empty days are solid. With rounded
+/// corners, each half is rounded on its own.
+#[allow(clippy::too_many_arguments)]
+fn draw_half_day_tile(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, size: u32, radius: u32, levels: [usize; 2], theme: &Theme, empty_days: TileStyle) {
+    let halves = [(0, size / 2), (size / 2, size - size / 2)];
+    for ((top, height), level) in halves.into_iter().zip(levels) {
+        if level == 0 && empty_days != TileStyle::Solid {
+            continue;
+        }
+        let radius = radius.min(height / 2);
+        for by in 0..height {
+            for bx in 0..size {
+                blend_pixel(img, x + bx, y + top + by, theme.commit_colors[level], rounded_rect_coverage(bx, by, size, height, radius));
+            }
+        }
+    }
+}
+
+/// Mixes a share of `color` into the drawn pixels of a tile, leaving the
+/// background around outlined or missing tiles as it is
+fn tint_tile(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, size: u32, color: Rgba<u8>, background: Rgba<u8>) {
+    const SHARE: f32 = 0.2;
+    for by in 0..size {
+        for bx in 0..size {
+            if x + bx >= img.width() || y + by >= img.height() {
+                continue;
+            }
+            let pixel = img.get_pixel_mut(x + bx, y + by);
+            if *pixel == background {
+                continue;
+            }
+            for channel in 0..3 {
+                pixel[channel] = (pixel[channel] as f32 * (1.0 - SHARE) + color[channel] as f32 * SHARE).round() as u8;
+            }
+        }
+    }
+}
+
+pub fn draw_block(img: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, x: u32, y: u32, size: u32, color: Rgba<u8>) {
+    for by in 0..size {
+        for bx in 0..size {
+            let pixel_x = x + bx;
+            let pixel_y = y + by;
+            
+            if pixel_x < img.width() && pixel_y < img.height() {
+                img.put_pixel(pixel_x, pixel_y, color);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn generate_commit_image(activity: &Activity, active_years: &[YearStats], levels: &Levels, holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = options.grid.label_unit;
+    let grid = options.grid;
+    let space_size = grid.px(2);
+    let tile_radius = grid.tile_radius();
+    let grid_layout = GridLayout::new(options);
+    let year_label_width: u32 = label_unit * 5;
+
+    // If no commits found, return a minimal image
+    if active_years.is_empty() {
+        return ImageBuffer::new(1, 1);
+    }
+
+    // Notes below the color legend, the rows grow to fit the longest list
+    let year_notes = wall_notes(activity, active_years, holidays, options);
+    let summary = summary_layout(&year_notes, font, options);
+    let summary_width = summary.width;
+    let panel_widths: Vec<u32> = options.panels.iter()
+        .filter(|_| !options.no_stats)
+        .map(|panel| active_years.iter().map(|stats| panel.measure(stats, &options.grid)).max().unwrap_or(0))
+        .collect();
+    let panels_width: u32 = panel_widths.iter().map(|panel_width| panel_width + label_unit).sum();
+    let max_notes = year_notes.iter().map(|notes| notes.len()).max().unwrap_or(0) as u32;
+    let notes_start = label_unit + space_size + LEVELS as u32 * (label_unit + space_size);
+    let year_height: u32 = (grid_layout.height + sparkline_height(options))
+        .max(if options.no_stats { 0 } else { notes_start + max_notes * (label_unit + space_size) })
+        // Room for the year label next to small grids
+        .max(label_unit * 2);
+
+    // Calculate image dimensions based on active years only
+    let years_count = active_years.len() as u32;
+    let width = year_label_width + 
+                grid_layout.width + 
+                summary_width + 
+                panels_width +
+                space_size * 4;  // Extra padding
+    let height = (year_height + grid.year_gap) * years_count;
+    
+    let mut img = ImageBuffer::new(width, height);
+
+    // Fill background
+    for pixel in img.pixels_mut() {
+        *pixel = theme.background;
+    }
+
+    let history_start = history_start(activity);
+    let half_days = (options.granularity == Granularity::HalfDay).then(|| half_day_commits(activity));
+    let month_totals = month_totals(activity);
+
+    // Fill the image based on commit counts
+    for (year_index, year_stats) in active_years.iter().enumerate() {
+        let year = year_stats.year;
+        let year_offset = (year_index as u32) * (year_height + grid.year_gap);
+        
+        // Draw year text in dark color
+        let year_text = year.to_string();
+        draw_sharp_text(
+            &mut img,
+            &year_text,
+            grid.px(5) as i32,
+            (year_offset + (year_height / 2)) as i32 - (label_unit as i32 / 2),
+            label_unit as f32 * 1.6,
+            theme.text_primary,
+            font
+        );
+
+        for (weekday, label_y) in grid_layout.weekday_labels() {
+            draw_sharp_text(
+                &mut img,
+                &options.locale.weekday_abbr(weekday),
+                year_label_width as i32,
+                (year_offset + label_y) as i32,
+                label_unit as f32 * 0.8,
+                theme.text_secondary,
+                font
+            );
+        }
+
+        // Process each month
+        for month in 1..=12 {
+            let (month_x, month_y, available_width) = grid_layout.month(year, month);
+            let month_x_offset = year_label_width + month_x;
+
+            // Draw month label in dark color
+            if let Some(label) = options.month_labels.label(month, &options.locale) {
+                draw_sharp_text(
+                    &mut img,
+                    &label,
+                    month_x_offset as i32,
+                    (year_offset + month_y + grid_layout.labels.label_y) as i32,
+                    month_label_size(&label, available_width, &grid, font),
+                    theme.text_secondary,
+                    font
+                );
+            }
+            if let Some(&total) = month_totals.get(&(year, month)).filter(|_| options.month_totals) {
+                draw_sharp_text(
+                    &mut img,
+                    &options.locale.number(total),
+                    month_x_offset as i32,
+                    (year_offset + month_y + grid_layout.labels.totals_y) as i32,
+                    label_unit as f32 * 0.8,
+                    theme.text_secondary,
+                    font
+                );
+            }
+
+            // Draw all days in a grid
+            let days_in_month = match month {
+                1 => 31, // January
+                2 => if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) { 29 } else { 28 }, // February
+                3 => 31, // March
+                4 => 30, // April
+                5 => 31, // May
+                6 => 30, // June
+                7 => 31, // July
+                8 => 31, // August
+                9 => 30, // September
+                10 => 31, // October
+                11 => 30, // November
+                12 => 31, // December
+                _ => 0, // Invalid month
+            };
+
+            for day in 1..=days_in_month {  // Adjusted to use days_in_month
+                // Only draw valid days
+                if let Some((day_x, day_y)) = NaiveDate::from_ymd_opt(year, month, day).map(|date| grid_layout.day(date)) {
+                    let x = year_label_width + day_x;
+                    let y = year_offset + day_y;
+
+                    // Set color based on the commits, or the lines they change
+                    let color_value = if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                        let count = activity.commits.get(&date).copied().unwrap_or(0);
+                        theme.commit_colors[levels.level(date, count, activity.diff_stats.get(&date))]
+                    } else {
+                        theme.commit_colors[0]  // Use no-commit color for invalid dates
+                    };
+
+                    // Empty days of a history that was lost stand out from the
+                    // other empty days
+                    let style = match NaiveDate::from_ymd_opt(year, month, day) {
+                        Some(date) if activity.commits.contains_key(&date) => TileStyle::Solid,
+                        Some(date) if history_start.is_some_and(|start| date < start) => options.empty_days.for_gaps(),
+                        _ => options.empty_days,
+                    };
+                    match (&half_days, NaiveDate::from_ymd_opt(year, month, day)) {
+                        (Some(half_days), Some(date)) if activity.commits.contains_key(&date) => {
+                            let halves = half_days.get(&date).copied().unwrap_or_default();
+                            draw_half_day_tile(&mut img, x, y, grid.block_size, tile_radius, halves.map(|count| levels.level(date, count, None)), theme, options.empty_days);
+                        }
+                        _ => draw_tile(&mut img, x, y, grid.block_size, tile_radius, color_value, style),
+                    }
+                    if options.highlight_weekends && NaiveDate::from_ymd_opt(year, month, day).is_some_and(|date| is_weekend(&date)) {
+                        tint_tile(&mut img, x, y, grid.block_size, theme.text_secondary, theme.background);
+                    }
+                    // Outline public holidays, so commits made on them stand out
+                    if NaiveDate::from_ymd_opt(year, month, day).is_some_and(|date| holidays.contains(&date)) {
+                        draw_tile(&mut img, x, y, grid.block_size, tile_radius, theme.text_primary, TileStyle::Outline);
+                    }
+
+                    // Mark the planned days of the cadence with a small dot
+                    if let (Some(cadence), Some(date)) = (&options.cadence, NaiveDate::from_ymd_opt(year, month, day)) {
+                        if cadence.is_planned(&date) {
+                            let dot_size = (grid.block_size / 5).max(1);
+                            let dot_offset = (grid.block_size - dot_size) / 2;
+                            draw_block(&mut img, x + dot_offset, y + dot_offset, dot_size, theme.text_secondary);
+                        }
+                    }
+
+                    // Mark the days of releases with a triangle in the upper
+                    // right corner
+                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                        if options.releases.iter().any(|release| release.date == date) {
+                            let half = grid.block_size / 2;
+                            for by in 0..half {
+                                for bx in half + by..grid.block_size {
+                                    img.put_pixel(x + bx, y + by, theme.text_primary);
+                                }
+                            }
+                        }
+                    }
+
+                    // Mark the days of annotations with a triangle in the
+                    // lower left corner
+                    if let Some(date) = NaiveDate::from_ymd_opt(year, month, day) {
+                        if options.annotations.iter().any(|annotation| annotation.date == date) {
+                            let half = grid.block_size / 2;
+                            for by in half..grid.block_size {
+                                for bx in 0..=by - half {
+                                    img.put_pixel(x + bx, y + by, theme.text_primary);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        if options.sparkline {
+            let sparkline_y = year_offset + grid_layout.height + label_unit;
+            let (sparkline_x, sparkline_width) = grid_layout.sparkline();
+            let sparkline_x = year_label_width + sparkline_x;
+            let points = sparkline_points(activity, year, sparkline_width, &grid);
+            for segment in points.windows(2) {
+                let point = |(x, y): (f32, f32)| ((sparkline_x as f32 + x).round() as i32, (sparkline_y as f32 + y).round() as i32);
+                draw_antialiased_line_segment_mut(&mut img, point(segment[0]), point(segment[1]), theme.commit_colors[LEVELS], interpolate);
+            }
+        }
+
+        // Draw year separator line in light gray
+        if year_index > 0 {
+            for x in 0..width {
+                let line_y = year_offset - (grid.year_gap / 2);
+                img.put_pixel(x, line_y, theme.separator);  // Light gray line
+            }
+        }
+
+        if options.no_stats {
+            continue;
+        }
+
+        // Draw summary on the right side
+        let summary_x = width - panels_width - summary_width - space_size * 2;
+        let stats_x = summary_x;  // Stats start at the same x position
+
+        // Draw summary text with stats
+        let summary_lines = summary_lines(activity, year_stats, &options.locale);
+
+        for (i, text) in summary_lines.iter().enumerate() {
+            draw_sharp_text(
+                &mut img,
+                text,
+                stats_x as i32,
+                (year_offset + label_unit + i as u32 * (label_unit + space_size)) as i32,
+                label_unit as f32 * 0.8,
+                theme.text_primary,
+                font
+            );
+        }
+
+        // Adjusted position for commit level counts
+        let level_start_y = year_offset + label_unit + space_size;
+
+        for (i, &count) in year_stats.level_days.iter().enumerate() {
+            let Some(legend_x) = summary.legend_x.map(|x| summary_x + x) else { break };
+            if count > 0 && 
+               legend_x + label_unit <= width && 
+               level_start_y + (i as u32 * (label_unit + space_size)) + label_unit <= height {
+                
+                // Draw colored square
+                draw_block(
+                    &mut img,
+                    legend_x,
+                    level_start_y + (i as u32 * (label_unit + space_size)),
+                    label_unit,
+                    theme.commit_colors[i + 1]
+                );
+
+                // Draw count text
+                let level_text = options.locale.level_days(count, options.metric, i, year_stats.level_ranges.map(|ranges| ranges[i]));
+
+                // Draw text only if there's enough space
+                let text_x = legend_x + label_unit + space_size * 2;
+                if text_x + label_unit * 15 <= width {
+                    draw_sharp_text(
+                        &mut img,
+                        &level_text,
+                        text_x as i32,
+                        (level_start_y + (i as u32 * (label_unit + space_size))) as i32,
+                        label_unit as f32 * 0.8,
+                        theme.text_secondary,
+                        font
+                    );
+                }
+            }
+        }
+
+        // Weekday distribution to the right of the legend, and the custom
+        // panels to the right of the summary
+        WeekdayChart(options.locale).render(&mut img, summary_x + summary.chart_x, level_start_y, year_stats, &options.grid, theme, font);
+        let mut panel_x = summary_x + summary_width;
+        for (panel, panel_width) in options.panels.iter().zip(&panel_widths) {
+            panel.render(&mut img, panel_x, level_start_y, year_stats, &options.grid, theme, font);
+            panel_x += panel_width + label_unit;
+        }
+
+        // Draw notes below the legend
+        for (i, text) in year_notes[year_index].iter().enumerate() {
+            draw_sharp_text(
+                &mut img,
+                text,
+                stats_x as i32,
+                (year_offset + notes_start + i as u32 * (label_unit + space_size)) as i32,
+                label_unit as f32 * 0.8,
+                theme.text_secondary,
+                font
+            );
+        }
+    }
+
+    img
+}
+
+/// Notes below the color legend of every year
+pub fn wall_notes(activity: &Activity, active_years: &[YearStats], holidays: &Holidays, options: &RenderOptions) -> Vec<Vec<String>> {
+    if options.no_stats {
+        return vec![Vec::new(); active_years.len()];
+    }
+    let findings = if options.wellness { wellness::findings(activity) } else { Vec::new() };
+    active_years.iter()
+        .map(|stats| {
+            let mut notes = summary_notes(stats, activity, holidays, options);
+            notes.extend(wellness::year_notes(&findings, stats.year));
+            notes.extend(release_notes(&options.releases, stats.year));
+            notes.extend(options.annotations.iter()
+                .filter(|annotation| annotation.date.year() == stats.year)
+                .map(|annotation| annotation_note(annotation, &options.locale)));
+            notes.extend(activity.imports.iter()
+                .filter(|(_, date)| import_year(active_years, date) == stats.year)
+                .map(|(name, date)| format!("History of {} before {} unavailable", name, date.format("%b %-d, %Y"))));
+            notes
+        })
+        .collect()
+}
+
+/// Notes on the releases of a year, one per project, naming the versions
+/// of few releases
+fn release_notes(releases: &[Release], year: i32) -> Vec<String> {
+    let mut projects: Vec<&str> = releases.iter().map(|release| release.project.as_str()).collect();
+    projects.sort_unstable();
+    projects.dedup();
+    projects.into_iter()
+        .filter_map(|project| {
+            let versions: Vec<&str> = releases.iter()
+                .filter(|release| release.project == project && release.date.year() == year)
+                .map(|release| release.version.as_str())
+                .collect();
+            match versions.len() {
+                0 => None,
+                1 => Some(format!("Released {} {}", project, versions[0])),
+                2..=3 => Some(format!("Released {} {}", project, versions.join(", "))),
+                count => Some(format!("{} releases of {}", count, project)),
+            }
+        })
+        .collect()
+}
+
+/// Footnote of an annotation, e.g. "Feb 14: v2.0 release"
+fn annotation_note(annotation: &Annotation, locale: &Locale) -> String {
+    format!("{} {}: {}", locale.month_abbr(annotation.date.month()), annotation.date.day(), annotation.text)
+}
+
+/// The year an imported history is noted in, which is the year of the
+/// import or the oldest year shown when that's older
+fn import_year(active_years: &[YearStats], date: &NaiveDate) -> i32 {
+    if active_years.iter().any(|stats| stats.year == date.year()) {
+        date.year()
+    } else {
+        active_years.last().map_or(date.year(), |stats| stats.year)
+    }
+}
+
+/// Days before this date may have had commits that were lost when a
+/// repository was imported, see `collect::find_import`
+pub fn history_start(activity: &Activity) -> Option<NaiveDate> {
+    activity.imports.iter().map(|&(_, date)| date).max()
+}
+
+fn summary_notes(year_stats: &YearStats, activity: &Activity, holidays: &Holidays, options: &RenderOptions) -> Vec<String> {
+    let mut notes = Vec::new();
+
+    if !options.filter.is_empty() {
+        notes.push(format!("Only commits made {}", options.filter.describe()));
+    }
+    if let Some(grep) = options.filter.describe_grep() {
+        notes.push(format!("Only commits {}", grep));
+    }
+
+    let locale = &options.locale;
+    if year_stats.commits > 0 {
+        let working_percent = year_stats.working_day_commits() as f64 * 100.0 / year_stats.commits as f64;
+        notes.push(locale.working_days(working_percent, !holidays.is_empty()));
+    }
+
+    if let Some(first_commit) = &year_stats.first_commit {
+        notes.push(locale.first_commit(first_commit.median, first_commit.earliest, first_commit.latest));
+    }
+
+    if let Some(sessions) = &year_stats.sessions {
+        notes.push(locale.sessions(sessions.sessions, sessions.per_week, sessions.average_length));
+    }
+
+    if let (Some(cadence), Some(adherence)) = (&options.cadence, &year_stats.adherence) {
+        notes.push(locale.adherence(adherence.percent(), adherence.hit_days, adherence.planned_days, &cadence.weekdays()));
+    }
+
+    // Which repositories the commits of the year came from
+    if activity.repo_commits.len() > 1 {
+        let mut repos: Vec<(&str, i32)> = activity.repo_commits.iter()
+            .filter_map(|(name, years)| years.get(&year_stats.year).map(|&count| (name.as_str(), count)))
+            .collect();
+        repos.sort_by_key(|&(name, count)| (Reverse(count), name));
+        let mut breakdown: Vec<String> = repos.iter()
+            .take(MAX_BREAKDOWN_REPOS)
+            .map(|(name, count)| format!("{}: {}", name, locale.number(*count)))
+            .collect();
+        if repos.len() > MAX_BREAKDOWN_REPOS {
+            breakdown.push(locale.more_repos(repos.len() - MAX_BREAKDOWN_REPOS));
+        }
+        if !breakdown.is_empty() {
+            notes.push(breakdown.join(", "));
+        }
+    }
+
+    if !activity.weighted_repos.is_empty() {
+        notes.push(locale.weighted(&activity.weighted_repos));
+    }
+
+    notes
+}
+
+/// The wall of one author, shown next to others with `--compare` or below
+/// them with `--author`
+pub struct AuthorWall {
+    pub author: String,
+    pub activity: Activity,
+    /// The same years for every author, with empty stats for the years
+    /// without commits
+    pub active_years: Vec<YearStats>,
+}
+
+/// Gives all walls the same years, empty ones for the years without
+/// enough commits of an author
+pub fn align_years(walls: &mut [AuthorWall]) {
+    let mut years: Vec<i32> = walls.iter()
+        .flat_map(|wall| wall.active_years.iter().map(|stats| stats.year))
+        .collect();
+    years.sort_by(|a, b| b.cmp(a));
+    years.dedup();
+    for wall in walls {
+        let mut active_years = std::mem::take(&mut wall.active_years);
+        wall.active_years = years.iter()
+            .map(|&year| match active_years.iter().position(|stats| stats.year == year) {
+                Some(index) => active_years.remove(index),
+                None => YearStats { year, ..Default::default() },
+            })
+            .collect();
+    }
+}
+
+/// Renders the walls of several authors next to each other below their
+/// names, with the rows of the same year lined up. The commit levels are
+/// shared by all walls, see `shared_levels`, so the colors compare fairly.
+pub fn generate_comparison(walls: &[AuthorWall], holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = options.grid.label_unit;
+    let label_height: u32 = label_unit * 3;
+    let year_gap = options.grid.year_gap;
+
+    let levels = shared_levels(walls.iter().map(|wall| &wall.activity), options);
+    let images: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = walls.iter()
+        .map(|wall| generate_commit_image(&wall.activity, &wall.active_years, &levels, holidays, theme, font, options))
+        .collect();
+    let years = walls.first().map_or(0, |wall| wall.active_years.len() as u32).max(1);
+    // Every wall gives all its years the same height, which is larger for
+    // walls with more notes
+    let row_height = images.iter().map(|image| image.height() / years - year_gap).max().unwrap_or(0);
+    let width = images.iter().map(|image| image.width()).sum::<u32>().max(1);
+    let height = label_height + years * (row_height + year_gap);
+
+    let mut img = ImageBuffer::from_pixel(width, height, theme.background);
+    let mut x = 0;
+    for (wall, image) in walls.iter().zip(&images) {
+        draw_sharp_text(&mut img, &wall.author, (x + options.grid.px(5)) as i32, (label_unit - options.grid.px(2)) as i32, label_unit as f32 * 1.4, theme.text_primary, font);
+        let wall_row_height = image.height() / years;
+        for year in 0..years {
+            let row = imageops::crop_imm(image, 0, year * wall_row_height, image.width(), wall_row_height - year_gap).to_image();
+            imageops::replace(&mut img, &row, x as i64, (label_height + year * (row_height + year_gap)) as i64);
+        }
+        if x > 0 {
+            for y in 0..height {
+                img.put_pixel(x, y, theme.separator);
+            }
+        }
+        x += image.width();
+    }
+    for year in 1..years {
+        let line_y = label_height + year * (row_height + year_gap) - year_gap / 2;
+        for x in 0..width {
+            img.put_pixel(x, line_y, theme.separator);
+        }
+    }
+
+    img
+}
+
+/// Renders the walls of several authors below each other, each below a
+/// label with the name of the author, given with `--author`, with the
+/// levels shared like in `generate_comparison`
+pub fn generate_author_walls(walls: &[AuthorWall], holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = options.grid.label_unit;
+    let label_height: u32 = label_unit * 3;
+
+    let levels = shared_levels(walls.iter().map(|wall| &wall.activity), options);
+    let images: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = walls.iter()
+        .map(|wall| generate_commit_image(&wall.activity, &wall.active_years, &levels, holidays, theme, font, options))
+        .collect();
+    let mut img = stack_walls(&images, label_height, theme);
+    let mut y = 0;
+    for (wall, image) in walls.iter().zip(&images) {
+        draw_sharp_text(&mut img, &wall.author, options.grid.px(5) as i32, (y + label_unit - options.grid.px(2)) as i32, label_unit as f32 * 1.4, theme.text_primary, font);
+        y += label_height + image.height();
+    }
+
+    img
+}
+
+/// The levels of walls drawn together, taken from the days of all of them
+/// when they depend on the days, see `--normalize` and `--color-scale`.
+/// The `YearStats` of the walls have to be computed with the same levels.
+pub fn shared_levels<'a>(activities: impl IntoIterator<Item = &'a Activity>, options: &RenderOptions) -> Levels {
+    Levels::shared(activities, options.metric, options.normalize, options.color_scale)
+}
+
+/// Puts the images below each other, leaving room for a label above each
+fn stack_walls(images: &[ImageBuffer<Rgba<u8>, Vec<u8>>], label_height: u32, theme: &Theme) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let width = images.iter().map(|image| image.width()).max().unwrap_or(1);
+    let height = images.iter().map(|image| label_height + image.height()).sum::<u32>().max(1);
+
+    let mut img = ImageBuffer::from_pixel(width, height, theme.background);
+    let mut y = 0;
+    for image in images {
+        imageops::replace(&mut img, image, 0, (y + label_height) as i64);
+        y += label_height + image.height();
+    }
+    img
+}
+
+/// Renders the wall of every repository below a label with the name and
+/// the primary language of the repository, with the levels shared like in
+/// `generate_comparison`
+pub fn generate_repo_walls(walls: &[RepoWall], holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let label_unit = options.grid.label_unit;
+    let label_height: u32 = label_unit * 3;
+
+    let levels = shared_levels(walls.iter().map(|wall| &wall.activity), options);
+    let images: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = walls.iter()
+        .map(|wall| generate_commit_image(&wall.activity, &wall.active_years, &levels, holidays, theme, font, options))
+        .collect();
+    let mut img = stack_walls(&images, label_height, theme);
+    let mut y = 0;
+    for (wall, image) in walls.iter().zip(&images) {
+        let text_y = (y + label_unit) as i32;
+        let mut text_x = options.grid.px(5) as i32;
+        if let Some(language) = &wall.language {
+            let radius = (label_unit / 2) as i32;
+            draw_filled_circle_mut(&mut img, (text_x + radius, text_y + radius), radius, language.color);
+            text_x += radius * 2 + label_unit as i32 / 2;
+        }
+        let label = match &wall.language {
+            Some(language) => format!("{} ({})", wall.name, language.name),
+            None => wall.name.clone(),
+        };
+        draw_sharp_text(&mut img, &label, text_x, text_y - options.grid.px(2) as i32, label_unit as f32 * 1.4, theme.text_primary, font);
+        y += label_height + image.height();
+    }
+
+    img
+}
@@ -0,0 +1,470 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::fonts::FontChain;
+use crate::holidays::Holidays;
+use crate::log;
+use crate::output;
+use crate::render::{generate_commit_image, RenderOptions};
+use crate::stats::{year_stats, Levels};
+use crate::theme::Theme;
+use crate::watch;
+
+/// How often the wall is rendered again by default
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Clients that don't send their whole request within this time are
+/// dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Longest request line and headers taken together
+const MAX_HEADER_SIZE: u64 = 8 * 1024;
+
+/// Largest body taken, enough for the payload of a webhook on a push
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+/// Address the theme preview is served on when `--listen` isn't given
+const DEFAULT_PREVIEW_ADDRESS: &str = "127.0.0.1:8000";
+
+/// How often the theme file is checked for changes
+const THEME_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Page of the theme preview, showing the wall again whenever the version
+/// on `/version` changes
+const PREVIEW_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>commits-tilewall theme preview</title>
+</head>
+<body style="margin: 0">
+<img id="wall" src="/wall.png?v={version}" alt="Wall of sample commits">
+<script>
+let version = "{version}";
+setInterval(async () => {
+  try {
+    const latest = await (await fetch("/version", { cache: "no-store" })).text();
+    if (latest !== version) {
+      version = latest;
+      document.getElementById("wall").src = "/wall.png?v=" + version;
+    }
+  } catch (err) {
+    // The server is restarting, try again
+  }
+}, 1000);
+</script>
+</body>
+</html>
+"#;
+
+/// The latest rendered wall along with its content type
+type Wall = Arc<Mutex<(Vec<u8>, &'static str)>>;
+
+/// The latest rendered theme preview along with its version, counted up
+/// on every render
+type Preview = Arc<Mutex<(Vec<u8>, u64)>>;
+
+/// Serves the wall over HTTP and renders it again every interval, e.g.
+/// `serve --listen 0.0.0.0:8080 "Jane Doe" ~/src/linux`. Everything
+/// besides `--listen`, `--interval` and `--refresh-secret` is passed on to
+/// rendering. Only the first render has to succeed, later failures are
+/// shown and the last rendered wall is served on.
+pub fn run(args: &[String], generate: fn(&[String]) -> Result<Vec<String>>) -> Result<()> {
+    let mut listen = None;
+    let mut interval = DEFAULT_INTERVAL;
+    let mut secret = None;
+    let mut render_args = vec![args[0].clone()];
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--listen" && i + 1 < args.len() {
+            listen = Some(args[i + 1].as_str());
+            i += 2;
+        } else if args[i] == "--interval" && i + 1 < args.len() {
+            interval = watch::parse_interval(&args[i + 1])?;
+            i += 2;
+        } else if args[i] == "--refresh-secret" && i + 1 < args.len() {
+            secret = Some(args[i + 1].clone());
+            i += 2;
+        } else {
+            render_args.push(args[i].clone());
+            i += 1;
+        }
+    }
+    let Some(listen) = listen else {
+        return Err(Error::Usage("serve needs --listen <address:port>".to_string()));
+    };
+
+    let content_type = match option_value(&render_args, "--format") {
+        _ if option_value(&render_args, "--badge").is_some() => "image/svg+xml",
+        None | Some("png") => "image/png",
+        Some("svg") => "image/svg+xml",
+        Some(format) => return Err(Error::Usage(format!("serve can't serve --format {}, expected png or svg", format))),
+    };
+    if render_args.iter().any(|arg| arg == "--watch" || arg == "--preview" || arg == "--append-to") {
+        return Err(Error::Usage("serve can't be combined with --watch, --preview or --append-to".to_string()));
+    }
+    // Render to the given file, or to a temporary one that is removed again
+    // as soon as it's read, the wall is served from memory
+    let (output, temporary) = match option_value(&render_args, "--output") {
+        Some(output) => (PathBuf::from(output), false),
+        None => {
+            let extension = if content_type == "image/png" { "png" } else { "svg" };
+            let output = env::temp_dir().join(format!("commits-tilewall-serve-{}.{}", process::id(), extension));
+            render_args.extend(["--output".to_string(), output.to_string_lossy().into_owned()]);
+            (output, true)
+        }
+    };
+
+    let listener = TcpListener::bind(listen)
+        .map_err(|err| Error::Usage(format!("Failed to listen on {}: {}", listen, err)))?;
+    generate(&render_args)?;
+    let wall: Wall = Arc::new(Mutex::new((read_wall(&output, temporary)?, content_type)));
+
+    // Requests to /refresh wake the rendering up before the interval is over
+    let (refresh, refresh_requested) = mpsc::channel();
+    let rendered = Arc::clone(&wall);
+    thread::spawn(move || loop {
+        match refresh_requested.recv_timeout(interval) {
+            Ok(()) => {
+                // Several pushes in a row only need one render
+                while refresh_requested.try_recv().is_ok() {}
+                log::info!("Refresh requested, rendering again");
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        match generate(&render_args).and_then(|_| read_wall(&output, temporary)) {
+            Ok(image) => rendered.lock().expect("wall lock poisoned").0 = image,
+            Err(err) => eprintln!("Error: {}", err),
+        }
+    });
+
+    log::info!("Serving the wall on http://{}/, rendering it again every {} seconds", listen, interval.as_secs());
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = respond(stream, &wall, &refresh, secret.as_deref()) {
+                    log::verbose!("Failed to answer a request: {}", err);
+                }
+            }
+            Err(err) => log::verbose!("Failed to accept a connection: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Serves a wall of sample commits in the theme of a file and renders it
+/// again whenever the file is saved, e.g. `preview --serve --theme
+/// mine.toml`. The page in the browser shows the new wall by itself. An
+/// invalid theme is shown as error, and the last valid one stays served.
+pub fn run_preview(args: &[String]) -> Result<()> {
+    let mut serve = false;
+    let mut theme_path = None;
+    let mut listen = DEFAULT_PREVIEW_ADDRESS;
+    let mut fonts = Vec::new();
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--serve" {
+            serve = true;
+            i += 1;
+        } else if args[i] == "--theme" && i + 1 < args.len() {
+            theme_path = Some(PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--listen" && i + 1 < args.len() {
+            listen = &args[i + 1];
+            i += 2;
+        } else if args[i] == "--font" && i + 1 < args.len() {
+            fonts.push(args[i + 1].clone());
+            i += 2;
+        } else {
+            return Err(Error::Usage(format!("Unknown preview option '{}'", args[i])));
+        }
+    }
+    if !serve {
+        return Err(Error::Usage("preview needs --serve, a wall is previewed in the terminal with --preview".to_string()));
+    }
+    let Some(theme_path) = theme_path else {
+        return Err(Error::Usage("preview needs --theme <file.toml>".to_string()));
+    };
+
+    let listener = TcpListener::bind(listen)
+        .map_err(|err| Error::Usage(format!("Failed to listen on {}: {}", listen, err)))?;
+    let preview: Preview = Arc::new(Mutex::new((render_preview(&theme_path, &FontChain::new(&fonts)?)?, 0)));
+
+    // The fonts can't be shared between threads, so the watching thread
+    // loads its own
+    let rendered = Arc::clone(&preview);
+    let watched = theme_path.clone();
+    thread::spawn(move || {
+        let font = match FontChain::new(&fonts) {
+            Ok(font) => font,
+            Err(err) => return eprintln!("Error: {}", err),
+        };
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let mut last_modified = modified(&watched);
+        loop {
+            thread::sleep(THEME_POLL_INTERVAL);
+            let current = modified(&watched);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            match render_preview(&watched, &font) {
+                Ok(image) => {
+                    log::info!("{} changed, rendering again", watched.display());
+                    let mut preview = rendered.lock().expect("preview lock poisoned");
+                    *preview = (image, preview.1 + 1);
+                }
+                Err(err) => eprintln!("Error: {}", err),
+            }
+        }
+    });
+
+    log::info!("Previewing {} on http://{}/, rendering again when it changes", theme_path.display(), listen);
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                if let Err(err) = respond_preview(stream, &preview) {
+                    log::verbose!("Failed to answer a request: {}", err);
+                }
+            }
+            Err(err) => log::verbose!("Failed to accept a connection: {}", err),
+        }
+    }
+    Ok(())
+}
+
+/// Renders the sample commits in the theme of the file as PNG
+fn render_preview(theme_path: &Path, font: &FontChain) -> Result<Vec<u8>> {
+    let theme = Theme::load(theme_path)?;
+    let activity = Activity::sample();
+    let holidays = Holidays::default();
+    let levels = Levels::default();
+    let active_years = year_stats(&activity, &holidays, None, &levels);
+    let img = generate_commit_image(&activity, &active_years, &levels, &holidays, &theme, font, &RenderOptions::default());
+    output::encode_png(&img, None)
+}
+
+/// Answers a single request to the theme preview, serving the page on `/`,
+/// the wall on `/wall.png` and its version on `/version`
+fn respond_preview(mut stream: TcpStream, preview: &Preview) -> io::Result<()> {
+    let request = read_request(&stream)?;
+    if request.method != "GET" && request.method != "HEAD" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed\n", true);
+    }
+    let with_body = request.method == "GET";
+    let (image, version) = preview.lock().expect("preview lock poisoned").clone();
+    match request.path.as_str() {
+        "/" => {
+            let page = PREVIEW_PAGE.replace("{version}", &version.to_string());
+            write_response(&mut stream, "200 OK", "text/html; charset=utf-8", page.as_bytes(), with_body)
+        }
+        "/wall.png" => write_response(&mut stream, "200 OK", "image/png", &image, with_body),
+        "/version" => write_response(&mut stream, "200 OK", "text/plain", version.to_string().as_bytes(), with_body),
+        _ => write_response(&mut stream, "404 Not Found", "text/plain", b"Not found\n", with_body),
+    }
+}
+
+/// The value following `option` in the arguments, if given
+fn option_value<'a>(args: &'a [String], option: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == option)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Reads the rendered wall, removing the file when it's a `temporary` one
+fn read_wall(path: &Path, temporary: bool) -> Result<Vec<u8>> {
+    let wall = fs::read(path).map_err(|err| Error::Output(format!("Failed to read the rendered wall {}: {}", path.display(), err)))?;
+    if temporary {
+        fs::remove_file(path).map_err(|err| Error::Output(format!("Failed to remove the rendered wall {}: {}", path.display(), err)))?;
+    }
+    Ok(wall)
+}
+
+/// Answers a single request, serving the wall on `/` and on `/wall.png`
+/// or `/wall.svg`, and rendering it again on `POST /refresh`
+fn respond(mut stream: TcpStream, wall: &Wall, refresh: &Sender<()>, secret: Option<&str>) -> io::Result<()> {
+    let Request { method, path, query, token, signature, body } = read_request(&stream)?;
+    let (method, path) = (method.as_str(), path.as_str());
+    if path == "/refresh" {
+        if method != "POST" {
+            return write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed\n", true);
+        }
+        if let Some(secret) = secret {
+            // GitHub signs the payload with the secret, GitLab sends it in a
+            // header, others can put it in the URL
+            let authorized = match signature {
+                Some(signature) => signed_with(&body, &signature, secret),
+                None => token.as_deref()
+                    .or_else(|| query.split('&').find_map(|param| param.strip_prefix("secret=")))
+                    .is_some_and(|given| bool::from(given.as_bytes().ct_eq(secret.as_bytes()))),
+            };
+            if !authorized {
+                return write_response(&mut stream, "403 Forbidden", "text/plain", b"Wrong secret\n", true);
+            }
+        }
+        // The rendering thread only goes away along with the server
+        let _ = refresh.send(());
+        return write_response(&mut stream, "202 Accepted", "text/plain", b"Rendering again\n", true);
+    }
+    if method != "GET" && method != "HEAD" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed\n", true);
+    }
+    let (image, content_type) = wall.lock().expect("wall lock poisoned").clone();
+    let file = if content_type == "image/png" { "/wall.png" } else { "/wall.svg" };
+    if path != "/" && path != file {
+        return write_response(&mut stream, "404 Not Found", "text/plain", b"Not found\n", method == "GET");
+    }
+
+    write_response(&mut stream, "200 OK", content_type, &image, method == "GET")
+}
+
+/// The parts of a request the servers look at
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    /// Secret sent by GitLab webhooks in `X-Gitlab-Token`
+    token: Option<String>,
+    /// Hex HMAC-SHA256 of the body sent by GitHub webhooks in
+    /// `X-Hub-Signature-256`
+    signature: Option<String>,
+    /// The body, only kept when it is signed
+    body: Vec<u8>,
+}
+
+/// Reads from a stream until the deadline, after which reads fail, so that
+/// a client sending a byte at a time can't hold the server up
+struct Deadline<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for Deadline<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "the request took too long"));
+        }
+        self.stream.set_read_timeout(Some(remaining))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+fn read_request(stream: &TcpStream) -> io::Result<Request> {
+    let too_large = |what| io::Error::new(io::ErrorKind::InvalidData, format!("the {} is too large", what));
+    let mut reader = BufReader::new(Deadline { stream, deadline: Instant::now() + REQUEST_TIMEOUT });
+    let mut head = (&mut reader).take(MAX_HEADER_SIZE);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut token = None;
+    let mut signature = None;
+    let mut header = String::new();
+    loop {
+        header.clear();
+        head.read_line(&mut header)?;
+        if !header.ends_with('\n') {
+            return Err(if head.limit() == 0 {
+                too_large("header")
+            } else {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "the request ended within the header")
+            });
+        }
+        if header.trim_end().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("X-Gitlab-Token") {
+                token = Some(value.trim().to_string());
+            } else if name.eq_ignore_ascii_case("X-Hub-Signature-256") {
+                signature = Some(value.trim().to_string());
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(too_large("body"));
+    }
+    // Read the body even when it isn't needed, like the payload of an
+    // unsigned webhook, so that closing the connection doesn't reset it
+    // before the response is read
+    let mut body = Vec::new();
+    let mut rest = reader.take(content_length);
+    if signature.is_some() {
+        rest.read_to_end(&mut body)?;
+    } else {
+        io::copy(&mut rest, &mut io::sink())?;
+    }
+    log::verbose!("{}", request_line.trim_end());
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or_default(), parts.next().unwrap_or_default());
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    Ok(Request { method: method.to_string(), path: path.to_string(), query: query.to_string(), token, signature, body })
+}
+
+/// Whether the `sha256=<hex>` signature is the HMAC-SHA256 of the body
+/// with the secret, compared in constant time
+fn signed_with(body: &[u8], signature: &str, secret: &str) -> bool {
+    let Some(hex) = signature.strip_prefix("sha256=") else {
+        return false;
+    };
+    let digest: Option<Vec<u8>> = (0..hex.len())
+        .step_by(2)
+        .map(|i| hex.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect();
+    let Some(digest) = digest else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    mac.verify_slice(&digest).is_ok()
+}
+
+fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], with_body: bool) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if with_body {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn github_signatures_are_verified() {
+        // The example of GitHub's documentation on validating webhook deliveries
+        let signature = "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17";
+        assert!(signed_with(b"Hello, World!", signature, "It's a Secret to Everybody"));
+        assert!(!signed_with(b"Hello, World?", signature, "It's a Secret to Everybody"));
+        assert!(!signed_with(b"Hello, World!", signature, "It's a Secret to Nobody"));
+        assert!(!signed_with(b"Hello, World!", &signature[7..], "It's a Secret to Everybody"));
+        assert!(!signed_with(b"Hello, World!", "sha256=75710", "It's a Secret to Everybody"));
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime};
+
+use crate::stats::today;
+
+/// Commits closer together than this belong to the same session
+pub const SESSION_GAP_HOURS: i64 = 2;
+
+/// A period of continuous work, from the first to the last commit
+#[derive(Debug, Clone, Copy)]
+pub struct Session {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl Session {
+    pub fn length(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Session numbers of one year
+#[derive(Debug, Clone, Copy)]
+pub struct SessionStats {
+    pub sessions: usize,
+    pub per_week: f64,
+    pub average_length: Duration,
+}
+
+/// Clusters commit times into sessions, a new session starting whenever
+/// there is a gap of more than `SESSION_GAP_HOURS` between two commits.
+pub fn find_sessions(timestamps: &[NaiveDateTime]) -> Vec<Session> {
+    let mut sorted = timestamps.to_vec();
+    sorted.sort_unstable();
+
+    let mut sessions: Vec<Session> = Vec::new();
+    for timestamp in sorted {
+        match sessions.last_mut() {
+            Some(session) if timestamp - session.end <= Duration::hours(SESSION_GAP_HOURS) => {
+                session.end = timestamp;
+            }
+            _ => sessions.push(Session { start: timestamp, end: timestamp }),
+        }
+    }
+    sessions
+}
+
+pub fn year_sessions(sessions: &[Session], year: i32) -> Option<SessionStats> {
+    let year_sessions: Vec<&Session> = sessions.iter()
+        .filter(|session| session.start.year() == year)
+        .collect();
+    if year_sessions.is_empty() {
+        return None;
+    }
+
+    // Only count the weeks of the current year that have already passed
+    let start = NaiveDate::from_ymd_opt(year, 1, 1)?;
+    let end = NaiveDate::from_ymd_opt(year, 12, 31)?.min(today());
+    let weeks = (((end - start).num_days() + 1) as f64 / 7.0).max(1.0);
+
+    let total: Duration = year_sessions.iter().map(|session| session.length()).sum();
+    Some(SessionStats {
+        sessions: year_sessions.len(),
+        per_week: year_sessions.len() as f64 / weeks,
+        average_length: total / year_sessions.len() as i32,
+    })
+}
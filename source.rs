@@ -0,0 +1,33 @@
+use crate::cache::CacheMode;
+use crate::collect::{collect_activity, Activity};
+use crate::config::Config;
+use crate::error::Result;
+use crate::filters::CommitFilter;
+
+/// Somewhere commit activity can be collected from
+pub trait DataSource {
+    /// Human readable description used in progress messages
+    fn describe(&self) -> String;
+
+    /// Collects the commits that match `filter`
+    fn collect(&self, filter: &CommitFilter) -> Result<Activity>;
+}
+
+/// Local git repositories, scanned with `git log`
+pub struct GitSource<'a> {
+    /// Patterns of `git log --author`, a commit matching any of them counts
+    pub authors: &'a [String],
+    pub repos: &'a [String],
+    pub config: &'a Config,
+    pub cache: CacheMode,
+}
+
+impl DataSource for GitSource<'_> {
+    fn describe(&self) -> String {
+        format!("{} local repositories", self.repos.len())
+    }
+
+    fn collect(&self, filter: &CommitFilter) -> Result<Activity> {
+        collect_activity(self.authors, self.repos, self.config, filter, self.cache)
+    }
+}
@@ -0,0 +1,446 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::env;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime};
+
+use crate::cadence::{Adherence, Cadence};
+use crate::collect::{Activity, DiffStats};
+use crate::error::{Error, Result};
+use crate::holidays::Holidays;
+use crate::sessions::{find_sessions, year_sessions, SessionStats};
+
+/// Years with fewer commits are left out of the wall
+pub const MIN_YEAR_COMMITS: i32 = 5;
+
+/// Environment variable fixing the current date, see `today`
+const SOURCE_DATE_EPOCH: &str = "SOURCE_DATE_EPOCH";
+
+/// Today, or the day of the seconds since 1970 in `SOURCE_DATE_EPOCH`
+/// when it's set, like for reproducible builds. Everything depending on
+/// the current date goes through here, so that the same history renders
+/// the same wall on any day.
+pub fn today() -> NaiveDate {
+    env::var(SOURCE_DATE_EPOCH).ok()
+        .and_then(|epoch| epoch.trim().parse().ok())
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .map(|time| time.date_naive())
+        .unwrap_or_else(|| Local::now().date_naive())
+}
+
+/// Refuses a `SOURCE_DATE_EPOCH` that `today` can't use, rather than
+/// silently rendering with the current date
+pub fn check_source_date_epoch() -> Result<()> {
+    match env::var(SOURCE_DATE_EPOCH) {
+        Ok(epoch) if epoch.trim().parse().ok().and_then(|seconds| DateTime::from_timestamp(seconds, 0)).is_none() => {
+            Err(Error::Usage(format!("Invalid {} '{}', expected a number of seconds since 1970", SOURCE_DATE_EPOCH, epoch)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Levels of the days with commits, level 0 being days without commits.
+/// Their labels are given by the `Locale`.
+pub const LEVELS: usize = 5;
+
+/// Weights of `--metric weighted` when none are given, a commit counting
+/// as much as 50 changed lines
+const DEFAULT_COMMIT_WEIGHT: f64 = 1.0;
+const DEFAULT_LINE_WEIGHT: f64 = 0.02;
+
+/// What the color of a day stands for, given with `--metric`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Metric {
+    #[default]
+    Commits,
+    /// Lines inserted and deleted
+    Lines,
+    /// Points of the commits plus points of the lines they change, which
+    /// are put into the levels of commits
+    Weighted { commit_weight: f64, line_weight: f64 },
+}
+
+impl Metric {
+    /// Parses `commits`, `lines` or `weighted=1,0.02`, the weights of a
+    /// commit and a changed line
+    pub fn parse(metric: &str) -> Result<Metric> {
+        match metric.split_once('=') {
+            None if metric == "commits" => Ok(Metric::Commits),
+            None if metric == "lines" => Ok(Metric::Lines),
+            None if metric == "weighted" => Ok(Metric::Weighted {
+                commit_weight: DEFAULT_COMMIT_WEIGHT,
+                line_weight: DEFAULT_LINE_WEIGHT,
+            }),
+            Some(("weighted", weights)) => {
+                let parse_weight = |weight: &str| match weight.trim().parse::<f64>() {
+                    Ok(weight) if weight >= 0.0 && weight.is_finite() => Ok(weight),
+                    _ => Err(Error::Usage(format!("Invalid weight '{}' in --metric {}, expected a number of at least 0", weight, metric))),
+                };
+                let (commits, lines) = weights.split_once(',')
+                    .ok_or_else(|| Error::Usage(format!("Invalid weights in --metric {}, expected <commit weight>,<line weight>", metric)))?;
+                Ok(Metric::Weighted { commit_weight: parse_weight(commits)?, line_weight: parse_weight(lines)? })
+            }
+            _ => Err(Error::Usage(format!("Unknown metric '{}', expected commits, lines or weighted=<commit weight>,<line weight>", metric))),
+        }
+    }
+
+    /// Whether the metric needs the lines changed, which only git
+    /// repositories report
+    pub fn uses_lines(&self) -> bool {
+        !matches!(self, Metric::Commits)
+    }
+
+    /// What the levels of a day with `commits` commits, changing `diff`,
+    /// are taken from: its commits, lines or points
+    pub fn value(&self, commits: i32, diff: Option<&DiffStats>) -> f64 {
+        let lines = diff.map_or(0, |diff| diff.insertions + diff.deletions);
+        match self {
+            Metric::Commits => commits as f64,
+            Metric::Lines => lines as f64,
+            Metric::Weighted { commit_weight, line_weight } => commits as f64 * commit_weight + lines as f64 * line_weight,
+        }
+    }
+
+    /// Level 0 to 5 of a day with `commits` commits, changing `diff`
+    pub fn level(&self, commits: i32, diff: Option<&DiffStats>) -> usize {
+        match self {
+            Metric::Commits => commit_level(commits),
+            Metric::Lines if commits <= 0 => 0,
+            // Days with commits stay visible when they change no lines
+            Metric::Lines => match diff.map_or(0, |diff| diff.insertions + diff.deletions) {
+                i32::MIN..=9 => 1,
+                10..=49 => 2,
+                50..=199 => 3,
+                200..=499 => 4,
+                _ => 5,
+            },
+            Metric::Weighted { .. } if commits <= 0 => 0,
+            Metric::Weighted { commit_weight, line_weight } => {
+                let lines = diff.map_or(0, |diff| diff.insertions + diff.deletions);
+                let points = commits as f64 * commit_weight + lines as f64 * line_weight;
+                commit_level((points.round() as i32).max(1))
+            }
+        }
+    }
+}
+
+/// How the days are put into levels, given with `--normalize`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Normalize {
+    /// The same levels for all years, see `Metric::level`
+    #[default]
+    Global,
+    /// Every year is split up by its own days, so that a quiet year uses
+    /// all levels too
+    PerYear,
+}
+
+impl Normalize {
+    pub fn parse(normalize: &str) -> Result<Normalize> {
+        match normalize {
+            "global" => Ok(Normalize::Global),
+            "per-year" => Ok(Normalize::PerYear),
+            _ => Err(Error::Usage(format!("Unknown normalization '{}', expected global or per-year", normalize))),
+        }
+    }
+}
+
+/// How the values of the days are mapped to levels, given with
+/// `--color-scale`. Without one, the levels have fixed thresholds, see
+/// `Metric::level`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorScale {
+    /// Every level holds about a fifth of the days by their percentile
+    /// rank, days of the same value sharing one level
+    Percentile,
+    /// The levels split the logarithm of the values between the least
+    /// and the busiest day into equal parts
+    Log,
+}
+
+impl ColorScale {
+    pub fn parse(scale: &str) -> Result<ColorScale> {
+        match scale {
+            "percentile" => Ok(ColorScale::Percentile),
+            "log" => Ok(ColorScale::Log),
+            _ => Err(Error::Usage(format!("Unknown color scale '{}', expected percentile or log", scale))),
+        }
+    }
+}
+
+/// Puts the days of an activity into levels, by the metric, the
+/// normalization and the color scale
+#[derive(Debug, Default)]
+pub struct Levels {
+    metric: Metric,
+    scale: Option<ColorScale>,
+    /// Values of the days with commits, sorted, per year when the years
+    /// are normalized and under `None` for all years otherwise
+    values: HashMap<Option<i32>, Vec<f64>>,
+    normalize: Normalize,
+}
+
+impl Levels {
+    /// The levels of the wall of a single activity, see `shared`
+    pub fn new(activity: &Activity, metric: Metric, normalize: Normalize, scale: Option<ColorScale>) -> Levels {
+        Levels::shared([activity], metric, normalize, scale)
+    }
+
+    /// One set of levels for the walls of several activities drawn
+    /// together, taken from the days of all of them, so that a color stands
+    /// for the same values on every wall. Without a `scale`, years
+    /// normalized with `Normalize::PerYear` use the percentile scale, and
+    /// otherwise the fixed thresholds of the metric are used.
+    pub fn shared<'a>(activities: impl IntoIterator<Item = &'a Activity>, metric: Metric, normalize: Normalize, scale: Option<ColorScale>) -> Levels {
+        let scale = scale.or((normalize == Normalize::PerYear).then_some(ColorScale::Percentile));
+        let mut values: HashMap<Option<i32>, Vec<f64>> = HashMap::new();
+        if scale.is_some() {
+            for activity in activities {
+                for (date, &count) in activity.commits.iter().filter(|(_, &count)| count > 0) {
+                    let group = (normalize == Normalize::PerYear).then_some(date.year());
+                    values.entry(group).or_default().push(metric.value(count, activity.diff_stats.get(date)));
+                }
+            }
+            for values in values.values_mut() {
+                values.sort_unstable_by(f64::total_cmp);
+            }
+        }
+        Levels { metric, scale, values, normalize }
+    }
+
+    /// Whether the levels depend on the days of the activity, so that the
+    /// legend can't use the fixed labels of the metric
+    pub fn relative(&self) -> bool {
+        self.scale.is_some()
+    }
+
+    /// Level 0 to 5 of the day `date` with `commits` commits, changing
+    /// `diff`, see `ColorScale`
+    pub fn level(&self, date: NaiveDate, commits: i32, diff: Option<&DiffStats>) -> usize {
+        let group = (self.normalize == Normalize::PerYear).then_some(date.year());
+        let (Some(scale), Some(values)) = (self.scale, self.values.get(&group).filter(|_| commits > 0)) else {
+            return self.metric.level(commits, diff);
+        };
+        let value = self.metric.value(commits, diff);
+        let share = match scale {
+            ColorScale::Percentile => {
+                let below = values.partition_point(|&other| other < value);
+                let equal = values.partition_point(|&other| other <= value) - below;
+                (below as f64 + equal as f64 / 2.0) / values.len() as f64
+            }
+            // Shifted by one, as days may change no lines
+            ColorScale::Log => {
+                let (least, busiest) = (values[0].ln_1p(), values[values.len() - 1].ln_1p());
+                if busiest > least { (value.ln_1p() - least) / (busiest - least) } else { 0.0 }
+            }
+        };
+        1 + ((LEVELS as f64 * share) as usize).min(LEVELS - 1)
+    }
+}
+
+/// Aggregated numbers of one year, as shown in the summary
+#[derive(Debug, Default)]
+pub struct YearStats {
+    pub year: i32,
+    pub commits: i32,
+    pub diff: DiffStats,
+    /// Number of days per level 1 to 5, see `Levels::level`
+    pub level_days: [usize; LEVELS],
+    /// Smallest and largest value of the days per level, when the levels
+    /// depend on the days, see `Levels::relative`
+    pub level_ranges: Option<[(f64, f64); LEVELS]>,
+    /// Commits on weekends and holidays
+    pub day_off_commits: i32,
+    /// Commits per weekday, Monday first
+    pub weekday_commits: [i32; 7],
+    /// Commits per month, January first
+    pub month_commits: [i32; 12],
+    /// Planned days hit, when a cadence is given
+    pub adherence: Option<Adherence>,
+    /// When the first commit of the day happens
+    pub first_commit: Option<FirstCommitTimes>,
+    /// Work sessions, see `sessions::find_sessions`
+    pub sessions: Option<SessionStats>,
+    /// Lines changed per language, the most first
+    pub languages: Vec<(String, i32)>,
+}
+
+/// Distribution of the time of the first commit over the days of a year
+#[derive(Debug, Clone, Copy)]
+pub struct FirstCommitTimes {
+    pub median: NaiveTime,
+    pub earliest: NaiveTime,
+    pub latest: NaiveTime,
+}
+
+impl YearStats {
+    pub fn working_day_commits(&self) -> i32 {
+        self.commits - self.day_off_commits
+    }
+}
+
+pub fn commit_level(commit_count: i32) -> usize {
+    match commit_count {
+        i32::MIN..=0 => 0,
+        1 => 1,
+        2..=4 => 2,
+        5..=9 => 3,
+        10..=19 => 4,
+        _ => 5,
+    }
+}
+
+/// Aggregates the collected activity per year, returning the years with
+/// significant activity, most recent first.
+pub fn year_stats(activity: &Activity, holidays: &Holidays, cadence: Option<&Cadence>, levels: &Levels) -> Vec<YearStats> {
+    let mut years: HashMap<i32, YearStats> = HashMap::new();
+
+    for (date, &count) in &activity.commits {
+        let stats = years.entry(date.year()).or_insert_with(|| YearStats {
+            year: date.year(),
+            ..Default::default()
+        });
+        stats.commits += count;
+        let level = levels.level(*date, count, activity.diff_stats.get(date));
+        stats.level_days[level - 1] += 1;
+        if levels.relative() {
+            let value = levels.metric.value(count, activity.diff_stats.get(date));
+            let ranges = stats.level_ranges.get_or_insert([(f64::MAX, f64::MIN); LEVELS]);
+            ranges[level - 1] = (ranges[level - 1].0.min(value), ranges[level - 1].1.max(value));
+        }
+        stats.weekday_commits[date.weekday().num_days_from_monday() as usize] += count;
+        stats.month_commits[date.month0() as usize] += count;
+        if holidays.is_day_off(date) {
+            stats.day_off_commits += count;
+        }
+        if let Some(diff) = activity.diff_stats.get(date) {
+            stats.diff.add(diff);
+        }
+    }
+
+    let mut active_years: Vec<YearStats> = years
+        .into_values()
+        .filter(|stats| stats.commits >= MIN_YEAR_COMMITS)
+        .collect();
+    // Time of the first commit of every day
+    let mut first_times: HashMap<NaiveDate, NaiveTime> = HashMap::new();
+    for timestamp in &activity.timestamps {
+        let time = first_times.entry(timestamp.date()).or_insert(timestamp.time());
+        *time = (*time).min(timestamp.time());
+    }
+    for stats in &mut active_years {
+        let mut times: Vec<NaiveTime> = first_times.iter()
+            .filter(|(date, _)| date.year() == stats.year)
+            .map(|(_, &time)| time)
+            .collect();
+        times.sort_unstable();
+        if let (Some(&earliest), Some(&latest)) = (times.first(), times.last()) {
+            stats.first_commit = Some(FirstCommitTimes {
+                median: times[times.len() / 2],
+                earliest,
+                latest,
+            });
+        }
+    }
+
+    let sessions = find_sessions(&activity.timestamps);
+    for stats in &mut active_years {
+        stats.sessions = year_sessions(&sessions, stats.year);
+    }
+
+    for stats in &mut active_years {
+        if let Some(lines) = activity.language_lines.get(&stats.year) {
+            stats.languages = lines.iter().map(|(language, &lines)| (language.clone(), lines)).collect();
+            stats.languages.sort_by(|(a_language, a), (b_language, b)| b.cmp(a).then(a_language.cmp(b_language)));
+        }
+    }
+
+    if let (Some(cadence), Some(&first)) = (cadence, activity.commits.keys().min()) {
+        for stats in &mut active_years {
+            stats.adherence = Some(adherence(activity, cadence, stats.year, first));
+        }
+    }
+    active_years.sort_unstable_by_key(|stats| Reverse(stats.year));  // Sort in descending order
+
+    active_years
+}
+
+/// Adherence to the cadence within a year, only counting the days since
+/// the first commit and up to today
+fn adherence(activity: &Activity, cadence: &Cadence, year: i32, first_commit: NaiveDate) -> Adherence {
+    let today = today();
+    let start = NaiveDate::from_ymd_opt(year, 1, 1).unwrap().max(first_commit);
+    let end = NaiveDate::from_ymd_opt(year, 12, 31).unwrap().min(today);
+
+    let mut result = Adherence::default();
+    for date in start.iter_days().take_while(|date| *date <= end) {
+        if cadence.is_planned(&date) {
+            result.planned_days += 1;
+            if activity.commits.contains_key(&date) {
+                result.hit_days += 1;
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An activity with one day of every count in March of `year`
+    fn activity(year: i32, counts: &[i32]) -> Activity {
+        let mut activity = Activity::default();
+        for (day, &count) in counts.iter().enumerate() {
+            activity.commits.insert(NaiveDate::from_ymd_opt(year, 3, day as u32 + 1).unwrap(), count);
+        }
+        activity
+    }
+
+    fn level(levels: &Levels, year: i32, commits: i32) -> usize {
+        levels.level(NaiveDate::from_ymd_opt(year, 1, 1).unwrap(), commits, None)
+    }
+
+    #[test]
+    fn fixed_levels_without_a_scale() {
+        let levels = Levels::new(&activity(2024, &[1, 2, 3]), Metric::Commits, Normalize::Global, None);
+        assert!(!levels.relative());
+        assert_eq!([0, 1, 4, 9, 19, 20].map(|commits| level(&levels, 2024, commits)), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn percentile_ranks_the_days() {
+        let levels = Levels::new(&activity(2024, &[1, 2, 3, 4, 5]), Metric::Commits, Normalize::Global, Some(ColorScale::Percentile));
+        assert_eq!([1, 2, 3, 4, 5].map(|commits| level(&levels, 2024, commits)), [1, 2, 3, 4, 5]);
+        assert_eq!(level(&levels, 2024, 0), 0);
+    }
+
+    #[test]
+    fn log_scale_spreads_the_logarithms() {
+        let levels = Levels::new(&activity(2024, &[1, 1000]), Metric::Commits, Normalize::Global, Some(ColorScale::Log));
+        assert_eq!(level(&levels, 2024, 1), 1);
+        assert_eq!(level(&levels, 2024, 31), 3);
+        assert_eq!(level(&levels, 2024, 1000), 5);
+    }
+
+    #[test]
+    fn per_year_levels_rank_every_year_on_its_own() {
+        let mut both = activity(2023, &[1, 2, 3, 4, 5]);
+        both.commits.extend(activity(2024, &[10, 20, 30, 40, 50]).commits);
+        let levels = Levels::new(&both, Metric::Commits, Normalize::PerYear, None);
+        assert!(levels.relative());
+        assert_eq!(level(&levels, 2023, 5), 5);
+        assert_eq!(level(&levels, 2024, 10), 1);
+    }
+
+    #[test]
+    fn shared_levels_compare_across_walls() {
+        let (quiet, busy) = (activity(2024, &[1, 1, 1]), activity(2024, &[8, 8, 8]));
+        for scale in [ColorScale::Percentile, ColorScale::Log] {
+            let own = Levels::new(&quiet, Metric::Commits, Normalize::PerYear, Some(scale));
+            let shared = Levels::shared([&quiet, &busy], Metric::Commits, Normalize::PerYear, Some(scale));
+            assert_eq!(level(&own, 2024, 1), level(&Levels::new(&busy, Metric::Commits, Normalize::PerYear, Some(scale)), 2024, 8));
+            assert!(level(&shared, 2024, 1) < level(&shared, 2024, 8));
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::fmt::Write;
+
+use base64::Engine;
+use image::Rgba;
+
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::header::Header;
+use crate::fonts::FontChain;
+use crate::holidays::Holidays;
+use crate::qr::{MODULE_SIZE, QUIET_ZONE};
+use crate::render::{Granularity, RenderOptions};
+use crate::stats::{YearStats, LEVELS};
+use crate::theme::Theme;
+use crate::vector::{draw_wall, wall_layout, Canvas, Corner, Style};
+use crate::woff2;
+
+/// Font stack used when no font family is given with `--font`
+pub const DEFAULT_FONT_FAMILY: &str = "'DejaVu Sans', 'Segoe UI', Helvetica, Arial, sans-serif";
+
+pub fn hex(color: Rgba<u8>) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Draws on an SVG document, with the colors in CSS classes
+struct SvgCanvas {
+    svg: String,
+    /// Radius of the corners of the tiles, see `render::Grid`
+    corner_radius: u32,
+    /// All text drawn, for the glyphs of the embedded fonts
+    text: String,
+}
+
+impl SvgCanvas {
+    fn class(style: Style) -> String {
+        match style {
+            Style::Primary => "t1".to_string(),
+            Style::Secondary => "t2".to_string(),
+            Style::Separator => "sep".to_string(),
+            Style::Level(level) => format!("l{}", level),
+            Style::Weekend => "we".to_string(),
+            Style::Gap => "gap".to_string(),
+            Style::Dotted => "dot".to_string(),
+            Style::Holiday => "hol".to_string(),
+            Style::Sparkline => "spark".to_string(),
+        }
+    }
+}
+
+/// The `rx` attribute of a rectangle with rounded corners, nothing for
+/// square ones
+fn rounding(radius: f32) -> String {
+    if radius > 0.0 {
+        format!(r#" rx="{}""#, radius)
+    } else {
+        String::new()
+    }
+}
+
+impl Canvas for SvgCanvas {
+    fn begin_group(&mut self, x: u32, y: u32) {
+        writeln!(self.svg, r#"<g transform="translate({},{})">"#, x, y).unwrap();
+    }
+
+    fn end_group(&mut self) {
+        self.svg.push_str("</g>\n");
+    }
+
+    fn text(&mut self, style: Style, x: u32, y: u32, size: f32, content: &str) {
+        let baseline = y as f32 + size * 0.8;
+        self.text.push_str(content);
+        writeln!(self.svg, r#"<text class="{}" x="{}" y="{:.1}" font-size="{:.1}">{}</text>"#, SvgCanvas::class(style), x, baseline, size, escape(content)).unwrap();
+    }
+
+    /// A `<use>` of the shared rectangle, which keeps walls of many years
+    /// small. Days without commits get the color of `use` elements.
+    fn tile(&mut self, style: Style, x: u32, y: u32, half: bool) {
+        let id = if half { "h" } else { "t" };
+        match style {
+            Style::Level(0) => writeln!(self.svg, r##"<use href="#{}" x="{}" y="{}"/>"##, id, x, y).unwrap(),
+            style => writeln!(self.svg, r##"<use href="#{}" x="{}" y="{}" class="{}"/>"##, id, x, y, SvgCanvas::class(style)).unwrap(),
+        }
+    }
+
+    fn border(&mut self, style: Style, x: u32, y: u32, size: u32) {
+        // The stroke runs half a pixel inside the tile, and so does its
+        // rounding
+        let radius = rounding(self.corner_radius as f32 - 0.5);
+        writeln!(self.svg, r#"<rect class="{}" x="{}.5" y="{}.5" width="{3}" height="{3}"{4}/>"#, SvgCanvas::class(style), x, y, size - 1, radius).unwrap();
+    }
+
+    fn rect(&mut self, style: Style, x: u32, y: u32, width: u32, height: u32) {
+        writeln!(self.svg, r#"<rect class="{}" x="{}" y="{}" width="{}" height="{}"/>"#, SvgCanvas::class(style), x, y, width, height).unwrap();
+    }
+
+    fn corner(&mut self, style: Style, x: u32, y: u32, size: u32, corner: Corner) {
+        let half = size / 2;
+        match corner {
+            Corner::UpperRight => writeln!(self.svg, r#"<path class="{}" d="M{} {}h{3}v{3}z"/>"#, SvgCanvas::class(style), x + half, y, half).unwrap(),
+            Corner::LowerLeft => writeln!(self.svg, r#"<path class="{}" d="M{} {}v{3}h{3}z"/>"#, SvgCanvas::class(style), x, y + half, half).unwrap(),
+        }
+    }
+
+    fn line(&mut self, style: Style, y: i32, width: u32) {
+        writeln!(self.svg, r#"<line class="{}" x1="0" y1="{1}" x2="{2}" y2="{1}"/>"#, SvgCanvas::class(style), y, width).unwrap();
+    }
+
+    fn polyline(&mut self, style: Style, points: &[(f32, f32)]) {
+        let points: Vec<String> = points.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+        writeln!(self.svg, r#"<polyline class="{}" points="{}"/>"#, SvgCanvas::class(style), points.join(" ")).unwrap();
+    }
+
+    fn qr_code(&mut self, size: u32, modules: &[bool], modules_width: u32) {
+        writeln!(self.svg, r##"<rect width="{0}" height="{0}" fill="#ffffff"/>"##, size).unwrap();
+        let mut path = String::new();
+        for (i, _) in modules.iter().enumerate().filter(|(_, &dark)| dark) {
+            let x = (QUIET_ZONE + i as u32 % modules_width) * MODULE_SIZE;
+            let y = (QUIET_ZONE + i as u32 / modules_width) * MODULE_SIZE;
+            write!(path, "M{} {}h{2}v{2}h-{2}z", x, y, MODULE_SIZE).unwrap();
+        }
+        writeln!(self.svg, r##"<path fill="#000000" d="{}"/>"##, path).unwrap();
+    }
+}
+
+/// Renders the wall as SVG with the same layout as
+/// `render::generate_commit_image`. Every tile is a `<use>` of one shared
+/// rectangle colored by a CSS class per commit level, which keeps walls of
+/// many years small. With `--svg-fonts embed` the fonts drawing the text
+/// are embedded as subsets, see `embedded_fonts`.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_commit_svg(activity: &Activity, active_years: &[YearStats], header: Option<&Header>, holidays: &Holidays, theme: &Theme, font: &FontChain, options: &RenderOptions, svg_fonts: SvgFonts) -> Result<String> {
+    let block_size = options.grid.block_size;
+    let layout = wall_layout(activity, active_years, header, holidays, font, options);
+    let (width, height) = (layout.width, layout.height);
+
+    // The text has to be drawn before the fonts drawing it are known
+    let corner_radius = options.grid.tile_radius();
+    let mut canvas = SvgCanvas { svg: String::new(), corner_radius, text: String::new() };
+    draw_wall(&mut canvas, &layout, activity, active_years, header, holidays, font, options);
+
+    // Without embedding, font files can't be referenced from the SVG, so
+    // only a family given by name is used
+    let mut font_family = match font.family() {
+        Some(family) => format!("'{}', {}", family.replace('\'', ""), DEFAULT_FONT_FAMILY),
+        None => DEFAULT_FONT_FAMILY.to_string(),
+    };
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#, width, height).unwrap();
+    if svg_fonts == SvgFonts::Embed {
+        let (font_faces, families) = embedded_fonts(font, &canvas.text)?;
+        writeln!(svg, "<defs><style>\n{}</style></defs>", font_faces).unwrap();
+        font_family = format!("{}, {}", families, font_family);
+    }
+    svg.push_str("<style>\n");
+    writeln!(svg, "text{{font-family:{}}}", font_family).unwrap();
+    writeln!(svg, ".t1{{fill:{}}}.t2{{fill:{}}}.sep{{stroke:{}}}", hex(theme.text_primary), hex(theme.text_secondary), hex(theme.separator)).unwrap();
+    writeln!(svg, "use{{fill:{}}}", hex(theme.commit_colors[0])).unwrap();
+    writeln!(svg, ".gap{{fill:none;stroke:{}}}", hex(theme.commit_colors[0])).unwrap();
+    writeln!(svg, ".dot{{fill:none;stroke:{};stroke-dasharray:1}}", hex(theme.commit_colors[0])).unwrap();
+    for (level, &color) in theme.commit_colors.iter().enumerate().skip(1) {
+        writeln!(svg, ".l{}{{fill:{}}}", level, hex(color)).unwrap();
+    }
+    if options.highlight_weekends {
+        writeln!(svg, ".we{{fill:{};fill-opacity:.2}}", hex(theme.text_secondary)).unwrap();
+    }
+    if !holidays.is_empty() {
+        writeln!(svg, ".hol{{fill:none;stroke:{}}}", hex(theme.text_primary)).unwrap();
+    }
+    if options.sparkline {
+        writeln!(svg, ".spark{{fill:none;stroke:{}}}", hex(theme.commit_colors[LEVELS])).unwrap();
+    }
+    svg.push_str("</style>\n");
+    if options.granularity == Granularity::HalfDay {
+        let half_radius = rounding(corner_radius.min(block_size / 4) as f32);
+        writeln!(svg, r#"<defs><rect id="t" width="{0}" height="{0}"{2}/><rect id="h" width="{0}" height="{1}"{3}/></defs>"#, block_size, block_size / 2, rounding(corner_radius as f32), half_radius).unwrap();
+    } else {
+        writeln!(svg, r#"<defs><rect id="t" width="{0}" height="{0}"{1}/></defs>"#, block_size, rounding(corner_radius as f32)).unwrap();
+    }
+    writeln!(svg, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex(theme.background)).unwrap();
+
+    svg.push_str(&canvas.svg);
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// The `@font-face` rules of the fonts of the chain that draw the text,
+/// each subset to the glyphs of the text and packed as WOFF2, with the
+/// list of their families in the order of the chain. The viewer picks the
+/// font for every character from that list like the chain does, so the
+/// text renders the same on machines without the fonts.
+fn embedded_fonts(font: &FontChain, text: &str) -> Result<(String, String)> {
+    let mut font_faces = String::new();
+    let mut families = Vec::new();
+    for (i, subset) in font.subsets(text)?.iter().enumerate() {
+        let family = format!("'tilewall-{}'", i);
+        let data = base64::engine::general_purpose::STANDARD.encode(woff2::encode(subset)?);
+        writeln!(font_faces, "@font-face{{font-family:{};src:url(data:font/woff2;base64,{}) format('woff2')}}", family, data).unwrap();
+        families.push(family);
+    }
+    Ok((font_faces, families.join(", ")))
+}
+
+/// Whether the SVG carries the fonts of its text, see `--svg-fonts`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SvgFonts {
+    /// Name the family of `--font` and common sans fonts, which keeps the
+    /// file small but leaves the look to the fonts of the viewing system
+    #[default]
+    System,
+    /// Embed subsets of the fonts that draw the text
+    Embed,
+}
+
+impl SvgFonts {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "system" => Ok(SvgFonts::System),
+            "embed" => Ok(SvgFonts::Embed),
+            _ => Err(Error::Usage(format!("Unknown --svg-fonts '{}', expected embed or system", value))),
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use image::{imageops, ImageBuffer, Rgba};
+
+use crate::collect::Activity;
+use crate::error::{Error, Result};
+use crate::fonts::FontChain;
+use crate::holidays::Holidays;
+use crate::log;
+use crate::output;
+use crate::preview::{self, Protocol};
+use crate::render::{draw_sharp_text, generate_commit_image, RenderOptions};
+use crate::stats::{year_stats, Levels};
+use crate::theme::{user_themes, Theme, BUILT_IN};
+
+/// Height of the band above every swatch with the name of its theme
+const LABEL_HEIGHT: u32 = 30;
+
+/// Lists the built-in themes and the ones saved in the `themes` directory
+/// of the configuration, e.g. `themes --preview kitty`. With `--preview`,
+/// every theme is rendered as a swatch of the newest year of
+/// `Activity::sample`, and the swatches are stacked into one image shown
+/// in the terminal, or written to `--output` when no protocol is given.
+pub fn run(args: &[String]) -> Result<()> {
+    let mut preview = false;
+    let mut protocol = None;
+    let mut fonts = Vec::new();
+    let mut output_path = None;
+    let mut i = 2;
+    while i < args.len() {
+        if args[i] == "--preview" {
+            preview = true;
+            if let Some(name) = args.get(i + 1).filter(|name| !name.starts_with('-')) {
+                protocol = Some(Protocol::parse(name)?);
+                i += 1;
+            }
+            i += 1;
+        } else if args[i] == "--font" && i + 1 < args.len() {
+            fonts.push(args[i + 1].clone());
+            i += 2;
+        } else if args[i] == "--output" && i + 1 < args.len() {
+            output_path = Some(PathBuf::from(&args[i + 1]));
+            i += 2;
+        } else if args[i] == "--quiet" || args[i] == "-q" {
+            log::set_level(log::Level::Quiet);
+            i += 1;
+        } else if args[i] == "--verbose" || args[i] == "-v" {
+            log::set_level(log::Level::Verbose);
+            i += 1;
+        } else {
+            return Err(Error::Usage(format!("Unknown themes option '{}'", args[i])));
+        }
+    }
+    if !preview && (output_path.is_some() || !fonts.is_empty()) {
+        return Err(Error::Usage("--output and --font of themes need --preview".to_string()));
+    }
+    if protocol.is_some() && output_path.is_some() {
+        return Err(Error::Usage("themes --preview either shows the swatches in the terminal or writes them to --output".to_string()));
+    }
+
+    // User themes named like a built-in one can't be picked with
+    // `--theme` by name, so they are left out
+    let mut themes: Vec<(String, Theme)> = BUILT_IN.iter().map(|name| (name.to_string(), Theme::by_name(name))).collect();
+    for (name, path) in user_themes() {
+        if BUILT_IN.contains(&name.as_str()) {
+            log::warning!("Skipping {}, it's named like a built-in theme", path.display());
+            continue;
+        }
+        match Theme::load(&path) {
+            Ok(theme) => themes.push((name, theme)),
+            Err(err) => log::warning!("Skipping the theme {}: {}", name, err),
+        }
+    }
+    if !preview {
+        for (name, _) in &themes {
+            println!("{}", name);
+        }
+        return Ok(());
+    }
+
+    let img = generate_swatches(&themes, &FontChain::new(&fonts)?);
+    match protocol {
+        Some(protocol) => preview::show(&img, protocol),
+        None => {
+            let output_path = match output_path {
+                Some(path) => path,
+                None => std::env::current_dir()
+                    .map_err(|err| Error::Output(format!("Failed to get the current directory: {}", err)))?
+                    .join("themes.png"),
+            };
+            output::save_png(&img, &output_path, None, None, None)?;
+            println!("Wrote {}", output_path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Stacks a wall of the sample commits in every theme, each below a band
+/// of its background with the name of the theme
+fn generate_swatches(themes: &[(String, Theme)], font: &FontChain) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let activity = Activity::sample();
+    let holidays = Holidays::default();
+    let levels = Levels::default();
+    let mut active_years = year_stats(&activity, &holidays, None, &levels);
+    let newest = active_years.iter().map(|stats| stats.year).max();
+    active_years.retain(|stats| Some(stats.year) == newest);
+    let options = RenderOptions { no_stats: true, ..Default::default() };
+
+    let walls: Vec<ImageBuffer<Rgba<u8>, Vec<u8>>> = themes.iter()
+        .map(|(_, theme)| generate_commit_image(&activity, &active_years, &levels, &holidays, theme, font, &options))
+        .collect();
+    let width = walls.iter().map(|wall| wall.width()).max().unwrap_or(1);
+    let height = walls.iter().map(|wall| LABEL_HEIGHT + wall.height()).sum::<u32>().max(1);
+    let mut img = ImageBuffer::new(width, height);
+    let mut y = 0;
+    for ((name, theme), wall) in themes.iter().zip(&walls) {
+        let swatch_height = LABEL_HEIGHT + wall.height();
+        imageops::replace(&mut img, &ImageBuffer::from_pixel(width, swatch_height, theme.background), 0, y as i64);
+        draw_sharp_text(&mut img, name, 10, y as i32 + 8, 18.0, theme.text_primary, font);
+        imageops::replace(&mut img, wall, 0, (y + LABEL_HEIGHT) as i64);
+        y += swatch_height;
+    }
+    img
+}
@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::Rgba;
+use serde::Deserialize;
+
+use crate::config::config_dir;
+use crate::error::{Error, Result};
+use crate::log;
+
+/// Names of the themes that come with the program
+pub const BUILT_IN: [&str; 3] = ["light", "dark", "github"];
+
+#[derive(Debug)]
+pub struct Theme {
+    pub background: Rgba<u8>,
+    pub text_primary: Rgba<u8>,
+    pub text_secondary: Rgba<u8>,
+    pub separator: Rgba<u8>,
+    pub commit_colors: [Rgba<u8>; 6],  // [no_commit, 1, 2-4, 5-9, 10-19, 20+]
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Theme {
+            background: Rgba([30, 30, 30, 255]),
+            text_primary: Rgba([255, 255, 255, 255]),
+            text_secondary: Rgba([200, 200, 200, 255]),
+            separator: Rgba([70, 70, 70, 255]),
+            commit_colors: [
+                Rgba([50, 50, 50, 255]),      // no commits
+                Rgba([40, 160, 40, 255]),     // 1 commit - brighter to be visible
+                Rgba([60, 200, 60, 255]),     // 2-4 commits
+                Rgba([80, 240, 80, 255]),     // 5-9 commits
+                Rgba([120, 255, 120, 255]),   // 10-19 commits
+                Rgba([160, 255, 160, 255]),   // 20+ commits
+            ],
+        }
+    }
+
+    pub fn light() -> Self {
+        Theme {
+            background: Rgba([255, 255, 255, 255]),
+            text_primary: Rgba([50, 50, 50, 255]),
+            text_secondary: Rgba([100, 100, 100, 255]),
+            separator: Rgba([220, 220, 220, 255]),
+            commit_colors: [
+                Rgba([240, 240, 240, 255]),   // no commits
+                Rgba([140, 240, 140, 255]),   // 1 commit - more distinct
+                Rgba([100, 220, 100, 255]),   // 2-4 commits
+                Rgba([60, 200, 60, 255]),     // 5-9 commits
+                Rgba([40, 180, 40, 255]),     // 10-19 commits
+                Rgba([20, 160, 20, 255]),     // 20+ commits
+            ],
+        }
+    }
+
+    pub fn github() -> Self {
+        Theme {
+            background: Rgba([255, 255, 255, 255]),
+            text_primary: Rgba([24, 23, 23, 255]),
+            text_secondary: Rgba([87, 96, 106, 255]),
+            separator: Rgba([235, 237, 240, 255]),
+            commit_colors: [
+                Rgba([235, 237, 240, 255]),   // no commits
+                Rgba([155, 233, 168, 255]),   // 1 commit - GitHub's actual color
+                Rgba([100, 220, 123, 255]),   // 2-4 commits
+                Rgba([64, 196, 99, 255]),     // 5-9 commits
+                Rgba([48, 161, 78, 255]),     // 10-19 commits
+                Rgba([33, 110, 57, 255]),     // 20+ commits
+            ],
+        }
+    }
+
+    pub fn by_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "dark" => Theme::dark(),
+            "github" => Theme::github(),
+            _ => Theme::light(),  // default to light theme
+        }
+    }
+
+    /// The theme given with `--theme`, read from the file when it's a
+    /// path to a `.toml` file, see `ThemeFile`, or when it's the name of
+    /// one of the `user_themes`
+    pub fn resolve(name: &str) -> Result<Self> {
+        if name.ends_with(".toml") {
+            return Theme::load(Path::new(name));
+        }
+        match user_themes().into_iter().find(|(user_theme, _)| user_theme == name) {
+            Some((_, path)) if !BUILT_IN.contains(&name) => Theme::load(&path),
+            _ => Ok(Theme::by_name(name)),
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| Error::Input(format!("Failed to read theme file {}: {}", path.display(), err)))?;
+        let file: ThemeFile = toml::from_str(&content)
+            .map_err(|err| Error::Input(format!("Failed to parse theme file {}: {}", path.display(), err)))?;
+        file.theme().map_err(|err| Error::Input(format!("Invalid theme file {}: {}", path.display(), err)))
+    }
+}
+
+/// Themes saved as `.toml` files in the `themes` directory next to the
+/// configuration file, by name, e.g. `mine` for `themes/mine.toml`
+pub fn user_themes() -> Vec<(String, PathBuf)> {
+    let Some(dir) = config_dir().map(|dir| dir.join("themes")) else {
+        return Vec::new();
+    };
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut themes: Vec<(String, PathBuf)> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .filter_map(|path| Some((path.file_stem()?.to_string_lossy().into_owned(), path)))
+        .collect();
+    themes.sort();
+    log::verbose!("Found {} themes in {}", themes.len(), dir.display());
+    themes
+}
+
+/// A custom theme, starting from a built-in one and overriding some of its
+/// colors, e.g.
+///
+/// ```toml
+/// base = "dark"
+/// background = "#101418"
+/// commit_colors = ["#20262c", "#0e4429", "#006d32", "#26a641", "#39d353", "#7ee787"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    /// Built-in theme providing the colors that aren't given, light when
+    /// not set
+    base: Option<String>,
+    background: Option<String>,
+    text_primary: Option<String>,
+    text_secondary: Option<String>,
+    separator: Option<String>,
+    /// From no commits to 20 and more commits a day
+    commit_colors: Option<Vec<String>>,
+}
+
+impl ThemeFile {
+    fn theme(&self) -> std::result::Result<Theme, String> {
+        let mut theme = Theme::by_name(self.base.as_deref().unwrap_or("light"));
+        for (color, value) in [
+            (&mut theme.background, &self.background),
+            (&mut theme.text_primary, &self.text_primary),
+            (&mut theme.text_secondary, &self.text_secondary),
+            (&mut theme.separator, &self.separator),
+        ] {
+            if let Some(value) = value {
+                *color = parse_color(value)?;
+            }
+        }
+        if let Some(colors) = &self.commit_colors {
+            if colors.len() != theme.commit_colors.len() {
+                return Err(format!("commit_colors needs {} colors, from no commits to 20 and more, not {}", theme.commit_colors.len(), colors.len()));
+            }
+            for (color, value) in theme.commit_colors.iter_mut().zip(colors) {
+                *color = parse_color(value)?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+/// Parses a color like `#1e1e1e` or `#1e1e1e80`, with an optional alpha
+fn parse_color(value: &str) -> std::result::Result<Rgba<u8>, String> {
+    let invalid = || format!("'{}' is no color, expected #rrggbb or #rrggbbaa", value);
+    let digits = value.strip_prefix('#').ok_or_else(invalid)?;
+    if !(digits.len() == 6 || digits.len() == 8) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let channel = |i: usize| digits.get(i * 2..i * 2 + 2).map_or(255, |hex| u8::from_str_radix(hex, 16).unwrap_or(255));
+    Ok(Rgba([channel(0), channel(1), channel(2), channel(3)]))
+}
@@ -0,0 +1,293 @@
+use chrono::NaiveDate;
+
+use crate::collect::Activity;
+use crate::fonts::FontChain;
+use crate::header::{Header, HEADER_HEIGHT};
+use crate::holidays::{is_weekend, Holidays};
+use crate::qr::{qr_modules, qr_size, MODULE_SIZE};
+use crate::render::{half_day_commits, history_start, month_label_size, month_totals, sparkline_height, sparkline_points, summary_layout, summary_lines, wall_notes, Granularity, GridLayout, RenderOptions, SummaryLayout, TileStyle};
+use crate::stats::{Levels, YearStats, LEVELS};
+
+const SPACE_SIZE: u32 = 2;
+
+/// Look of a shape drawn on a `Canvas`, the CSS classes of the SVG
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Style {
+    /// Text and marks in the primary or the secondary text color
+    Primary,
+    Secondary,
+    Separator,
+    /// Color of a commit level, 0 being a day without commits
+    Level(usize),
+    /// Translucent tint over the tiles of weekends
+    Weekend,
+    /// Borders of empty days, see `TileStyle`, and of holidays
+    Gap,
+    Dotted,
+    Holiday,
+    /// Line of the weekly commits, see `--sparkline`
+    Sparkline,
+}
+
+/// Corner of a tile marked with a triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Corner {
+    UpperRight,
+    LowerLeft,
+}
+
+/// Shapes of the vector outputs, drawn by `draw_wall` in the same layout as
+/// `render::generate_commit_image`. Coordinates are in pixels of the PNG,
+/// from the top left corner of the current group.
+pub trait Canvas {
+    /// Starts a group whose shapes are moved by `x` and `y`
+    fn begin_group(&mut self, x: u32, y: u32);
+    fn end_group(&mut self);
+    /// Text with `y` being its top, like for `render::draw_sharp_text`
+    fn text(&mut self, style: Style, x: u32, y: u32, size: f32, content: &str);
+    /// A day tile of `render::Grid::block_size`, or the upper or lower half
+    /// of one when `half` is set
+    fn tile(&mut self, style: Style, x: u32, y: u32, half: bool);
+    /// The one pixel wide border inside a square of `size`
+    fn border(&mut self, style: Style, x: u32, y: u32, size: u32);
+    fn rect(&mut self, style: Style, x: u32, y: u32, width: u32, height: u32);
+    /// A triangle filling half of a square of `size` in one corner
+    fn corner(&mut self, style: Style, x: u32, y: u32, size: u32, corner: Corner);
+    /// A line across the whole width at `y`
+    fn line(&mut self, style: Style, y: i32, width: u32);
+    /// A one pixel wide line through the points
+    fn polyline(&mut self, style: Style, points: &[(f32, f32)]);
+    /// A QR code with its quiet zone, always black on white, see
+    /// `qr::add_qr_footer`
+    fn qr_code(&mut self, size: u32, modules: &[bool], modules_width: u32);
+}
+
+/// Size and layout of a wall in a vector output
+pub struct WallLayout {
+    pub width: u32,
+    pub height: u32,
+    year_notes: Vec<Vec<String>>,
+    summary: SummaryLayout,
+    notes_start: u32,
+    grid_layout: GridLayout,
+    year_height: u32,
+    header_height: u32,
+    walls_height: u32,
+}
+
+pub fn wall_layout(activity: &Activity, active_years: &[YearStats], header: Option<&Header>, holidays: &Holidays, font: &FontChain, options: &RenderOptions) -> WallLayout {
+    let grid = options.grid;
+    let label_unit = grid.label_unit;
+    let grid_layout = GridLayout::new(options);
+    let year_label_width = label_unit * 5;
+
+    let year_notes = wall_notes(activity, active_years, holidays, options);
+    // The text is measured with the font used for PNG output
+    let summary = summary_layout(&year_notes, font, options);
+    let max_notes = year_notes.iter().map(|notes| notes.len()).max().unwrap_or(0) as u32;
+    let notes_start = label_unit + SPACE_SIZE + LEVELS as u32 * (label_unit + SPACE_SIZE);
+    let year_height = (grid_layout.height + sparkline_height(options))
+        .max(if options.no_stats { 0 } else { notes_start + max_notes * (label_unit + SPACE_SIZE) })
+        // Room for the year label next to small grids
+        .max(label_unit * 2);
+
+    let width = year_label_width +
+                grid_layout.width +
+                summary.width +
+                SPACE_SIZE * 4;
+    let header_height = if header.is_some() { HEADER_HEIGHT } else { 0 };
+    let walls_height = header_height + (year_height + grid.year_gap) * active_years.len() as u32;
+    let footer_height = match &options.qr {
+        Some(url) => qr_size(qr_modules(url).1, MODULE_SIZE) + label_unit,
+        None => 0,
+    };
+    WallLayout {
+        width,
+        height: walls_height + footer_height,
+        year_notes,
+        summary,
+        notes_start,
+        grid_layout,
+        year_height,
+        header_height,
+        walls_height,
+    }
+}
+
+/// Draws the wall laid out by `wall_layout` on the canvas
+#[allow(clippy::too_many_arguments)]
+pub fn draw_wall(canvas: &mut impl Canvas, layout: &WallLayout, activity: &Activity, active_years: &[YearStats], header: Option<&Header>, holidays: &Holidays, font: &FontChain, options: &RenderOptions) {
+    let space_size = SPACE_SIZE;
+    let grid = options.grid;
+    let label_unit = grid.label_unit;
+    let grid_layout = &layout.grid_layout;
+    let year_label_width = label_unit * 5;
+    let width = layout.width;
+    let summary = &layout.summary;
+    let year_height = layout.year_height;
+
+    // Header band, see `header::add_header`
+    if let Some(header) = header {
+        canvas.text(Style::Primary, 5, label_unit / 2, label_unit as f32 * 2.0, &header.author);
+        canvas.text(Style::Secondary, 5, label_unit * 3, label_unit as f32 * 1.1, &header.subtitle());
+        canvas.line(Style::Separator, HEADER_HEIGHT as i32 - 8, width);
+    }
+
+    let history_start = history_start(activity);
+    let half_days = (options.granularity == Granularity::HalfDay).then(|| half_day_commits(activity));
+    let levels = Levels::new(activity, options.metric, options.normalize, options.color_scale);
+    let month_totals = month_totals(activity);
+    for (year_index, year_stats) in active_years.iter().enumerate() {
+        let year = year_stats.year;
+        let year_offset = layout.header_height + year_index as u32 * (year_height + grid.year_gap);
+        canvas.begin_group(0, year_offset);
+
+        canvas.text(Style::Primary, 5, year_height / 2 - label_unit / 2, label_unit as f32 * 1.6, &year.to_string());
+
+        for (weekday, label_y) in grid_layout.weekday_labels() {
+            canvas.text(Style::Secondary, year_label_width, label_y, label_unit as f32 * 0.8, &options.locale.weekday_abbr(weekday));
+        }
+
+        for month in 1..=12 {
+            let (month_x, month_y, available_width) = grid_layout.month(year, month);
+            let month_x_offset = year_label_width + month_x;
+            if let Some(label) = options.month_labels.label(month, &options.locale) {
+                canvas.text(Style::Secondary, month_x_offset, month_y + grid_layout.labels.label_y, month_label_size(&label, available_width, &grid, font), &label);
+            }
+            if let Some(&total) = month_totals.get(&(year, month)).filter(|_| options.month_totals) {
+                canvas.text(Style::Secondary, month_x_offset, month_y + grid_layout.labels.totals_y, label_unit as f32 * 0.8, &options.locale.number(total));
+            }
+
+            for day in 1..=31 {
+                let Some(date) = NaiveDate::from_ymd_opt(year, month, day) else { break };
+                let (day_x, y) = grid_layout.day(date);
+                let x = year_label_width + day_x;
+
+                let level = levels.level(date, activity.commits.get(&date).copied().unwrap_or(0), activity.diff_stats.get(&date));
+                // Drawn tiles of weekends are tinted, like the pixels in the PNG
+                let tinted = options.highlight_weekends && is_weekend(&date);
+                if level == 0 {
+                    let style = if history_start.is_some_and(|start| date < start) {
+                        options.empty_days.for_gaps()
+                    } else {
+                        options.empty_days
+                    };
+                    empty_tile(canvas, x, y, grid.block_size, style);
+                    if tinted && style == TileStyle::Solid {
+                        canvas.tile(Style::Weekend, x, y, false);
+                    }
+                } else if let Some(half_days) = &half_days {
+                    // Halves without commits are left out unless the empty
+                    // days are solid, like in the PNG
+                    let halves = half_days.get(&date).copied().unwrap_or_default();
+                    for (half, count) in halves.into_iter().enumerate() {
+                        let half_y = y + half as u32 * grid.block_size / 2;
+                        match levels.level(date, count, None) {
+                            0 if options.empty_days != TileStyle::Solid => continue,
+                            level => canvas.tile(Style::Level(level), x, half_y, true),
+                        }
+                        if tinted {
+                            canvas.tile(Style::Weekend, x, half_y, true);
+                        }
+                    }
+                } else {
+                    canvas.tile(Style::Level(level), x, y, false);
+                    if tinted {
+                        canvas.tile(Style::Weekend, x, y, false);
+                    }
+                }
+
+                if holidays.contains(&date) {
+                    canvas.border(Style::Holiday, x, y, grid.block_size);
+                }
+                if options.cadence.as_ref().is_some_and(|cadence| cadence.is_planned(&date)) {
+                    let dot_size = (grid.block_size / 5).max(1);
+                    let dot_offset = (grid.block_size - dot_size) / 2;
+                    canvas.rect(Style::Secondary, x + dot_offset, y + dot_offset, dot_size, dot_size);
+                }
+                if options.releases.iter().any(|release| release.date == date) {
+                    canvas.corner(Style::Primary, x, y, grid.block_size, Corner::UpperRight);
+                }
+                if options.annotations.iter().any(|annotation| annotation.date == date) {
+                    canvas.corner(Style::Primary, x, y, grid.block_size, Corner::LowerLeft);
+                }
+            }
+        }
+
+        if options.sparkline {
+            let sparkline_y = grid_layout.height + label_unit;
+            let (sparkline_x, sparkline_width) = grid_layout.sparkline();
+            let points: Vec<(f32, f32)> = sparkline_points(activity, year, sparkline_width, &grid).into_iter()
+                .map(|(x, y)| ((year_label_width + sparkline_x) as f32 + x, sparkline_y as f32 + y))
+                .collect();
+            canvas.polyline(Style::Sparkline, &points);
+        }
+
+        if year_index > 0 {
+            canvas.line(Style::Separator, -((grid.year_gap / 2) as i32), width);
+        }
+
+        if options.no_stats {
+            canvas.end_group();
+            continue;
+        }
+
+        // Summary on the right side
+        let summary_x = width - summary.width - space_size * 2;
+
+        let summary_lines = summary_lines(activity, year_stats, &options.locale);
+        for (i, line) in summary_lines.iter().enumerate() {
+            canvas.text(Style::Primary, summary_x, label_unit + i as u32 * (label_unit + space_size), label_unit as f32 * 0.8, line);
+        }
+
+        let level_start_y = label_unit + space_size;
+        for (i, &count) in year_stats.level_days.iter().enumerate() {
+            let Some(legend_x) = summary.legend_x.map(|x| summary_x + x) else { break };
+            if count > 0 {
+                let y = level_start_y + i as u32 * (label_unit + space_size);
+                // The legend keeps the size of the text, not of the tiles
+                canvas.rect(Style::Level(i + 1), legend_x, y, label_unit, label_unit);
+                canvas.text(Style::Secondary, legend_x + label_unit + space_size * 2, y, label_unit as f32 * 0.8, &options.locale.level_days(count, options.metric, i, year_stats.level_ranges.map(|ranges| ranges[i])));
+            }
+        }
+
+        // Weekday distribution, see `panels::WeekdayChart`
+        let chart_x = summary_x + summary.chart_x;
+        let bar_width = label_unit + 2;
+        let max_bar_height = label_unit * 4;
+        let max = year_stats.weekday_commits.iter().copied().max().unwrap_or(0).max(1);
+        let initials = options.locale.weekday_initials();
+        for (i, &count) in year_stats.weekday_commits.iter().enumerate() {
+            let bar_x = chart_x + i as u32 * (bar_width + 2);
+            let bar_height = (count as u32 * max_bar_height).div_ceil(max as u32);
+            canvas.rect(Style::Level(4), bar_x, level_start_y + max_bar_height - bar_height, bar_width, bar_height);
+            canvas.text(Style::Secondary, bar_x + bar_width / 4, level_start_y + max_bar_height + 1, label_unit as f32 * 0.8, &initials[i]);
+        }
+
+        for (i, note) in layout.year_notes[year_index].iter().enumerate() {
+            canvas.text(Style::Secondary, summary_x, layout.notes_start + i as u32 * (label_unit + space_size), label_unit as f32 * 0.8, note);
+        }
+
+        canvas.end_group();
+    }
+
+    // QR code footer, see `qr::add_qr_footer`
+    if let Some(url) = &options.qr {
+        let (modules, modules_width) = qr_modules(url);
+        let size = qr_size(modules_width, MODULE_SIZE);
+        canvas.begin_group(5, layout.walls_height);
+        canvas.qr_code(size, &modules, modules_width);
+        canvas.text(Style::Secondary, size + label_unit, size / 2 - label_unit / 2, label_unit as f32 * 1.1, url);
+        canvas.end_group();
+    }
+}
+
+/// A day without commits in the given style, see `render::draw_tile`
+fn empty_tile(canvas: &mut impl Canvas, x: u32, y: u32, block_size: u32, style: TileStyle) {
+    match style {
+        TileStyle::Solid => canvas.tile(Style::Level(0), x, y, false),
+        TileStyle::None => {}
+        TileStyle::Outline => canvas.border(Style::Gap, x, y, block_size),
+        TileStyle::Dotted => canvas.border(Style::Dotted, x, y, block_size),
+    }
+}
@@ -0,0 +1,96 @@
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::collect;
+use crate::error::{Error, Result};
+use crate::log;
+use crate::remote;
+
+/// How often the repositories are checked for new commits by default
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Takes `--watch` and `--interval <seconds>` out of the arguments,
+/// returning the remaining ones along with the interval when watching
+pub fn split_args(args: &[String]) -> Result<(Vec<String>, Option<Duration>)> {
+    let mut rest = Vec::new();
+    let mut watch = false;
+    let mut interval = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--watch" {
+            watch = true;
+            i += 1;
+        } else if args[i] == "--interval" && i + 1 < args.len() {
+            interval = Some(parse_interval(&args[i + 1])?);
+            i += 2;
+        } else {
+            rest.push(args[i].clone());
+            i += 1;
+        }
+    }
+
+    match (watch, interval) {
+        (false, Some(_)) => Err(Error::Usage("--interval needs --watch".to_string())),
+        (false, None) => Ok((rest, None)),
+        (true, interval) => Ok((rest, Some(interval.unwrap_or(DEFAULT_INTERVAL)))),
+    }
+}
+
+/// Parses the seconds given with `--interval`
+pub fn parse_interval(arg: &str) -> Result<Duration> {
+    match arg.parse() {
+        Ok(seconds) if seconds > 0 => Ok(Duration::from_secs(seconds)),
+        _ => Err(Error::Usage(format!("Invalid --interval {}, expected a number of seconds", arg))),
+    }
+}
+
+/// Renders the wall, then keeps rendering it again whenever a branch of one
+/// of the repositories it was collected from moves. Only the first render
+/// has to succeed, later failures are shown and the watching goes on.
+pub fn run(args: &[String], interval: Duration, generate: fn(&[String]) -> Result<Vec<String>>) -> Result<()> {
+    // Checked before rendering, which would clone the remote repositories
+    // or download the commits of a forge first
+    let remote = args.iter().any(|arg| remote::is_remote_url(arg) || arg == "--load")
+        || args.windows(2).any(|pair| pair[0] == "--source" && pair[1] != "git");
+    if remote {
+        return Err(Error::Usage("--watch only works with local git repositories".to_string()));
+    }
+    let repos = generate(args)?;
+    if repos.is_empty() {
+        return Err(Error::Usage("--watch only works with local git repositories".to_string()));
+    }
+
+    let mut heads = all_heads(&repos)?;
+    loop {
+        log::info!("Waiting for new commits in {} repositories, checking every {} seconds", repos.len(), interval.as_secs());
+        loop {
+            thread::sleep(interval);
+            // A repository may be locked by a fetch or gone for a moment,
+            // it's checked again after the next interval
+            let current = match all_heads(&repos) {
+                Ok(current) => current,
+                Err(err) => {
+                    log::warning!("Failed to check for new commits: {}", err);
+                    continue;
+                }
+            };
+            if current != heads {
+                heads = current;
+                break;
+            }
+        }
+
+        log::info!("Found new commits, rendering again");
+        if let Err(err) = generate(args) {
+            eprintln!("Error: {}", err);
+        }
+    }
+}
+
+/// The commits HEAD and all branches of every repository point to
+fn all_heads(repos: &[String]) -> Result<Vec<String>> {
+    repos.iter()
+        .map(|repo| collect::run_git(Command::new("git").args(["rev-parse", "HEAD", "--branches"]), repo))
+        .collect()
+}
@@ -0,0 +1,110 @@
+use chrono::{Datelike, Duration, NaiveDate};
+
+use crate::collect::Activity;
+
+/// A 7-day window with at least this many commits counts as a busy week
+pub const BUSY_WEEK_COMMITS: i32 = 50;
+/// This many days in a row with commits count as a stretch without a break
+pub const NO_BREAK_DAYS: i64 = 21;
+/// Findings listed per year, any further ones are only counted
+const MAX_FINDINGS_PER_YEAR: usize = 3;
+
+/// Periods that may be worth a second look when using the wall for
+/// self-reflection, shown with `--wellness`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Finding {
+    /// Consecutive 7-day windows with at least `BUSY_WEEK_COMMITS` commits,
+    /// with the highest number of commits in any of the windows
+    BusyWeeks { start: NaiveDate, end: NaiveDate, peak: i32 },
+    /// Consecutive days that all have commits
+    NoBreak { start: NaiveDate, end: NaiveDate },
+}
+
+impl Finding {
+    pub fn start(&self) -> NaiveDate {
+        match *self {
+            Finding::BusyWeeks { start, .. } | Finding::NoBreak { start, .. } => start,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match *self {
+            Finding::BusyWeeks { start, end, peak } => format!(
+                "Intense stretch {} - {}: up to {} commits a week",
+                start.format("%b %-d"), end.format("%b %-d"), peak
+            ),
+            Finding::NoBreak { start, end } => format!(
+                "No day off {} - {} ({} days)",
+                start.format("%b %-d"), end.format("%b %-d"), (end - start).num_days() + 1
+            ),
+        }
+    }
+}
+
+pub fn findings(activity: &Activity) -> Vec<Finding> {
+    let (Some(&first), Some(&last)) = (activity.commits.keys().min(), activity.commits.keys().max()) else {
+        return Vec::new();
+    };
+    let count = |date: NaiveDate| activity.commits.get(&date).copied().unwrap_or(0);
+
+    let mut findings = Vec::new();
+    let mut busy: Option<(NaiveDate, NaiveDate, i32)> = None;
+    let mut streak_start: Option<NaiveDate> = None;
+    let mut window: i32 = (0..7).map(|offset| count(first + Duration::days(offset))).sum();
+
+    let mut date = first;
+    while date <= last {
+        // Busy weeks, merging overlapping windows into one period
+        let window_end = date + Duration::days(6);
+        if window >= BUSY_WEEK_COMMITS {
+            busy = match busy {
+                Some((start, _, peak)) => Some((start, window_end, peak.max(window))),
+                None => Some((date, window_end, window)),
+            };
+        } else if let Some((start, end, peak)) = busy.take() {
+            findings.push(Finding::BusyWeeks { start, end, peak });
+        }
+        window += count(window_end + Duration::days(1)) - count(date);
+
+        // Stretches without a single day off
+        if count(date) > 0 {
+            streak_start.get_or_insert(date);
+        } else if let Some(start) = streak_start.take() {
+            push_no_break(&mut findings, start, date - Duration::days(1));
+        }
+
+        date += Duration::days(1);
+    }
+
+    if let Some((start, end, peak)) = busy {
+        findings.push(Finding::BusyWeeks { start, end, peak });
+    }
+    if let Some(start) = streak_start {
+        push_no_break(&mut findings, start, last);
+    }
+
+    findings.sort_by_key(|finding| finding.start());
+    findings
+}
+
+fn push_no_break(findings: &mut Vec<Finding>, start: NaiveDate, end: NaiveDate) {
+    if (end - start).num_days() + 1 >= NO_BREAK_DAYS {
+        findings.push(Finding::NoBreak { start, end });
+    }
+}
+
+/// Annotation lines for the summary of one year
+pub fn year_notes(findings: &[Finding], year: i32) -> Vec<String> {
+    let year_findings: Vec<&Finding> = findings.iter()
+        .filter(|finding| finding.start().year() == year)
+        .collect();
+
+    let mut notes: Vec<String> = year_findings.iter()
+        .take(MAX_FINDINGS_PER_YEAR)
+        .map(|finding| finding.describe())
+        .collect();
+    if year_findings.len() > MAX_FINDINGS_PER_YEAR {
+        notes.push(format!("...and {} more intense periods", year_findings.len() - MAX_FINDINGS_PER_YEAR));
+    }
+    notes
+}
@@ -0,0 +1,192 @@
+use std::io::Write;
+
+use crate::error::{Error, Result};
+
+const SIGNATURE: &[u8; 4] = b"wOF2";
+const HEADER_SIZE: usize = 48;
+/// Flag of a table directory entry whose tag follows it, instead of being
+/// one of the known tags by index
+const EXPLICIT_TAG: u8 = 63;
+/// Transform version of `glyf` and `loca` stored as they are
+const NULL_TRANSFORM: u8 = 3 << 6;
+
+/// A table of an sfnt font
+struct Table<'a> {
+    tag: [u8; 4],
+    data: &'a [u8],
+}
+
+/// Packs a TrueType or OpenType font into a WOFF2 file, for `@font-face`
+/// rules. Every table is stored untransformed, only the Brotli compression
+/// of the whole font shrinks it.
+pub fn encode(sfnt: &[u8]) -> Result<Vec<u8>> {
+    let invalid = || Error::Input("The font to embed is not a TrueType or OpenType font".to_string());
+    let read_u32 = |offset: usize| -> Result<u32> {
+        let bytes = sfnt.get(offset..offset + 4).ok_or_else(invalid)?;
+        Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+    };
+
+    let flavor = read_u32(0)?;
+    let num_tables = sfnt.get(4..6).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).ok_or_else(invalid)?;
+    let mut tables = Vec::new();
+    for i in 0..num_tables as usize {
+        let record = 12 + 16 * i;
+        let tag = sfnt.get(record..record + 4).ok_or_else(invalid)?.try_into().unwrap();
+        let offset = read_u32(record + 8)? as usize;
+        let length = read_u32(record + 12)? as usize;
+        let data = sfnt.get(offset..offset + length).ok_or_else(invalid)?;
+        tables.push(Table { tag, data });
+    }
+    // Decoders expect `loca` right after `glyf`, the other tables keep
+    // their order by tag
+    tables.sort_by_key(|table| table.tag);
+    if let Some(loca) = tables.iter().position(|table| &table.tag == b"loca") {
+        let loca = tables.remove(loca);
+        let glyf = tables.iter().position(|table| &table.tag == b"glyf").ok_or_else(invalid)?;
+        tables.insert(glyf + 1, loca);
+    }
+
+    let mut directory = Vec::new();
+    let mut stream = Vec::new();
+    for table in &tables {
+        let transform = if &table.tag == b"glyf" || &table.tag == b"loca" { NULL_TRANSFORM } else { 0 };
+        directory.push(EXPLICIT_TAG | transform);
+        directory.extend_from_slice(&table.tag);
+        write_base128(&mut directory, table.data.len() as u32);
+        stream.extend_from_slice(table.data);
+    }
+    let mut compressed = Vec::new();
+    {
+        let mut compressor = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        compressor.write_all(&stream)
+            .map_err(|err| Error::Output(format!("Failed to compress the font: {}", err)))?;
+    }
+
+    let sfnt_size = 12 + 16 * tables.len() + tables.iter().map(|table| padded(table.data.len())).sum::<usize>();
+    let length = padded(HEADER_SIZE + directory.len() + compressed.len());
+    let mut woff2 = Vec::with_capacity(length);
+    woff2.extend_from_slice(SIGNATURE);
+    woff2.extend_from_slice(&flavor.to_be_bytes());
+    woff2.extend_from_slice(&(length as u32).to_be_bytes());
+    woff2.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+    woff2.extend_from_slice(&0u16.to_be_bytes());
+    woff2.extend_from_slice(&(sfnt_size as u32).to_be_bytes());
+    woff2.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    // Version 1.0, without metadata or private data
+    woff2.extend_from_slice(&1u16.to_be_bytes());
+    woff2.extend_from_slice(&0u16.to_be_bytes());
+    woff2.extend_from_slice(&[0; 20]);
+    woff2.extend_from_slice(&directory);
+    woff2.extend_from_slice(&compressed);
+    woff2.resize(length, 0);
+    Ok(woff2)
+}
+
+/// Length rounded up to the 4-byte alignment of sfnt tables
+fn padded(length: usize) -> usize {
+    (length + 3) & !3
+}
+
+/// Appends the number as a UIntBase128, seven bits a byte with the high bit
+/// set on all but the last
+fn write_base128(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    /// An sfnt with the given tables, without checksums
+    fn sfnt(tables: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut font = vec![0, 1, 0, 0];
+        font.extend_from_slice(&(tables.len() as u16).to_be_bytes());
+        font.extend_from_slice(&[0; 6]);
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, data) in tables {
+            font.extend_from_slice(*tag);
+            font.extend_from_slice(&[0; 4]);
+            font.extend_from_slice(&(offset as u32).to_be_bytes());
+            font.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            offset += padded(data.len());
+        }
+        for (_, data) in tables {
+            font.extend_from_slice(data);
+            font.resize(padded(font.len()), 0);
+        }
+        font
+    }
+
+    fn read_base128(data: &[u8], position: &mut usize) -> u32 {
+        let mut value = 0;
+        loop {
+            let byte = data[*position];
+            *position += 1;
+            value = value << 7 | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return value;
+            }
+        }
+    }
+
+    #[test]
+    fn base128_uses_as_few_bytes_as_needed() {
+        let mut out = Vec::new();
+        write_base128(&mut out, 0);
+        write_base128(&mut out, 127);
+        write_base128(&mut out, 128);
+        write_base128(&mut out, 63 * 128 * 128 + 5);
+        assert_eq!(out, [0x00, 0x7f, 0x81, 0x00, 0xbf, 0x80, 0x05]);
+    }
+
+    #[test]
+    fn tables_round_trip_through_the_compressed_stream() {
+        let head = [1u8; 54];
+        let glyf = b"outlines of the glyphs";
+        let loca = [0u8, 0, 0, 11, 0, 22];
+        let woff2 = encode(&sfnt(&[(b"glyf", glyf), (b"head", &head), (b"loca", &loca)])).unwrap();
+
+        assert_eq!(&woff2[0..4], SIGNATURE);
+        assert_eq!(&woff2[4..8], &[0, 1, 0, 0]);
+        assert_eq!(u32::from_be_bytes(woff2[8..12].try_into().unwrap()) as usize, woff2.len());
+        assert_eq!(woff2.len() % 4, 0);
+        assert_eq!(u16::from_be_bytes([woff2[12], woff2[13]]), 3);
+        let sfnt_size = u32::from_be_bytes(woff2[16..20].try_into().unwrap());
+        assert_eq!(sfnt_size as usize, 12 + 16 * 3 + 24 + 56 + 8);
+
+        let mut position = HEADER_SIZE;
+        let mut entries = Vec::new();
+        for _ in 0..3 {
+            let flags = woff2[position];
+            let tag = woff2[position + 1..position + 5].to_vec();
+            position += 5;
+            entries.push((flags, tag, read_base128(&woff2, &mut position)));
+        }
+        // loca follows glyf, and both are left untransformed
+        assert_eq!(entries, [
+            (EXPLICIT_TAG | NULL_TRANSFORM, b"glyf".to_vec(), glyf.len() as u32),
+            (EXPLICIT_TAG | NULL_TRANSFORM, b"loca".to_vec(), loca.len() as u32),
+            (EXPLICIT_TAG, b"head".to_vec(), head.len() as u32),
+        ]);
+
+        let compressed_size = u32::from_be_bytes(woff2[20..24].try_into().unwrap()) as usize;
+        let mut stream = Vec::new();
+        brotli::Decompressor::new(&woff2[position..position + compressed_size], 4096).read_to_end(&mut stream).unwrap();
+        assert_eq!(stream, [&glyf[..], &loca, &head].concat());
+    }
+
+    #[test]
+    fn truncated_fonts_are_rejected() {
+        let font = sfnt(&[(b"head", &[1; 54])]);
+        assert!(encode(&font[..40]).is_err());
+        assert!(encode(b"wOF").is_err());
+    }
+}